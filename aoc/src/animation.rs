@@ -0,0 +1,211 @@
+use std::{
+    io::{self, IsTerminal, Stdout, Write},
+    thread,
+    time::Duration,
+};
+
+use clap::Args;
+
+/// `--animate` and `--fps`, for the days that simulate a grid step by step.
+#[derive(Args, Debug, Clone)]
+pub struct AnimateOptions {
+    /// Draw every step of the simulation, redrawing in place when stdout is a terminal and
+    /// printing one frame after another otherwise
+    #[clap(long)]
+    pub animate: bool,
+    /// Frames per second when redrawing in place
+    #[clap(long, default_value_t = 10.0, value_parser = parse_fps)]
+    pub fps: f64,
+}
+
+/// A frame rate above zero, like `10` or `2.5`, slow enough only that one frame's time still
+/// fits in a `Duration`.
+pub fn parse_fps(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(fps) if fps.is_finite() && fps > 0.0 => Duration::try_from_secs_f64(1.0 / fps)
+            .map(|_| fps)
+            .map_err(|_| format!("{text:?} frames per second holds each frame too long")),
+        _ => Err(format!("{text:?} is not a frame rate above zero")),
+    }
+}
+
+impl AnimateOptions {
+    /// The animation to draw frames on, or `None` without `--animate`.
+    pub fn animation(&self) -> Option<Animation<Stdout>> {
+        let out = io::stdout();
+        let in_place = out.is_terminal();
+        self.animate.then(|| Animation::new(out, in_place, self.fps))
+    }
+}
+
+/// Draws frames of text one after another. In place, each frame is drawn over the last with
+/// ANSI cursor movements and held for one frame's time. Otherwise frames are printed in turn,
+/// separated by a blank line, as fast as they come.
+///
+/// The simulations only hand frames over, so a write error is kept and returned by `finish`
+/// instead of from every frame, and no more frames are drawn after it. A hidden cursor is shown
+/// again on drop too, so a failed write or input never leaves the terminal without one.
+pub struct Animation<W: Write> {
+    out: W,
+    in_place: bool,
+    delay: Duration,
+    /// Lines in the last frame drawn in place, which the next one moves back up over
+    lines: usize,
+    frames: usize,
+    cursor_hidden: bool,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Animation<W> {
+    pub fn new(out: W, in_place: bool, fps: f64) -> Self {
+        let delay = Duration::try_from_secs_f64(1.0 / fps).unwrap_or(Duration::MAX);
+        Self { out, in_place, delay, lines: 0, frames: 0, cursor_hidden: false, error: None }
+    }
+
+    /// Draw `frame`, a title line followed by the grid.
+    pub fn frame(&mut self, title: &str, grid: &str) {
+        if self.error.is_none() {
+            if let Err(error) = self.draw(title, grid) {
+                self.error = Some(error);
+            }
+        }
+    }
+
+    fn draw(&mut self, title: &str, grid: &str) -> io::Result<()> {
+        if self.in_place {
+            if self.frames == 0 {
+                // Hide the cursor while drawing
+                self.cursor_hidden = true;
+                write!(self.out, "\x1b[?25l")?;
+            } else {
+                // Back up to the start of the last frame and clear everything below it
+                write!(self.out, "\x1b[{}F\x1b[J", self.lines)?;
+            }
+        } else if self.frames > 0 {
+            writeln!(self.out)?;
+        }
+        writeln!(self.out, "{title}")?;
+        write!(self.out, "{grid}")?;
+        if !grid.is_empty() && !grid.ends_with('\n') {
+            writeln!(self.out)?;
+        }
+        self.out.flush()?;
+        self.lines = 1 + grid.lines().count();
+        self.frames += 1;
+        if self.in_place {
+            thread::sleep(self.delay);
+        }
+        Ok(())
+    }
+
+    /// How many frames have been drawn.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Leave the last frame on screen and report the first write that failed, if any.
+    pub fn finish(mut self) -> io::Result<()> {
+        let shown = self.show_cursor();
+        self.error.take().map_or(shown, Err)
+    }
+
+    fn show_cursor(&mut self) -> io::Result<()> {
+        if self.cursor_hidden {
+            self.cursor_hidden = false;
+            write!(self.out, "\x1b[?25h")?;
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for Animation<W> {
+    fn drop(&mut self) {
+        // Nothing is left to report a failure to here, so a write error is only kept by `finish`
+        let _ = self.show_cursor();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printed_frames() {
+        let mut out = Vec::new();
+        let mut animation = Animation::new(&mut out, false, 1000.0);
+        animation.frame("Step 1", "#.\n..\n");
+        animation.frame("Step 2", ".#\n..");
+        assert_eq!(animation.frames(), 2);
+        animation.finish().unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "Step 1\n#.\n..\n\nStep 2\n.#\n..\n");
+    }
+
+    #[test]
+    fn frames_in_place() {
+        let mut out = Vec::new();
+        let mut animation = Animation::new(&mut out, true, 1000.0);
+        animation.frame("Step 1", "#.\n..\n");
+        animation.frame("Step 2", ".#\n..\n");
+        animation.finish().unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "\x1b[?25lStep 1\n#.\n..\n\x1b[3F\x1b[JStep 2\n.#\n..\n\x1b[?25h");
+    }
+
+    /// Takes `limit` bytes and then fails every write.
+    struct Failing {
+        written: Vec<u8>,
+        limit: usize,
+    }
+
+    impl Write for Failing {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            if self.written.len() + bytes.len() > self.limit {
+                return Err(io::Error::new(io::ErrorKind::Other, "full"));
+            }
+            self.written.extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn cursor_comes_back() {
+        // Dropped partway through, as when an input fails to parse
+        let mut out = Vec::new();
+        let mut animation = Animation::new(&mut out, true, 1000.0);
+        animation.frame("Step 1", "#.\n");
+        drop(animation);
+        assert!(out.ends_with(b"\x1b[?25h"));
+
+        // A failed frame is reported, and the cursor still comes back once there is room
+        let mut out = Failing { written: Vec::new(), limit: 16 };
+        let mut animation = Animation::new(&mut out, true, 1000.0);
+        animation.frame("Step 1", "#.\n");
+        animation.frame("Step 2", "#.\n");
+        assert_eq!(animation.frames(), 1);
+        animation.out.limit = usize::MAX;
+        assert!(animation.finish().is_err());
+        assert!(out.written.ends_with(b"\x1b[?25h"));
+
+        // Nothing drawn means the cursor was never hidden
+        let mut out = Vec::new();
+        Animation::new(&mut out, true, 1000.0).finish().unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn frame_rates() {
+        assert_eq!(parse_fps("2.5"), Ok(2.5));
+        assert!(parse_fps("0").is_err());
+        assert!(parse_fps("-1").is_err());
+        assert!(parse_fps("inf").is_err());
+        assert!(parse_fps("fast").is_err());
+        assert!(parse_fps("1e-30").is_err());
+        assert!(parse_fps("1e-320").is_err());
+        assert_eq!(Animation::new(Vec::new(), true, 1e-30).delay, Duration::MAX);
+    }
+}
@@ -1,18 +1,20 @@
-use std::path::PathBuf;
-
 use clap::Parser;
 
+use crate::utils::Inputs;
+
 use super::{CommandImpl, DynError};
 
 #[derive(Parser, Debug)]
 pub struct Day0 {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
 }
 
 impl CommandImpl for Day0 {
     fn main(&mut self) -> Result<(), DynError> {
-        println!("EX: {:?}", self.input);
-        Ok(())
+        self.input.for_each(|bytes| {
+            println!("EX: {} bytes", bytes.len());
+            Ok(())
+        })
     }
 }
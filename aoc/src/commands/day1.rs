@@ -1,441 +1,424 @@
-use std::fs::{self, File};
-use std::io::{Read, BufReader};
-use std::path::{Path, PathBuf};
+use std::{collections::VecDeque, fs, path::PathBuf};
 
 use clap::Parser;
 
+use crate::utils::{Inputs, ParseError};
+
 use super::{CommandImpl, DynError};
 
+/// The spelled-out digits counted by part two when no `--words` file is given.
+const ENGLISH: [(&str, u8); 9] = [
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+];
+
 #[derive(Parser, Debug)]
 pub struct Day1 {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+
+    /// Vocabulary file with one `word digit` pair per line, replacing the English number words
+    #[clap(long)]
+    words: Option<PathBuf>,
 }
 
-impl Day1 {
-    pub fn part_one(&self) -> Result<(), DynError> {
-        let input = fs::read_to_string(self.input.as_path()).unwrap();
-        // println!("EX: {:?}", self.input);
-        let mut sum: u64 = 0;
-
-        input.lines().for_each(|line| {
-            // println!("Line: {}", line);
-            let mut begin_idx = 0;
-            let mut end_idx = line.len() - 1;
-            let mut first_digit: Option<char> = None;
-            let mut last_digit: Option<char> = None;
-
-            loop {
-                let char = line.chars().nth(begin_idx).unwrap();
-                if char.is_numeric() {
-                    first_digit = Some(char);
-                    break;
-                } else {
-                    begin_idx += 1;
+/// Aho-Corasick automaton over a vocabulary, with the failure links folded into the transition
+/// table so every byte is a single lookup and overlapping words like "twone" are all found.
+#[derive(Debug)]
+struct Automaton {
+    transitions: Vec<[usize; 256]>,
+    /// The value and length of the longest word ending in each state, if any
+    output: Vec<Option<(u8, usize)>>,
+    /// The length of the longest word
+    longest: usize,
+}
+
+impl Automaton {
+    fn new<'a>(words: impl Iterator<Item = (&'a [u8], u8)>) -> Self {
+        let mut transitions = vec![[0; 256]];
+        let mut output = vec![None];
+        let mut longest = 0;
+
+        // Build the trie, where a zero transition means there is no child yet
+        for (word, value) in words {
+            let mut state = 0;
+            for &byte in word {
+                if transitions[state][byte as usize] == 0 {
+                    transitions.push([0; 256]);
+                    output.push(None);
+                    transitions[state][byte as usize] = transitions.len() - 1;
                 }
+                state = transitions[state][byte as usize];
             }
-            loop {
-                let char = line.chars().nth(end_idx).unwrap();
-                if char.is_numeric() {
-                    last_digit = Some(char);
-                    break;
+            output[state] = Some((value, word.len()));
+            longest = longest.max(word.len());
+        }
+
+        // Walk the trie breadth first, so a state's failure state is complete before its own
+        // missing transitions are copied from it
+        let mut failure = vec![0; transitions.len()];
+        let mut queue = VecDeque::from([0]);
+        while let Some(state) = queue.pop_front() {
+            for byte in 0..256 {
+                let child = transitions[state][byte];
+                let fallback = if state == 0 { 0 } else { transitions[failure[state]][byte] };
+                if child == 0 {
+                    transitions[state][byte] = fallback;
                 } else {
-                    end_idx -= 1;
+                    failure[child] = fallback;
+                    output[child] = output[child].or(output[fallback]);
+                    queue.push_back(child);
                 }
             }
+        }
 
-            let first: u64 = first_digit.unwrap().to_digit(10).unwrap() as u64;
-            let last: u64 = last_digit.unwrap().to_digit(10).unwrap() as u64;
+        Self { transitions, output, longest }
+    }
+}
 
-            // println!("Digits: {}{}", first_digit.unwrap(), last_digit.unwrap());
+/// The first and last digits of one line, with and without spelled-out words.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct LineDigits {
+    first_literal: Option<u8>,
+    last_literal: Option<u8>,
+    first: Option<u8>,
+    last: Option<u8>,
+}
 
-            sum += first * 10 + last;
-        });
+/// Finds digits and spelled-out digits in calibration lines. Scanning forward finds the digit
+/// that starts first and scanning backward over the reversed words finds the one that starts
+/// last, so each scan can stop early.
+#[derive(Debug)]
+pub struct Recognizer {
+    forward: Automaton,
+    backward: Automaton,
+}
 
-        // println!("Sum: {}", sum);
-        Ok(())
-    }
+/// Calibration sums for both parts, each failing on its first line without a digit.
+#[derive(Debug)]
+pub struct Calibration {
+    pub part_one: Result<u64, ParseError>,
+    pub part_two: Result<u64, ParseError>,
+}
 
-    pub fn part_two(&self) -> Result<(), DynError> {
-        let input = fs::read_to_string(self.input.as_path()).unwrap();
-        let mut sum: u64 = 0;
+impl Recognizer {
+    pub fn new(words: &[(Vec<u8>, u8)]) -> Result<Self, ParseError> {
+        for (idx, (word, value)) in words.iter().enumerate() {
+            let word_text = String::from_utf8_lossy(word);
+            if word.is_empty() {
+                return Err(ParseError::new(String::from("words must not be empty")));
+            }
+            if *value > 9 {
+                let msg = format!("\"{word_text}\" must stand for a single digit, not {value}");
+                return Err(ParseError::new(msg));
+            }
+            if words[..idx].iter().any(|(other, _)| other == word) {
+                return Err(ParseError::new(format!("\"{word_text}\" is listed twice")));
+            }
+        }
 
-        input.lines().for_each(|line| {
-            let mut test_idx = 0;
+        let reversed: Vec<(Vec<u8>, u8)> = words
+            .iter()
+            .map(|(word, value)| (word.iter().rev().copied().collect(), *value))
+            .collect();
 
-            let mut line_sum: u64 = loop {
-                let char = line.chars().nth(test_idx).unwrap();
-                match char {
-                    '0'..='9' => {
-                        break char as u64 - 48;
-                    }
-                    't' => {
-                        // two, three
-                        if test_idx + 2 < line.len() {
-                            if &line[test_idx..test_idx + 3] == "two" {
-                                break 2;
-                            } else if (test_idx + 4 < line.len())
-                                && &line[test_idx..test_idx + 5] == "three"
-                            {
-                                break 3;
-                            }
-                        } else {
-                            test_idx += 1;
-                            continue;
-                        }
-                    }
-                    'f' => {
-                        // four, five
-                        if (test_idx + 3) < line.len() {
-                            if &line[test_idx..test_idx + 4] == "four" {
-                                break 4;
-                            } else if &line[test_idx..test_idx + 4] == "five" {
-                                break 5;
-                            }
-                        } else {
-                            test_idx += 1;
-                            continue;
-                        }
-                    }
-                    's' => {
-                        // six, seven
-                        if (test_idx + 2 < line.len()) && &line[test_idx..test_idx + 3] == "six" {
-                            break 6;
-                        } else if (test_idx + 4 < line.len())
-                            && &line[test_idx..test_idx + 5] == "seven"
-                        {
-                            break 7;
-                        }
-                    }
-                    'e' => {
-                        // eight
-                        if (test_idx + 4 < line.len()) && &line[test_idx..test_idx + 5] == "eight" {
-                            break 8;
-                        }
-                    }
-                    'n' => {
-                        // nine
-                        if (test_idx + 3 < line.len()) && &line[test_idx..test_idx + 4] == "nine" {
-                            break 9;
-                        }
-                    }
-                    'o' => {
-                        // one
-                        if &line[test_idx..test_idx + 3] == "one" {
-                            break 1;
-                        }
-                    }
-                    _ => {}
-                }
+        Ok(Self {
+            forward: Automaton::new(words.iter().map(|(word, value)| (&word[..], *value))),
+            backward: Automaton::new(reversed.iter().map(|(word, value)| (&word[..], *value))),
+        })
+    }
 
-                test_idx += 1;
+    /// Recognizes the English number words "one" to "nine".
+    pub fn english() -> Self {
+        let words: Vec<(Vec<u8>, u8)> =
+            ENGLISH.iter().map(|(word, value)| (word.as_bytes().to_vec(), *value)).collect();
+        Self::new(&words).expect("English words are valid")
+    }
 
-                if test_idx == line.len() {
-                    panic!("No first digit found");
+    /// Read a vocabulary with one `word digit` pair per line. Blank lines and lines starting
+    /// with `#` are skipped.
+    pub fn from_words_file(text: &[u8]) -> Result<Self, ParseError> {
+        let mut words = Vec::new();
+        for (line_idx, line) in text.split(|&byte| byte == b'\n').enumerate() {
+            let mut fields = line.split(u8::is_ascii_whitespace).filter(|field| !field.is_empty());
+            let (word, value) = match (fields.next(), fields.next(), fields.next()) {
+                (None, ..) => continue,
+                (Some(word), ..) if word.starts_with(b"#") => continue,
+                (Some(word), Some(value), None) => (word, value),
+                _ => {
+                    let msg = format!("line {} must be a word and a digit", line_idx + 1);
+                    return Err(ParseError::new(msg));
                 }
             };
+            let value = match value {
+                [digit @ b'0'..=b'9'] => digit - b'0',
+                _ => {
+                    let msg = format!("line {} must give a single digit", line_idx + 1);
+                    return Err(ParseError::new(msg));
+                }
+            };
+            words.push((word.to_vec(), value));
+        }
+        Self::new(&words)
+    }
 
-            test_idx = line.len() - 1;
-
-            let last_digit = loop {
-                let char = line.chars().nth(test_idx).unwrap();
-                match char {
-                    '0'..='9' => {
-                        break char as u64 - 48;
-                    }
-                    't' => {
-                        // two, three
-                        if (test_idx + 2 < line.len()) && &line[test_idx..test_idx + 3] == "two" {
-                            break 2;
-                        } else if (test_idx + 4 < line.len())
-                            && &line[test_idx..test_idx + 5] == "three"
-                        {
-                            break 3;
-                        }
-                    }
-                    'f' => {
-                        // four, five
-                        // println!("end_idx: {} len: {}", end_idx, line.len());
-                        if (test_idx + 3) < line.len() {
-                            if &line[test_idx..test_idx + 4] == "four" {
-                                break 4;
-                            } else if &line[test_idx..test_idx + 4] == "five" {
-                                break 5;
-                            }
-                        }
-                    }
-                    's' => {
-                        // six, seven
-                        if (test_idx + 2) < line.len() {
-                            if &line[test_idx..test_idx + 3] == "six" {
-                                break 6;
-                            } else if (test_idx + 4) < line.len()
-                                && &line[test_idx..test_idx + 5] == "seven"
-                            {
-                                break 7;
-                            }
-                        }
-                    }
-                    'e' => {
-                        // eight
-                        if (test_idx + 4) < line.len() && &line[test_idx..test_idx + 5] == "eight" {
-                            break 8;
-                        }
-                    }
-                    'n' => {
-                        // nine
-                        if (test_idx + 3) < line.len() && &line[test_idx..test_idx + 4] == "nine" {
-                            break 9;
-                        }
-                    }
-                    'o' => {
-                        // one
-                        if (test_idx + 2 < line.len()) && &line[test_idx..test_idx + 3] == "one" {
-                            break 1;
-                        }
+    /// The first literal digit met while feeding `line`, and the digit of any kind that starts
+    /// first, or with `by_end` the one that ends first. Either way the longest word wins a tie.
+    fn scan<'a>(
+        automaton: &Automaton,
+        line: impl Iterator<Item = &'a u8>,
+        by_end: bool,
+    ) -> (Option<u8>, Option<u8>) {
+        let mut state = 0;
+        let mut literal = None;
+        // The best match so far, as (start, length, digit)
+        let mut best: Option<(usize, usize, u8)> = None;
+        for (end, &byte) in line.enumerate() {
+            // A match ending here or later cannot start before `end + 1 - longest`
+            let settled =
+                best.is_some_and(|(start, ..)| by_end || end >= start + automaton.longest);
+            if literal.is_some() && settled {
+                break;
+            }
+            state = automaton.transitions[state][byte as usize];
+            let digit = (byte as char).to_digit(10).map(|digit| digit as u8);
+            literal = literal.or(digit);
+            let matches = automaton.output[state].into_iter().chain(digit.map(|digit| (digit, 1)));
+            for (value, length) in matches {
+                let start = end + 1 - length;
+                let better = match best {
+                    None => true,
+                    Some((best_start, best_length, _)) => {
+                        !by_end
+                            && (start < best_start || start == best_start && length > best_length)
                     }
-                    _ => {}
+                };
+                if better {
+                    best = Some((start, length, value));
                 }
+            }
+        }
+        (literal, best.map(|(.., value)| value))
+    }
+
+    fn line_digits(&self, line: &[u8]) -> LineDigits {
+        let (first_literal, first) = Self::scan(&self.forward, line.iter(), false);
+        // Reversed, the digit starting last in the line is the one ending first
+        let (last_literal, last) = Self::scan(&self.backward, line.iter().rev(), true);
+        LineDigits { first_literal, last_literal, first, last }
+    }
 
-                if test_idx == 0 {
-                    panic!("No last digit found");
+    pub fn calibrate(&self, input: &[u8]) -> Calibration {
+        let mut part_one = Ok(0);
+        let mut part_two = Ok(0);
+
+        let input = input.strip_suffix(b"\n").unwrap_or(input);
+        for (line_idx, line) in input.split(|&byte| byte == b'\n').enumerate() {
+            let digits = self.line_digits(line);
+            for (sum, first, last) in [
+                (&mut part_one, digits.first_literal, digits.last_literal),
+                (&mut part_two, digits.first, digits.last),
+            ] {
+                if let Ok(total) = sum {
+                    *sum = match first.zip(last) {
+                        Some((first, last)) => Ok(*total + (first * 10 + last) as u64),
+                        None => Err(no_digit(line_idx)),
+                    };
                 }
+            }
+        }
 
-                test_idx -= 1;
-            };
+        Calibration { part_one, part_two }
+    }
+}
 
-            // println!("Digits: {}{}", first_digit.unwrap(), last_digit.unwrap());
-            line_sum += last_digit as u64 * 10;
-            sum += line_sum
-        });
+fn no_digit(line_idx: usize) -> ParseError {
+    ParseError::new(format!("no digit on line {}", line_idx + 1))
+}
 
-        println!("Sum: {}", sum);
+impl Day1 {
+    pub fn part_one(input: &[u8]) -> Result<u64, ParseError> {
+        Recognizer::english().calibrate(input).part_one
+    }
 
+    pub fn part_two(input: &[u8]) -> Result<u64, ParseError> {
         // 54249 is too high
-
-        Ok(())
+        Recognizer::english().calibrate(input).part_two
     }
+}
 
-    pub fn part_one_fast(&self) -> Result<(), DynError> {
-        let f = File::open(self.input.as_path()).unwrap();
-        let mut buffer = vec![];
-        let mut reader = BufReader::new(f);
-        reader.read_to_end(buffer.as_mut()).unwrap();
-        // f.read_to_end(&mut buffer).unwrap();
-        let file_length: usize = buffer.len();
-
-        let mut sum: u32 = 0;
-        let mut read_idx: usize = 0;
-        let mut last_digit = 0;
-
-        loop {
-            // Find the first digit
-            loop {
-                if (buffer[read_idx] & 0b1000000) == 0 {
-                    sum += (buffer[read_idx] as u32 - 48) * 10;
-                    break;
+impl CommandImpl for Day1 {
+    fn main(&mut self) -> Result<(), DynError> {
+        let recognizer = match &self.words {
+            Some(path) => Recognizer::from_words_file(&fs::read(path)?)?,
+            None => Recognizer::english(),
+        };
+
+        self.input.for_each(|bytes| {
+            let calibration = recognizer.calibrate(&bytes);
+            for (part, sum) in [("Day1a", calibration.part_one), ("Day1b", calibration.part_two)] {
+                match sum {
+                    Ok(sum) => println!("{part}: {sum}"),
+                    Err(err) => println!("{part}: {err}"),
                 }
-                read_idx += 1;
             }
+            Ok(())
+        })
+    }
+}
 
-            // Find the end of the line
-            loop {
-                if buffer[read_idx] == b'\n' {
-                    break;
-                }
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const PART_ONE_EXAMPLE: &str = "\
+1abc2
+pqr3stu8vwx
+a1b2c3d4e5f
+treb7uchet
+";
+    const PART_TWO_EXAMPLE: &str = "\
+two1nine
+eightwothree
+abcone2threexyz
+xtwone3four
+4nineeightseven2
+zoneight234
+7pqrstsixteen
+";
+    const GERMAN: &str = "\
+# Zahlwörter
+eins 1
+zwei 2
+drei 3
+vier 4
+fünf 5
+sechs 6
+sieben 7
+acht 8
+neun 9
+";
+
+    fn part_one(input: &[u8]) -> u64 {
+        parse_part_one(input).unwrap()
+    }
 
-                last_digit = if (buffer[read_idx] & 0b1000000) == 0 { buffer[read_idx] - 48 } else { last_digit };
+    fn part_two(input: &[u8]) -> u64 {
+        parse_part_two(input).unwrap()
+    }
 
-                read_idx += 1;
-            }
+    fn parse_part_one(input: &[u8]) -> Result<u64, ParseError> {
+        Day1::part_one(input)
+    }
 
-            sum += last_digit as u32;
-            // last_digit = 0;
-            read_idx += 1;
-            if read_idx == file_length as usize {
-                break;
-            }
+    fn parse_part_two(input: &[u8]) -> Result<u64, ParseError> {
+        Day1::part_two(input)
+    }
+
+    fn parse_words(input: &[u8]) -> Result<Recognizer, ParseError> {
+        Recognizer::from_words_file(input)
+    }
+
+    puzzle_tests! {
+        part_one {
+            example statement: PART_ONE_EXAMPLE => 142;
+        }
+        part_two {
+            example statement: PART_TWO_EXAMPLE => 281;
+            example overlapping_words: "twone\neightwo\noneight\n" => 21 + 82 + 18;
+            example no_trailing_newline: "7pqrstsixteen" => 76;
         }
+    }
 
-        println!("Sum: {}", sum);
+    #[test]
+    fn both_parts_in_one_pass() {
+        let calibration = Recognizer::english().calibrate(PART_ONE_EXAMPLE.as_bytes());
+        assert_eq!(calibration.part_one.unwrap(), 142);
+        assert_eq!(calibration.part_two.unwrap(), 142);
 
-        Ok(())
+        // "eightwothree" has no literal digit, which only fails part one
+        let calibration = Recognizer::english().calibrate(PART_TWO_EXAMPLE.as_bytes());
+        assert!(calibration.part_one.is_err());
+        assert_eq!(calibration.part_two.unwrap(), 281);
     }
 
-    pub fn part_two_fast(&self) -> Result<(), DynError> {
-        let f = File::open(self.input.as_path()).unwrap();
-        let mut buffer = vec![];
-        let mut reader = BufReader::new(f);
-        reader.read_to_end(buffer.as_mut()).unwrap();
-        let file_length: usize = buffer.len();
-
-        let mut sum: u32 = 0;
-        let mut read_idx: usize = 0;
-
-        loop {
-            // Find the first digit
-            loop {
-                if (buffer[read_idx] & 0b1000000) == 0 {
-                    sum += (buffer[read_idx] as u32 - 48) * 10;
-                    break;
-                } else if read_idx < file_length - 2 {
-                    let test_slice = &buffer[read_idx..read_idx + 3];
-                    let next_char = if read_idx < file_length - 3 { Some(buffer[read_idx + 3]) } else { None };
-                    let next_two_chars = if read_idx < file_length - 4 { Some(&buffer[read_idx + 3..read_idx+5]) } else { None };
-                    match test_slice {
-                        b"one" => {
-                            sum += 10;
-                            read_idx += 3;
-                            break;
-                        },
-                        b"two" => {
-                            sum += 20;
-                            read_idx += 3;
-                            break;
-                        },
-                        b"thr" => 
-                            if let Some(b"ee") = next_two_chars {
-                                sum += 30;
-                                read_idx += 5;
-                                break;
-                            }
-                        ,
-                        b"fou" => {
-                            if let Some(b'r') = next_char {
-                                sum += 40;
-                                read_idx += 4;
-                                break;
-                            }
-                        },
-                        b"fiv" => {
-                            if let Some(b'e') = next_char {
-                                sum += 50;
-                                read_idx += 4;
-                                break;
-                            }
-                        },
-                        b"six" => {
-                            sum += 60;
-                            read_idx += 3;
-                            break;
-                        },
-                        b"sev" => {
-                            if let Some(b"en") = next_two_chars {
-                                sum += 70;
-                                read_idx += 5;
-                                break;
-                            }
-                        },
-                        b"eig" => {
-                            if let Some(b"ht") = next_two_chars {
-                                sum += 80;
-                                read_idx += 5;
-                                break;
-                            }
-                        },
-                        b"nin" => {
-                            if let Some(b'e') = next_char {
-                                sum += 90;
-                                read_idx += 4;
-                                break;
-                            }
-                        },
-                        _ => {}
-                    }
-                } else {
-                    panic!()
-                }
+    #[test]
+    fn words_file_vocabulary() {
+        let recognizer = Recognizer::from_words_file(GERMAN.as_bytes()).unwrap();
+        let calibration = recognizer.calibrate("xzweinsx\nfünfacht\nsiebenzig3\n".as_bytes());
+        assert!(calibration.part_one.is_err());
+        assert_eq!(calibration.part_two.unwrap(), 21 + 58 + 73);
+    }
 
-                read_idx += 1;
-            }
+    #[test]
+    fn words_file_errors() {
+        for words in ["ten 10\n", "one 1\none 2\n", "one\n", "one 1 2\n", "one x\n"] {
+            assert!(Recognizer::from_words_file(words.as_bytes()).is_err(), "{words:?}");
+        }
+    }
 
-            // Find the end of the line
-            loop {
-                if buffer[read_idx] == b'\n' {
-                    break;
-                }
-                read_idx += 1;
-            }
+    /// Words that sit inside one another, where the first to start and the first to end differ.
+    const NESTED: [(&str, u8); 4] = [("seventeen", 1), ("seven", 7), ("even", 2), ("teen", 3)];
+
+    #[test]
+    fn nested_words() {
+        let words: Vec<(Vec<u8>, u8)> =
+            NESTED.iter().map(|(word, value)| (word.as_bytes().to_vec(), *value)).collect();
+        let recognizer = Recognizer::new(&words).unwrap();
+        // "even" ends first, but "seventeen" starts first
+        assert_eq!(recognizer.calibrate(b"xseventeenx\n").part_two.unwrap(), 13);
+        assert_eq!(recognizer.calibrate(b"5seventeen\n").part_two.unwrap(), 53);
+        // "even" starts inside "seven", so it is the last digit
+        assert_eq!(recognizer.calibrate(b"seven\n").part_two.unwrap(), 72);
+    }
 
-            // Find the last digit
-            let mut last_digit_idx = read_idx - 1;
-            loop {
-                if (buffer[last_digit_idx] & 0b1000000) == 0 {
-                    sum += buffer[last_digit_idx] as u32 - 48;
-                    break;
-                } else if last_digit_idx < file_length - 2 {
-                    let test_slice = &buffer[last_digit_idx..last_digit_idx + 3];
-                    let next_char = if last_digit_idx < file_length - 3 { Some(buffer[last_digit_idx + 3]) } else { None };
-                    let next_two_chars = if last_digit_idx < file_length - 4 { Some(&buffer[last_digit_idx + 3..last_digit_idx+5]) } else { None };
-                    match test_slice {
-                        b"one" => {
-                            sum += 1;
-                            break;
-                        },
-                        b"two" => {
-                            sum += 2;
-                            break;
-                        },
-                        b"thr" => 
-                            if let Some(b"ee") = next_two_chars {
-                                sum += 3;
-                                break;
-                            }
-                        ,
-                        b"fou" => {
-                            if let Some(b'r') = next_char {
-                                sum += 4;
-                                break;
-                            }
-                        },
-                        b"fiv" => {
-                            if let Some(b'e') = next_char {
-                                sum += 5;
-                                break;
-                            }
-                        },
-                        b"six" => {
-                            sum += 6;
-                            break;
-                        },
-                        b"sev" => {
-                            if let Some(b"en") = next_two_chars {
-                                sum += 7;
-                                break;
-                            }
-                        },
-                        b"eig" => {
-                            if let Some(b"ht") = next_two_chars {
-                                sum += 8;
-                                break;
-                            }
-                        },
-                        b"nin" => {
-                            if let Some(b'e') = next_char {
-                                sum += 9;
-                                break;
-                            }
-                        },
-                        _ => {}
-                    }
-                }
-                last_digit_idx -= 1;
-            }
+    /// The digit starting earliest in `line`, preferring the longest word starting at that byte.
+    fn naive_first(words: &[(&str, u8)], line: &[u8]) -> Option<u8> {
+        (0..line.len()).find_map(|start| naive_at(words, line, start))
+    }
 
-            read_idx += 1;
-            if read_idx == file_length as usize {
-                break;
-            }
-        }
+    /// The digit starting latest in `line`, preferring the longest word starting at that byte.
+    fn naive_last(words: &[(&str, u8)], line: &[u8]) -> Option<u8> {
+        (0..line.len()).rev().find_map(|start| naive_at(words, line, start))
+    }
+
+    fn naive_at(words: &[(&str, u8)], line: &[u8], start: usize) -> Option<u8> {
+        let words = words.iter().filter(|(word, _)| line[start..].starts_with(word.as_bytes()));
+        let word = words.max_by_key(|(word, _)| word.len()).map(|(_, value)| *value);
+        word.or_else(|| (line[start] as char).to_digit(10).map(|digit| digit as u8))
+    }
 
-        println!("Sum: {}", sum);
+    proptest! {
+        #[test]
+        fn automaton_matches_naive_search(line in "[onetwhrfuivsxg0-9]{0,24}") {
+            let digits = Recognizer::english().line_digits(line.as_bytes());
+            prop_assert_eq!(digits.first, naive_first(&ENGLISH, line.as_bytes()));
+            prop_assert_eq!(digits.last, naive_last(&ENGLISH, line.as_bytes()));
+        }
 
-        Ok(())
+        #[test]
+        fn nested_words_match_naive_search(line in "[sevnt0-9]{0,24}") {
+            let words: Vec<(Vec<u8>, u8)> =
+                NESTED.iter().map(|(word, value)| (word.as_bytes().to_vec(), *value)).collect();
+            let digits = Recognizer::new(&words).unwrap().line_digits(line.as_bytes());
+            prop_assert_eq!(digits.first, naive_first(&NESTED, line.as_bytes()));
+            prop_assert_eq!(digits.last, naive_last(&NESTED, line.as_bytes()));
+        }
     }
-}
 
-impl CommandImpl for Day1 {
-    fn main(&mut self) -> Result<(), DynError> {
-        self.part_two()
+    robustness_tests! {
+        parse_part_one: PART_ONE_EXAMPLE;
+        parse_part_two: PART_TWO_EXAMPLE;
+        parse_words: GERMAN;
     }
 }
@@ -1,212 +1,427 @@
-use std::path::PathBuf;
+use std::io::Write;
 
 use clap::Parser;
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::{
+    animation::{AnimateOptions, Animation},
+    render::{Frames, Render, RenderOptions, Rgb},
+    utils::{Inputs, ParseError},
+};
 
 use super::{CommandImpl, DynError};
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Direction {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
     North,
     East,
     South,
     West,
 }
 
-pub struct Solver {
-    reader: AsciiReader,
+impl Direction {
+    pub const ALL: [Self; 4] = [Self::North, Self::East, Self::South, Self::West];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::North => Self::South,
+            Self::East => Self::West,
+            Self::South => Self::North,
+            Self::West => Self::East,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct Position(Option<usize>);
+/// The sides of a tile its pipe opens onto, one bit per `Direction`. Ground opens onto none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pipe(u8);
+
+impl Pipe {
+    pub const GROUND: Self = Self(0);
+
+    fn between(a: Direction, b: Direction) -> Self {
+        Self(1 << a as u8 | 1 << b as u8)
+    }
+
+    /// The pipe drawn by `tile`, or `None` for the start and anything that is not a tile.
+    pub fn from_tile(tile: u8) -> Option<Self> {
+        use Direction::*;
+        Some(match tile {
+            b'|' => Self::between(North, South),
+            b'-' => Self::between(East, West),
+            b'L' => Self::between(North, East),
+            b'J' => Self::between(North, West),
+            b'7' => Self::between(South, West),
+            b'F' => Self::between(South, East),
+            b'.' => Self::GROUND,
+            _ => return None,
+        })
+    }
+
+    pub fn tile(self) -> u8 {
+        *b"|-LJ7F."
+            .iter()
+            .find(|&&tile| Self::from_tile(tile) == Some(self))
+            .expect("every pipe is drawn by a tile")
+    }
 
-impl Position {
-    fn step(&mut self, direction: Direction, width: usize, max_position: usize) {
-        if self.0.is_none() {
-            panic!("Cannot step from None position");
+    /// The pipe as a box-drawing line, for drawing the loop.
+    pub fn line(self) -> char {
+        match self.tile() {
+            b'|' => '│',
+            b'-' => '─',
+            b'L' => '└',
+            b'J' => '┘',
+            b'7' => '┐',
+            b'F' => '┌',
+            _ => ' ',
         }
+    }
 
-        let position = self.0.unwrap();
+    pub fn connects(self, direction: Direction) -> bool {
+        self.0 & 1 << direction as u8 != 0
+    }
 
-        self.0 = match direction {
-            Direction::North => {
-                if position < width {
-                    None
-                } else {
-                    Some(position - width)
-                }
-            },
-            Direction::East => {
-                if (position + 1) % width == 0 {
-                    None
-                } else {
-                    Some(position + 1)
-                }
-            },
-            Direction::South => {
-                if position + width > max_position {
-                    None
-                } else {
-                    Some(position + width)
-                }
-            },
-            Direction::West => {
-                if position % width == 0 {
-                    None
-                } else {
-                    Some(position - 1)
-                }
-            },
+    /// Where a walk heading `heading` goes next after entering this pipe, if the pipe lets it
+    /// in at all.
+    pub fn turn(self, heading: Direction) -> Option<Direction> {
+        let entry = heading.opposite();
+        if !self.connects(entry) {
+            return None;
         }
+        Direction::ALL.into_iter().find(|&side| side != entry && self.connects(side))
     }
 }
 
-impl Solver {
-    pub fn new(buffer: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(buffer),
+/// A parsed pipe maze with the start's tile replaced by the pipe that closes the loop through
+/// it. Tiles are indexed row by row without the newlines.
+#[derive(Debug, Clone)]
+pub struct Maze {
+    pub width: usize,
+    pub height: usize,
+    pub pipes: Vec<Pipe>,
+    pub start: usize,
+}
+
+impl Maze {
+    /// Read a rectangle of pipe tiles with exactly one start, whose last row may leave out its
+    /// newline, and work out which pipe is under the start.
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+        if bytes.is_empty() {
+            return Err(ParseError::new(String::from("maze is empty")));
         }
-    }
+        let rows: Vec<&[u8]> = bytes.split(|&byte| byte == b'\n').collect();
+        let width = rows[0].len();
+        let mut pipes = Vec::with_capacity(width * rows.len());
+        let mut start = None;
+        for (idx, row) in rows.iter().enumerate() {
+            if row.len() != width {
+                return Err(ParseError::new(format!("row {} is not {width} tiles wide", idx + 1)));
+            }
+            for &tile in row.iter() {
+                match (tile, Pipe::from_tile(tile)) {
+                    (_, Some(pipe)) => pipes.push(pipe),
+                    (b'S', None) if start.is_none() => {
+                        start = Some(pipes.len());
+                        pipes.push(Pipe::GROUND);
+                    }
+                    (b'S', None) => {
+                        return Err(ParseError::new(String::from(
+                            "maze must have exactly one start",
+                        )))
+                    }
+                    _ => return Err(ParseError::new(format!("unknown tile {:?}", tile as char))),
+                }
+            }
+        }
+        let start = start
+            .ok_or_else(|| ParseError::new(String::from("maze must have exactly one start")))?;
 
-    pub fn get_maze_width(&mut self) -> u32 {
-        self.reader.seek(0);
+        let mut maze = Self { width, height: rows.len(), pipes, start };
+        maze.pipes[start] = maze.infer_start()?;
+        Ok(maze)
+    }
 
-        // Add one here to include the newline as part of the maze tiles
-        let width = self.reader.read_until(b'\n').unwrap().len() + 1;
+    pub fn neighbor(&self, idx: usize, direction: Direction) -> Option<usize> {
+        let (x, y) = (idx % self.width, idx / self.width);
+        match direction {
+            Direction::North if y > 0 => Some(idx - self.width),
+            Direction::East if x + 1 < self.width => Some(idx + 1),
+            Direction::South if y + 1 < self.height => Some(idx + self.width),
+            Direction::West if x > 0 => Some(idx - 1),
+            _ => None,
+        }
+    }
 
-        self.reader.seek(0);
+    /// Walk out of the start heading `heading` and follow the pipes. Returns the tiles passed,
+    /// starting with the start, and the side the walk came back in by, or `None` at a dead end.
+    fn walk(&self, heading: Direction) -> Option<(Vec<usize>, Direction)> {
+        let mut path = vec![self.start];
+        let (mut idx, mut heading) = (self.start, heading);
+        loop {
+            idx = self.neighbor(idx, heading)?;
+            if idx == self.start {
+                return Some((path, heading.opposite()));
+            }
+            // Every tile has two ends, so a walk can only come back around through the start
+            heading = self.pipes[idx].turn(heading)?;
+            path.push(idx);
+        }
+    }
 
-        width as u32
+    /// The start's pipe joins the two neighbours that lead back to it. Stray pipes may point at
+    /// the start too, so each side is walked to see which pairs actually close a loop.
+    fn infer_start(&self) -> Result<Pipe, ParseError> {
+        let mut loops = Vec::new();
+        for heading in Direction::ALL {
+            if let Some((_, back)) = self.walk(heading) {
+                let pipe = Pipe::between(heading, back);
+                if back != heading && !loops.contains(&pipe) {
+                    loops.push(pipe);
+                }
+            }
+        }
+        match loops[..] {
+            [pipe] => Ok(pipe),
+            [] => Err(ParseError::new(String::from("start is not on a loop"))),
+            _ => Err(ParseError::new(String::from("start is on more than one loop"))),
+        }
     }
 
-    fn find_loop_length(&mut self, start_position: u32, width: u32, direction: Direction) -> Option<u32> {
-        // println!("find_loop_length: direction: {:?}", direction);
-        let mut loop_length = 0;
-        let max_position = self.reader.len() - 1;
-        let mut position = Position(Some(start_position as usize));
-        position.step(direction, width as usize, max_position);
+    /// The loop's tiles in order, starting at the start.
+    pub fn find_loop(&self) -> Vec<usize> {
+        let heading = Direction::ALL
+            .into_iter()
+            .find(|&side| self.pipes[self.start].connects(side))
+            .expect("the start's pipe is inferred when parsing");
+        self.walk(heading).expect("the start's pipe closes a loop").0
+    }
 
-        let mut last_direction = direction;
+    /// How many steps along the loop the tile farthest from the start is.
+    pub fn farthest(&self) -> usize {
+        // A loop on a grid always has an even length
+        self.find_loop().len() / 2
+    }
 
-        loop {
-            loop_length += 1;
-            let tile = self.reader.at(position.0?);
-            // println!("tile: {} direction: {:?}", tile as char, last_direction);
-
-            last_direction = match tile {
-                // Dead end
-                b'\n' | b'.' => {
-                    return None;
-                },
-                b'J' => match last_direction {
-                    Direction::East => {
-                        Direction::North
-                    },
-                    Direction::South => {
-                        Direction::West
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                b'F' => match last_direction {
-                    Direction::North => {
-                        Direction::East
-                    },
-                    Direction::West => {
-                        Direction::South
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                b'7' => match last_direction {
-                    Direction::North => {
-                        Direction::West
-                    },
-                    Direction::East => {
-                        Direction::South
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                b'L' => match last_direction {
-                    Direction::South => {
-                        Direction::East
-                    },
-                    Direction::West => {
-                        Direction::North
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                b'|' => match last_direction {
-                    Direction::North | Direction::South => {
-                        last_direction
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                b'-' => match last_direction {
-                    Direction::East | Direction::West => {
-                        last_direction
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                _ => panic!("Unexpected tile: {}", tile)
-            };
+    /// The maze as text, with the start drawn as its inferred pipe.
+    pub fn render(&self) -> String {
+        let mut text = String::with_capacity((self.width + 1) * self.height);
+        for row in self.pipes.chunks(self.width) {
+            text.extend(row.iter().map(|pipe| pipe.tile() as char));
+            text.push('\n');
+        }
+        text
+    }
+}
 
-            position.step(last_direction, width as usize, max_position);
+impl Maze {
+    /// The maze as text with the tiles in `walked` drawn as box-drawing lines.
+    pub fn render_walk(&self, walked: &[usize]) -> String {
+        let mut tiles: Vec<char> = self.pipes.iter().map(|pipe| pipe.tile() as char).collect();
+        for &idx in walked {
+            tiles[idx] = self.pipes[idx].line();
+        }
+        let mut text = String::with_capacity((self.width + 1) * self.height * 3);
+        for row in tiles.chunks(self.width) {
+            text.extend(row);
+            text.push('\n');
+        }
+        text
+    }
 
-            if position.0? == start_position as usize {
-                break;
+    /// Draw the walk around the loop from the start on `animation` and as images on `frames`,
+    /// in at most `MAX_WALK_FRAMES` frames.
+    pub fn record_walk<W: Write>(
+        &self,
+        animation: &mut Option<Animation<W>>,
+        frames: &mut Option<Frames>,
+    ) {
+        let path = self.find_loop();
+        let stride = path.len().div_ceil(MAX_WALK_FRAMES);
+        let mut walked = vec![false; self.pipes.len()];
+        for steps in (stride..path.len()).step_by(stride).chain([path.len()]) {
+            if let Some(animation) = animation {
+                let title = format!("Step {steps} of {}", path.len());
+                animation.frame(&title, &self.render_walk(&path[..steps]));
+            }
+            if let Some(frames) = frames {
+                for &idx in &path[..steps] {
+                    walked[idx] = true;
+                }
+                frames.frame(&Walk { maze: self, walked: &walked });
             }
         }
+    }
+}
 
-        // println!("loop_length: {}", loop_length);
+impl Render for Maze {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
 
-        Some(loop_length)
+    fn color(&self, x: usize, y: usize) -> Rgb {
+        let idx = y * self.width + x;
+        match self.pipes[idx] {
+            _ if idx == self.start => Rgb(220, 40, 40),
+            Pipe::GROUND => Rgb::BLACK,
+            _ => Rgb(70, 70, 80),
+        }
     }
+}
 
-    pub fn solve(&mut self) -> u32 {
-        let width = self.get_maze_width();
+/// The maze with the tiles walked so far lit up, for images.
+pub struct Walk<'a> {
+    pub maze: &'a Maze,
+    /// Whether each tile has been walked, row by row
+    pub walked: &'a [bool],
+}
 
-        let start_position = self.reader.read_until(b'S').unwrap().len() as u32;
+impl Render for Walk<'_> {
+    fn size(&self) -> (usize, usize) {
+        self.maze.size()
+    }
 
-        let max_loop_length = [
-            Direction::North,
-            Direction::East,
-            Direction::South,
-            Direction::West
-        ].iter()
-            .filter_map(|d| self.find_loop_length(start_position, width, *d))
-            .max()
-            .unwrap();
+    fn color(&self, x: usize, y: usize) -> Rgb {
+        let idx = y * self.maze.width + x;
+        if self.walked[idx] && idx != self.maze.start {
+            Rgb(80, 200, 120)
+        } else {
+            self.maze.color(x, y)
+        }
+    }
+}
 
-        // println!("width: {}", width);
-        // println!("start_position: {}", start_position);
-        println!("max_loop_length: {}", max_loop_length);
+/// The most frames `--animate` and `--frames` draw a loop walk in, however long the loop.
+pub const MAX_WALK_FRAMES: usize = 100;
 
-        (max_loop_length + 1) / 2
-    }
+/// How many steps along the loop it is to the tile farthest from the start.
+pub fn farthest(bytes: &[u8]) -> Result<usize, ParseError> {
+    Ok(Maze::parse(bytes)?.farthest())
 }
 
 #[derive(Parser, Debug)]
 pub struct Day10a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    #[clap(flatten)]
+    animate: AnimateOptions,
+    #[clap(flatten)]
+    render: RenderOptions,
 }
 
 impl CommandImpl for Day10a {
     fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day10a: {answer}");
-        Ok(())
+        let (animate, render) = (self.animate.clone(), self.render.clone());
+        self.input.for_each(|bytes| {
+            let maze = Maze::parse(&bytes)?;
+            let (mut animation, mut frames) = (animate.animation(), render.frames()?);
+            if animation.is_some() || frames.is_some() || render.wants_image() {
+                maze.record_walk(&mut animation, &mut frames);
+                let mut walked = vec![false; maze.pipes.len()];
+                for idx in maze.find_loop() {
+                    walked[idx] = true;
+                }
+                render.save(&Walk { maze: &maze, walked: &walked })?;
+            }
+            if let Some(animation) = animation {
+                animation.finish()?;
+            }
+            if let Some(frames) = frames {
+                frames.finish()?;
+            }
+            let answer = maze.farthest();
+            println!("Day10a: {answer}");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const SQUARE_LOOP: &str = "\
+.....
+.S-7.
+.|.|.
+.L-J.
+.....
+";
+    const COMPLEX_LOOP: &str = "\
+..F7.
+.FJ|.
+SJ.L7
+|F--J
+LJ...
+";
+
+    fn solve(input: &[u8]) -> usize {
+        farthest(input).unwrap()
+    }
+
+    fn parse(input: &[u8]) -> Result<Maze, ParseError> {
+        Maze::parse(input)
+    }
+
+    puzzle_tests! {
+        solve {
+            example square_loop: SQUARE_LOOP => 4;
+            example complex_loop: COMPLEX_LOOP => 8;
+            input real: "day10.txt" => 6773;
+        }
+    }
+
+    #[test]
+    fn loop_walk() {
+        let maze = parse(SQUARE_LOOP.as_bytes()).unwrap();
+        let path = maze.find_loop();
+        assert_eq!(maze.render_walk(&path[..3]), ".....\n.┌─┐.\n.|.|.\n.L-J.\n.....\n");
+
+        let mut animation = Some(Animation::new(Vec::new(), false, 1.0));
+        maze.record_walk(&mut animation, &mut None);
+        assert_eq!(animation.unwrap().frames(), path.len());
+
+        let walked: Vec<bool> = (0..25).map(|idx| path[..3].contains(&idx)).collect();
+        let canvas = Walk { maze: &maze, walked: &walked }.canvas(1);
+        assert_eq!(canvas.pixels[6..9], [Rgb(220, 40, 40), Rgb(80, 200, 120), Rgb(80, 200, 120)]);
+        assert_eq!(canvas.pixels[13..15], [Rgb(70, 70, 80), Rgb::BLACK]);
+
+        let long = format!("S{}7\nL{}J\n", "-".repeat(200), "-".repeat(200));
+        let maze = parse(long.as_bytes()).unwrap();
+        let mut animation = Some(Animation::new(Vec::new(), false, 1.0));
+        maze.record_walk(&mut animation, &mut None);
+        assert!(animation.unwrap().frames() <= MAX_WALK_FRAMES);
+    }
+
+    #[test]
+    fn start_shape() {
+        let start = |input: &str| {
+            let maze = parse(input.as_bytes()).unwrap();
+            maze.pipes[maze.start].tile()
+        };
+        assert_eq!(start(SQUARE_LOOP), b'F');
+        assert_eq!(start(COMPLEX_LOOP), b'F');
+        // The pipes above and left of S point at it but lead nowhere
+        assert_eq!(start("7|..\n-S-7\n.|.|\n.L-J\n"), b'F');
+        assert_eq!(start("F-7\nS.|\nL-J\n"), b'|');
+        assert_eq!(start("F-S-7\n|.|.|\nL-J-J\n"), b'7');
+        assert_eq!(parse(SQUARE_LOOP.as_bytes()).unwrap().render(), SQUARE_LOOP.replace('S', "F"));
+
+        assert!(parse(b"S-7\n|.|\n|.J\n").is_err());
+        assert!(parse(b".S.\n...\n").is_err());
+        // Two separate loops meet at S
+        assert!(parse(b"F-7..\n|.|..\nL-S-7\n..|.|\n..L-J\n").is_err());
+    }
+
+    #[test]
+    fn last_newline_is_optional() {
+        assert_eq!(solve(SQUARE_LOOP.trim_end().as_bytes()), 4);
+        assert!(parse(b"").is_err());
+        assert!(parse(b"\n").is_err());
+    }
+
+    robustness_tests! {
+        parse: SQUARE_LOOP;
     }
 }
@@ -1,428 +1,192 @@
-use std::path::PathBuf;
-
 use clap::Parser;
 
-use crate::utils::{AsciiReader, slurp_bytes};
-
-use super::{CommandImpl, DynError};
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Direction {
-    North,
-    East,
-    South,
-    West,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Visit {
-    Unvisited,
-    Visited {
-        north: bool,
-        east: bool,
-        south: bool,
-        west: bool,
-    },
+use crate::utils::{Inputs, ParseError};
+
+use super::{
+    day10a::{Direction, Maze},
+    CommandImpl, DynError,
+};
+
+/// Count the tiles inside the loop from its area. The shoelace formula gives the area of the
+/// polygon through the centres of the loop's tiles, and Pick's theorem turns that into the
+/// number of tile centres strictly inside it: `area = inside + on_loop / 2 - 1`.
+pub fn enclosed_by_area(maze: &Maze, path: &[usize]) -> u64 {
+    let point = |idx: usize| ((idx % maze.width) as i64, (idx / maze.width) as i64);
+    let twice_area: i64 = path
+        .iter()
+        .zip(path.iter().cycle().skip(1))
+        .map(|(&a, &b)| {
+            let ((ax, ay), (bx, by)) = (point(a), point(b));
+            ax * by - bx * ay
+        })
+        .sum();
+    (twice_area.unsigned_abs() - path.len() as u64) / 2 + 1
 }
 
-impl Direction {
-    fn all() -> [Self; 4] {
-        [
-            Direction::North,
-            Direction::East,
-            Direction::South,
-            Direction::West
-        ]
+/// Count the tiles inside the loop by scanning each row and flipping between outside and
+/// inside whenever the loop crosses it. Only loop tiles opening north count as a crossing, so
+/// `F-J` flips once and `F-7` twice, as if the row were read just below the tiles' middles.
+pub fn enclosed_by_scanline(maze: &Maze, path: &[usize]) -> u64 {
+    let mut on_loop = vec![false; maze.pipes.len()];
+    for &idx in path {
+        on_loop[idx] = true;
     }
-}
-
-pub struct Solver {
-    reader: AsciiReader,
-}
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct Position(Option<usize>);
-
-impl Position {
-    pub fn new(position: usize) -> Self {
-        Self(Some(position))
-    }
-
-    fn step(&mut self, direction: Direction, width: usize, max_position: usize) -> &mut Self {
-        if self.0.is_none() {
-            panic!("Cannot step from None position");
+    let (mut enclosed, mut inside) = (0, false);
+    for (idx, pipe) in maze.pipes.iter().enumerate() {
+        if idx % maze.width == 0 {
+            inside = false;
         }
-
-        let position = self.0.unwrap();
-
-        self.0 = match direction {
-            Direction::North => {
-                if position < width {
-                    None
-                } else {
-                    Some(position - width)
-                }
-            },
-            Direction::East => {
-                if (position + 1) % width == 0 {
-                    None
-                } else {
-                    Some(position + 1)
-                }
-            },
-            Direction::South => {
-                if position + width > max_position {
-                    None
-                } else {
-                    Some(position + width)
-                }
-            },
-            Direction::West => {
-                if position % width == 0 {
-                    None
-                } else {
-                    Some(position - 1)
-                }
-            },
-        };
-
-        self
-    }
-
-    fn on_perimiter(&self, width: usize, max_position: usize) -> bool {
-        if let Some(position) = self.0 {
-            position < width || position > max_position - width || position % width == 0 || (position + 2) % width == 0
-        } else {
-            false
+        if on_loop[idx] {
+            inside ^= pipe.connects(Direction::North);
+        } else if inside {
+            enclosed += 1;
         }
     }
+    enclosed
 }
 
-impl Solver {
-    pub fn new(buffer: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(buffer),
+/// How many tiles the loop encloses, optionally checked against a row by row count.
+pub fn enclosed(bytes: &[u8], cross_check: bool) -> Result<u64, ParseError> {
+    let maze = Maze::parse(bytes)?;
+    let path = maze.find_loop();
+    let enclosed = enclosed_by_area(&maze, &path);
+    if cross_check {
+        let scanned = enclosed_by_scanline(&maze, &path);
+        if scanned != enclosed {
+            return Err(ParseError::new(format!(
+                "Pick's theorem counts {enclosed} enclosed tiles but the scanline counts {scanned}"
+            )));
         }
     }
+    Ok(enclosed)
+}
 
-    fn print_maze(&self) {
-        let mut maze = String::from_utf8(self.reader.buffer.clone()).unwrap();
-        maze = maze.replacen('F', "┌", self.reader.buffer.len())
-            .replacen('7', "┐", self.reader.buffer.len())
-            .replacen('|', "│", self.reader.buffer.len())
-            .replacen('-', "─", self.reader.buffer.len())
-            .replacen('J', "┘", self.reader.buffer.len())
-            .replacen('L', "└", self.reader.buffer.len())
-            .replacen('I', "•", self.reader.buffer.len());
+#[derive(Parser, Debug)]
+pub struct Day10b {
+    #[clap(flatten)]
+    input: Inputs,
+    /// Also count the enclosed tiles row by row and fail if the two counts differ
+    #[clap(long)]
+    cross_check: bool,
+}
 
-        
-        println!("{}", maze);
+impl CommandImpl for Day10b {
+    fn main(&mut self) -> Result<(), DynError> {
+        let cross_check = self.cross_check;
+        self.input.for_each(|bytes| {
+            let answer = enclosed(&bytes, cross_check)?;
+            println!("Day10b: {answer}");
+            Ok(())
+        })
     }
+}
 
-    pub fn get_maze_width(&mut self) -> u32 {
-        self.reader.seek(0);
-
-        // Add one here to include the newline as part of the maze tiles
-        let width = self.reader.read_until(b'\n').unwrap().len() + 1;
-
-        self.reader.seek(0);
-
-        width as u32
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+    use crate::commands::generate::maze;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const OPEN_LOOP: &str = "\
+...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+...........
+";
+    const SQUEEZED_LOOP: &str = "\
+..........
+.S------7.
+.|F----7|.
+.||....||.
+.||....||.
+.|L-7F-J|.
+.|..||..|.
+.L--JL--J.
+..........
+";
+    const LARGER_LOOP: &str = "\
+.F----7F7F7F7F-7....
+.|F--7||||||||FJ....
+.||.FJ||||||||L7....
+FJL7L7LJLJ||LJ.L-7..
+L--J.L7...LJS7F-7L7.
+....F-J..F7FJ|L7L7L7
+....L7.F7||L7|.L7L7|
+.....|FJLJ|FJ|F7|.LJ
+....FJL-7.||.||||...
+....L---J.LJ.LJLJ...
+";
+    const JUNK_LOOP: &str = "\
+FF7FSF7F7F7F7F7F---7
+L|LJ||||||||||||F--J
+FL-7LJLJ||||||LJL-77
+F--JF--7||LJLJ7F7FJ-
+L---JF-JLJ.||-FJLJJ7
+|F|F-JF---7F7-L7L|7|
+|FFJF7L7F-JF7|JL---7
+7-L-JL7||F7|L7F-7F7|
+L.L7LFJ|||||FJL7||LJ
+L7JLJL-JLJLJL--JLJ.L
+";
+
+    fn solve(input: &[u8]) -> u64 {
+        enclosed(input, true).unwrap()
     }
 
-    fn find_loop(&mut self, start_position: u32, width: u32, direction: Direction) -> Option<Vec<usize>> {
-        // println!("find_loop: direction: {:?}", direction);
-        let mut loop_length = 0;
-        let max_position = self.reader.len() - 1;
-        let mut position = Position(Some(start_position as usize));
-        let mut loop_positions: Vec<usize> = vec![position.0.unwrap()];
-
-        // Check if we can step in the given direction
-        let mut candidate_position = position;
-        candidate_position.step(direction, width as usize, max_position);
-        let candidate_tile = self.reader.at(candidate_position.0?);
-        match direction {
-            Direction::North => match candidate_tile {
-                b'|' | b'F' | b'7' => {},
-                _ => {
-                    return None;
-                }
-            },
-            Direction::East => match candidate_tile {
-                b'-' | b'J' | b'7' => {},
-                _ => {
-                    return None;
-                }
-            },
-            Direction::South => match candidate_tile {
-                b'|' | b'J' | b'L' => {},
-                _ => {
-                    return None;
-                }
-            },
-            Direction::West => match candidate_tile {
-                b'-' | b'F' | b'L' => {},
-                _ => {
-                    return None;
-                }
-            },
-        }
-        position = candidate_position;
-
-
-        let mut last_direction = direction;
-
-        loop {
-            loop_length += 1;
-            let tile = self.reader.at(position.0?);
-
-            last_direction = match tile {
-                // Dead end
-                b'\n' | b'.' => {
-                    return None;
-                },
-                b'J' => match last_direction {
-                    Direction::East => {
-                        Direction::North
-                    },
-                    Direction::South => {
-                        Direction::West
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                b'F' => match last_direction {
-                    Direction::North => {
-                        Direction::East
-                    },
-                    Direction::West => {
-                        Direction::South
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                b'7' => match last_direction {
-                    Direction::North => {
-                        Direction::West
-                    },
-                    Direction::East => {
-                        Direction::South
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                b'L' => match last_direction {
-                    Direction::South => {
-                        Direction::East
-                    },
-                    Direction::West => {
-                        Direction::North
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                b'|' => match last_direction {
-                    Direction::North | Direction::South => {
-                        last_direction
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                b'-' => match last_direction {
-                    Direction::East | Direction::West => {
-                        last_direction
-                    },
-                    _ => {
-                        return None;
-                    }
-                },
-                _ => panic!("Unexpected tile: {}", tile)
-            };
-
-            if let Some(position) = position.0 {
-                loop_positions.push(position);
-            }
-
-            position.step(last_direction, width as usize, max_position);
-
-            if position.0? == start_position as usize {
-                break;
-            }
-        }
-
-        let mut has_north = direction == Direction::North;
-        let mut has_east = direction == Direction::East;
-        let mut has_south = direction == Direction::South;
-        let mut has_west = direction == Direction::West;
-
-        for d in Direction::all() {
-            if d == direction {
-                continue;
-            }
-            let mut candidate_position = Position(Some(start_position as usize));
-            candidate_position.step(d, width as usize, max_position);
-            let in_loop = candidate_position.0.is_some() && loop_positions.contains(&candidate_position.0.unwrap());
-            let tile = self.reader.at(candidate_position.0?);
-            match d {
-                Direction::North => {
-                    if tile == b'|' || tile == b'F' || tile == b'7' {
-                        has_north = true;
-                        break;
-                    }
-                },
-                Direction::East => {
-                    if tile == b'-' || tile == b'J' || tile == b'7' {
-                        has_east = true;
-                        break;
-                    }
-                },
-                Direction::South => {
-                    if tile == b'|' || tile == b'J' || tile == b'L' {
-                        has_south = true;
-                        break;
-                    }
-                },
-                Direction::West => {
-                    if tile == b'-' || tile == b'F' || tile == b'L' {
-                        has_west = true;
-                        break;
-                    }
-                },
-            }
-        }
-
-        match (has_north, has_east, has_south, has_west) {
-            (true, true, false, false) => {
-                self.reader.buffer[start_position as usize] = b'L';
-            },
-            (true, false, true, false) => {
-                self.reader.buffer[start_position as usize] = b'|';
-            },
-            (true, false, false, true) => {
-                self.reader.buffer[start_position as usize] = b'J';
-            },
-            (false, true, true, false) => {
-                self.reader.buffer[start_position as usize] = b'F';
-            },
-            (false, true, false, true) => {
-                self.reader.buffer[start_position as usize] = b'-';
-            },
-            (false, false, true, true) => {
-                self.reader.buffer[start_position as usize] = b'7';
-            },
-            _ => {
-                panic!("Could not determine start tile {has_north} {has_east} {has_south} {has_west}");
-            }
-        }
-
-        // println!("loop_length: {}", loop_length);
-
-        Some(loop_positions)
+    fn parse(input: &[u8]) -> Result<u64, ParseError> {
+        enclosed(input, true)
     }
 
-    fn mark_loop_path(&mut self, loop_positions: &[usize], max_position: usize) {
-        for position in 0..max_position {
-            if loop_positions.contains(&position) {
-                continue;
-            }
-            if self.reader.at(position) == b'\n' {
-                continue;
-            }
-            self.reader.buffer[position] = b'.';
+    puzzle_tests! {
+        solve {
+            example open_loop: OPEN_LOOP => 4;
+            example squeezed_loop: SQUEEZED_LOOP => 4;
+            example larger_loop: LARGER_LOOP => 8;
+            example junk_loop: JUNK_LOOP => 10;
+            input real: "day10.txt" => 493;
         }
     }
 
-    fn count_enclosed_tiles(&mut self, loop_positions: &[usize], width: usize, max_position: usize) -> u32 {
-        self.mark_loop_path(loop_positions, max_position);
-
-        let mut count = 0;
-
-        for position in 0..max_position {
-            let tile = self.reader.at(position);
-            if tile != b'.' {
-                continue;
-            }
-            // Count how many transitions happen from this position to the end of the line
-            // A tile inside the loop will have an odd number of transitions
-            let mut transitions: i32 = 0;
-            let mut test_position = position + 1;
-            let mut last_transition_tile = None;
-            loop {
-                let test_tile = self.reader.at(test_position);
-                match test_tile {
-                    b'.' => {
-                        last_transition_tile = None;
-                    },
-                    b'\n' => {
-                        break;
-                    },
-                    b'J' => {
-                        if last_transition_tile != Some(b'F') {
-                            last_transition_tile = Some(test_tile);
-                            transitions += 1;
-                        }
-                    },
-                    b'7' => {
-                        if last_transition_tile != Some(b'L') {
-                            last_transition_tile = Some(test_tile);
-                            transitions += 1;
-                        }
-                    },
-                    b'F' | b'L' | b'|' | b'L' => {
-                        last_transition_tile = Some(test_tile);
-                        transitions += 1;
-                    }
-                    b'-' => {}, 
-                    _ => {
-                        panic!("Unexpected tile: {}", test_tile);
-                    }
-                }
-                test_position += 1;
-            }
-
-            // If there are no transitions, skip to the next line
-            if transitions == 0 {
-                test_position = width * (test_position.div_ceil(width) + 1);
-            } else if (transitions % 2) == 1 {
-                count += 1;
-            }
-        }
-
-        count
+    #[test]
+    fn start_on_a_crossing() {
+        // S stands for a |, which the scanline has to count as a crossing
+        let input = b"F-7\nS.|\nL-J\n";
+        let maze = Maze::parse(input).unwrap();
+        let path = maze.find_loop();
+        assert_eq!(enclosed_by_area(&maze, &path), 1);
+        assert_eq!(enclosed_by_scanline(&maze, &path), 1);
     }
 
-    pub fn solve(&mut self) -> u32 {
-        let width = self.get_maze_width();
-
-        let start_position = self.reader.read_until(b'S').unwrap().len() as u32;
-        
-        for direction in Direction::all().iter() {
-            if let Some(loop_positions) = self.find_loop(start_position, width, *direction) {
-                return self.count_enclosed_tiles(&loop_positions, width as usize, self.reader.len() - 1);
-            }
+    proptest! {
+        #[test]
+        fn scanline_matches_area(
+            size in (3..=13usize, 3..=13usize),
+            seed in any::<usize>(),
+            growth in vec(any::<usize>(), 0..24),
+            filler in vec(prop::sample::select(b"|-LJ7F.".to_vec()), 0..48),
+            start in any::<usize>(),
+        ) {
+            let input = maze(size, seed, &growth, &filler, start);
+            let maze = Maze::parse(input.as_bytes()).unwrap();
+            let path = maze.find_loop();
+            prop_assert_eq!(
+                enclosed_by_scanline(&maze, &path),
+                enclosed_by_area(&maze, &path),
+                "maze:\n{}",
+                input
+            );
         }
-
-        panic!("No loop found");
     }
-}
 
-#[derive(Parser, Debug)]
-pub struct Day10b {
-    #[clap(long, short)]
-    input: PathBuf,
-}
-
-impl CommandImpl for Day10b {
-    fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day10b: {answer}");
-        // 762 is too high
-        // 1777
-        Ok(())
+    robustness_tests! {
+        parse: OPEN_LOOP;
     }
 }
@@ -1,125 +1,238 @@
-use std::path::PathBuf;
-
 use clap::Parser;
 
-use crate::utils::{slurp_bytes, AsciiReader};
+use crate::{
+    render::{ImageOptions, Render, Rgb},
+    utils::{Inputs, ParseError},
+};
 
 use super::{CommandImpl, DynError};
 
-struct Solver {
-    input: Vec<u8>
+fn overflow() -> ParseError {
+    ParseError::new(String::from("the distance sum overflows a u128"))
 }
 
-impl Solver {
-    fn new(input: Vec<u8>) -> Self {
-        Self {
-            input
-        }
-    }
+/// The galaxies in an image, with how many of them sit in each row and each column.
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// `(x, y)` of every galaxy, row by row
+    pub galaxies: Vec<(usize, usize)>,
+    pub rows: Vec<u64>,
+    pub columns: Vec<u64>,
+}
 
-    fn find_galaxies(&mut self, width: usize) -> Vec<(u32, u32)> {
-        let galaxies: Vec<(u32, u32)> = (0..self.input.len()).filter_map(|read_idx| {
-            match self.input[read_idx] {
-                b'#' => {
-                    Some(
-                        ((read_idx % (width + 1)) as u32,
-                        (read_idx / (width + 1)) as u32)
-                    )
-                },
-                _ => {
-                    None
+impl Image {
+    /// Read a rectangle of `.` and `#` pixels, whose last line may leave out its newline.
+    pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
+        let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+        if bytes.is_empty() {
+            return Err(ParseError::new(String::from("image is empty")));
+        }
+        let lines: Vec<&[u8]> = bytes.split(|&byte| byte == b'\n').collect();
+        let width = lines[0].len();
+        let mut image = Self {
+            width,
+            height: lines.len(),
+            galaxies: Vec::new(),
+            rows: vec![0; lines.len()],
+            columns: vec![0; width],
+        };
+        for (y, line) in lines.iter().enumerate() {
+            if line.len() != width {
+                return Err(ParseError::new(format!("row {} is not {width} pixels wide", y + 1)));
+            }
+            for (x, &pixel) in line.iter().enumerate() {
+                match pixel {
+                    b'#' => {
+                        image.galaxies.push((x, y));
+                        image.rows[y] += 1;
+                        image.columns[x] += 1;
+                    }
+                    b'.' => (),
+                    _ => return Err(ParseError::new(format!("unknown pixel {:?}", pixel as char))),
                 }
             }
-        }).collect();
+        }
+        Ok(image)
+    }
 
-        galaxies
+    /// The sum of the distances between every pair of galaxies once each empty row and column
+    /// has grown to `expansion` rows or columns.
+    pub fn sum_of_distances(&self, expansion: u128) -> Result<u128, ParseError> {
+        let rows = axis_distances(&self.rows, expansion).ok_or_else(overflow)?;
+        let columns = axis_distances(&self.columns, expansion).ok_or_else(overflow)?;
+        rows.checked_add(columns).ok_or_else(overflow)
     }
+}
 
-    fn get_galaxy_distance(&mut self, galaxy_a: (u32, u32), galaxy_b: (u32, u32), empty_rows: &[u32], empty_cols: &[u32]) -> u64 {
-        let mut column_distance: u64 = (galaxy_a.0).abs_diff(galaxy_b.0) as u64;
-        // Count empty columns between the galaxies
-        for empty_col in empty_cols {
-            if *empty_col > galaxy_a.0.min(galaxy_b.0) && *empty_col < galaxy_b.0.max(galaxy_a.0) {
-                column_distance += 1;
-            }
-        }
+/// Galaxies are white and the empty rows and columns that expand are tinted blue.
+impl Render for Image {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
 
-        let mut row_distance: u64 = (galaxy_a.1).abs_diff(galaxy_b.1) as u64;
-        // Count rows columns between the galaxies
-        for empty_row in empty_rows {
-            if *empty_row > galaxy_a.1.min(galaxy_b.1) && *empty_row < galaxy_b.1.max(galaxy_a.1) {
-                row_distance += 1;
-            }
+    fn color(&self, x: usize, y: usize) -> Rgb {
+        if self.galaxies.binary_search_by_key(&(y, x), |&(x, y)| (y, x)).is_ok() {
+            Rgb::WHITE
+        } else if self.rows[y] == 0 || self.columns[x] == 0 {
+            Rgb(20, 30, 70)
+        } else {
+            Rgb(5, 5, 10)
         }
-
-        column_distance + row_distance
     }
+}
 
-    fn get_dimensions(&mut self) -> (usize, usize) {
-        let mut width = 0;
-        while self.input[width] != b'\n' {
-            width += 1;
+/// The sum of the gaps between every pair of galaxies along one axis, given how many galaxies
+/// are at each coordinate. Walking the coordinates in order, a galaxy at `c` is `c - p` past
+/// each galaxy before it at `p`, so all of those gaps add up to `c * seen - (sum of p)`. This
+/// is linear in the size of the axis however many galaxies there are.
+pub fn axis_distances(counts: &[u64], expansion: u128) -> Option<u128> {
+    let (mut coordinate, mut seen, mut prefix, mut total) = (0u128, 0u128, 0u128, 0u128);
+    for &count in counts {
+        if count == 0 {
+            coordinate = coordinate.checked_add(expansion)?;
+            continue;
         }
+        let gaps = coordinate.checked_mul(seen)?.checked_sub(prefix)?;
+        total = total.checked_add(gaps.checked_mul(count as u128)?)?;
+        seen += count as u128;
+        prefix = prefix.checked_add(coordinate.checked_mul(count as u128)?)?;
+        coordinate = coordinate.checked_add(1)?;
+    }
+    Some(total)
+}
 
-        let height = self.input.len() / (width + 1);
+#[derive(Parser, Debug)]
+pub struct Day11a {
+    #[clap(flatten)]
+    input: Inputs,
+    /// How many rows or columns each empty row or column grows into
+    #[clap(long, value_name = "N", default_value_t = 2)]
+    expansion: u128,
+    #[clap(flatten)]
+    image: ImageOptions,
+}
 
-        (width, height)
+impl CommandImpl for Day11a {
+    fn main(&mut self) -> Result<(), DynError> {
+        let (expansion, options) = (self.expansion, self.image.clone());
+        self.input.for_each(|bytes| {
+            let image = Image::parse(&bytes)?;
+            options.save(&image)?;
+            let answer = image.sum_of_distances(expansion)?;
+            println!("Day11a: {answer}");
+            Ok(())
+        })
     }
+}
 
-    fn find_empty_rows_and_cols(&mut self, width: usize, height: usize, galaxies: &Vec<(u32, u32)>) -> (Vec<u32>, Vec<u32>) {
-        let mut empty_row_mask: Vec<u8> = vec![1; height];
-        let mut empty_col_mask: Vec<u8> = vec![1; width];
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....
+";
+
+    fn solve(input: &[u8]) -> u128 {
+        Image::parse(input).unwrap().sum_of_distances(2).unwrap()
+    }
+
+    fn parse(input: &[u8]) -> Result<Image, ParseError> {
+        Image::parse(input)
+    }
 
-        for galaxy in galaxies {
-            empty_row_mask[galaxy.0 as usize] = 0;
-            empty_col_mask[galaxy.1 as usize] = 0;
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 374;
+            input real: "day11.txt" => 10490062;
         }
+    }
 
-        let empty_rows = empty_row_mask.iter().enumerate().filter_map(|(idx, val)| {
-            if *val == 1 {
-                Some(idx as u32)
-            } else {
-                None
-            }
-        }).collect();
-        let empty_cols = empty_col_mask.iter().enumerate().filter_map(|(idx, val)| {
-            if *val == 1 {
-                Some(idx as u32)
-            } else {
-                None
-            }
-        }).collect();
+    #[test]
+    fn expansions() {
+        let image = parse(EXAMPLE.as_bytes()).unwrap();
+        assert_eq!(image.sum_of_distances(10).unwrap(), 1030);
+        assert_eq!(image.sum_of_distances(100).unwrap(), 8410);
+        assert_eq!(image.sum_of_distances(1).unwrap(), 292);
+        let huge = image.sum_of_distances(u64::MAX as u128).unwrap();
+        assert_eq!(huge, 292 + (u64::MAX as u128 - 1) * 82);
+        assert!(image.sum_of_distances(u128::MAX).is_err());
+    }
 
-        (empty_rows, empty_cols)
+    #[test]
+    fn last_newline_is_optional() {
+        let image = parse(EXAMPLE.trim_end().as_bytes()).unwrap();
+        assert_eq!((image.width, image.height), (10, 10));
+        assert_eq!(image.sum_of_distances(2).unwrap(), 374);
+        assert!(parse(b"").is_err());
     }
 
-    fn solve(&mut self) -> u64 {
-        let (height, width) = self.get_dimensions();
-        let galaxies = self.find_galaxies(width);
-        let (empty_cols, empty_rows) = self.find_empty_rows_and_cols(width, height, &galaxies);
+    #[test]
+    fn galaxy_field() {
+        let image = parse(EXAMPLE.as_bytes()).unwrap();
+        assert_eq!(image.color(3, 0), Rgb::WHITE);
+        assert_eq!(image.color(2, 0), Rgb(20, 30, 70));
+        assert_eq!(image.color(4, 3), Rgb(20, 30, 70));
+        assert_eq!(image.color(0, 1), Rgb(5, 5, 10));
+        let canvas = image.canvas(3);
+        let white = canvas.pixels.iter().filter(|&&pixel| pixel == Rgb::WHITE).count();
+        assert_eq!(white, image.galaxies.len() * 9);
+    }
 
+    /// Pair up every two galaxies and measure the distance between them directly.
+    fn brute_force(image: &Image, expansion: u128) -> u128 {
+        let grow = |counts: &[u64], at: usize| {
+            let empty = counts[..at].iter().filter(|&&count| count == 0).count() as u128;
+            at as u128 - empty + empty * expansion
+        };
+        let expanded: Vec<(u128, u128)> = image
+            .galaxies
+            .iter()
+            .map(|&(x, y)| (grow(&image.columns, x), grow(&image.rows, y)))
+            .collect();
         let mut sum = 0;
-        for galaxy_a_idx in 0..galaxies.len() {
-            for galaxy_b_idx in (galaxy_a_idx + 1)..galaxies.len() {
-                let distance = self.get_galaxy_distance(galaxies[galaxy_a_idx], galaxies[galaxy_b_idx], &empty_rows[..], &empty_cols[..]);
-                sum += distance;
+        for (idx, a) in expanded.iter().enumerate() {
+            for b in &expanded[idx + 1..] {
+                sum += a.0.abs_diff(b.0) + a.1.abs_diff(b.1);
             }
         }
-
         sum
     }
-}
 
-#[derive(Parser, Debug)]
-pub struct Day11a {
-    #[clap(long, short)]
-    input: PathBuf,
-}
+    proptest! {
+        #[test]
+        fn matches_brute_force(
+            pixels in vec(vec(prop::bool::weighted(0.2), 12), 1..12),
+            expansion in 0u128..1_000_000,
+        ) {
+            let mut input = String::new();
+            for row in pixels {
+                input.extend(row.iter().map(|&galaxy| if galaxy { '#' } else { '.' }));
+                input.push('\n');
+            }
+            let image = Image::parse(input.as_bytes()).unwrap();
+            prop_assert_eq!(
+                image.sum_of_distances(expansion).unwrap(),
+                brute_force(&image, expansion)
+            );
+        }
+    }
 
-impl CommandImpl for Day11a {
-    fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day11a: {answer}");
-        Ok(())
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
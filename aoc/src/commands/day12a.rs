@@ -1,169 +1,417 @@
-use std::{path::PathBuf, fmt::Display};
+use std::fmt;
 
-use clap::Parser;
+use clap::{Args, Parser};
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::utils::{AsciiReader, Inputs, ParseError};
 
-use super::{CommandImpl, DynError};
-
-struct Solver {
-    reader: AsciiReader,
-}
+use super::{generate::Rng, CommandImpl, DynError};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
-enum Spring {
+pub enum Spring {
     Operational,
     Damaged,
-    Unknown
+    Unknown,
 }
 
-#[derive(Debug, Clone)]
-struct SpringRow {
-    springs: Vec<Spring>,
-    groups: Vec<u32>
+impl Spring {
+    fn symbol(self) -> char {
+        match self {
+            Spring::Operational => '.',
+            Spring::Damaged => '#',
+            Spring::Unknown => '?',
+        }
+    }
+
+    fn allows(self, condition: Spring) -> bool {
+        self == Spring::Unknown || self == condition
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpringRow {
+    pub springs: Vec<Spring>,
+    pub groups: Vec<u32>,
+}
+
+impl fmt::Display for SpringRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let springs: String = self.springs.iter().map(|spring| spring.symbol()).collect();
+        let groups: Vec<String> = self.groups.iter().map(u32::to_string).collect();
+        write!(f, "{springs} {}", groups.join(","))
+    }
 }
 
-impl Display for SpringRow {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut springs = String::new();
-        for spring in &self.springs {
-            match spring {
-                Spring::Operational => springs.push('.'),
-                Spring::Damaged => springs.push('#'),
-                Spring::Unknown => springs.push('?'),
+/// Where a left to right scan of a row is: `done` groups are complete and the scan is `run`
+/// springs into the next one, or between groups when `run` is 0. Every state is numbered so
+/// that closing a group or growing a run moves to the next number, which keeps the counting
+/// tables flat.
+struct States {
+    /// `(run, size of the group being scanned)`, with no size once every group is done
+    states: Vec<(u32, Option<u32>)>,
+}
+
+impl States {
+    fn new(groups: &[u32]) -> Self {
+        let mut states = Vec::new();
+        for &size in groups {
+            states.extend((0..=size).map(|run| (run, Some(size))));
+        }
+        states.push((0, None));
+        Self { states }
+    }
+
+    fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// The state after a spring in `condition`, which must be operational or damaged.
+    fn next(&self, state: usize, condition: Spring) -> Option<usize> {
+        let (run, size) = self.states[state];
+        match condition {
+            Spring::Damaged if size.is_some_and(|size| run < size) => Some(state + 1),
+            Spring::Operational if run == 0 => Some(state),
+            Spring::Operational if Some(run) == size => Some(state + 1),
+            _ => None,
+        }
+    }
+}
+
+impl SpringRow {
+    /// The row repeated `fold` times with a `?` between copies.
+    pub fn unfold(&self, fold: usize) -> Self {
+        let mut springs = Vec::with_capacity((self.springs.len() + 1) * fold);
+        for copy in 0..fold {
+            if copy > 0 {
+                springs.push(Spring::Unknown);
             }
+            springs.extend_from_slice(&self.springs);
         }
-        let mut groups = String::new();
-        for group in &self.groups {
-            groups.push_str(&format!("{} ", group));
+        Self { springs, groups: self.groups.repeat(fold) }
+    }
+
+    /// The springs followed by one more operational spring, so every group ends before the
+    /// last state.
+    fn scan(&self) -> impl Iterator<Item = Spring> + '_ {
+        self.springs.iter().copied().chain(std::iter::once(Spring::Operational))
+    }
+
+    /// Count the arrangements with one pass over the springs, keeping how many ways there are
+    /// to reach each state so far. `None` if the count overflows a `u64`.
+    pub fn count_arrangements(&self) -> Option<u64> {
+        let states = States::new(&self.groups);
+        let mut ways = vec![0u64; states.len()];
+        ways[0] = 1;
+        for spring in self.scan() {
+            let mut next = vec![0u64; states.len()];
+            for (state, &count) in ways.iter().enumerate().filter(|(_, &count)| count > 0) {
+                for condition in [Spring::Operational, Spring::Damaged] {
+                    if !spring.allows(condition) {
+                        continue;
+                    }
+                    if let Some(to) = states.next(state, condition) {
+                        next[to] = next[to].checked_add(count)?;
+                    }
+                }
+            }
+            ways = next;
         }
-        write!(f, "{} {}", springs, groups)
+        Some(ways[states.len() - 1])
     }
+
+    /// Every concrete arrangement, or as many as `limit`, in order with `.` before `#`.
+    pub fn arrangements(&self, limit: usize) -> Vec<String> {
+        let table = Completions::new(self);
+        let mut found = Vec::new();
+        let mut prefix = String::with_capacity(self.springs.len());
+        table.enumerate(0, 0, &mut prefix, limit, &mut found);
+        found
+    }
+
+    /// `count` arrangements picked uniformly at random, possibly with repeats.
+    pub(crate) fn sample(&self, count: usize, rng: &mut Rng) -> Vec<String> {
+        let table = Completions::new(self);
+        if table.ways[0][0] == 0 {
+            return Vec::new();
+        }
+        (0..count).map(|_| table.sample(rng)).collect()
+    }
+}
+
+/// How many ways each state can be finished from each position, filled in from the end of the
+/// row so that enumerating and sampling never step into a dead end. Counts saturate, which
+/// only skews sampling on rows with more than `u64::MAX` arrangements.
+struct Completions {
+    states: States,
+    springs: Vec<Spring>,
+    ways: Vec<Vec<u64>>,
 }
 
-impl Solver {
-    fn new(input: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(input),
+impl Completions {
+    fn new(row: &SpringRow) -> Self {
+        let states = States::new(&row.groups);
+        let springs: Vec<Spring> = row.scan().collect();
+        let mut ways = vec![vec![0u64; states.len()]; springs.len() + 1];
+        ways[springs.len()][states.len() - 1] = 1;
+        for (pos, &spring) in springs.iter().enumerate().rev() {
+            for state in 0..states.len() {
+                ways[pos][state] = [Spring::Operational, Spring::Damaged]
+                    .into_iter()
+                    .filter(|&condition| spring.allows(condition))
+                    .filter_map(|condition| states.next(state, condition))
+                    .fold(0u64, |total, to| total.saturating_add(ways[pos + 1][to]));
+            }
+        }
+        Self { states, springs, ways }
+    }
+
+    /// The ways to go on from `state` at `pos`, one per condition the spring there allows.
+    fn choices(&self, pos: usize, state: usize) -> impl Iterator<Item = (Spring, usize, u64)> + '_ {
+        [Spring::Operational, Spring::Damaged]
+            .into_iter()
+            .filter(move |&condition| self.springs[pos].allows(condition))
+            .filter_map(move |condition| {
+                let to = self.states.next(state, condition)?;
+                Some((condition, to, self.ways[pos + 1][to])).filter(|&(_, _, ways)| ways > 0)
+            })
+    }
+
+    fn enumerate(
+        &self,
+        pos: usize,
+        state: usize,
+        prefix: &mut String,
+        limit: usize,
+        found: &mut Vec<String>,
+    ) {
+        if found.len() >= limit {
+            return;
+        }
+        // The last spring is the operational one added to close the final group
+        if pos + 1 == self.springs.len() {
+            found.push(prefix.clone());
+            return;
+        }
+        for (condition, to, _) in self.choices(pos, state) {
+            prefix.push(condition.symbol());
+            self.enumerate(pos + 1, to, prefix, limit, found);
+            prefix.pop();
+        }
+    }
+
+    fn sample(&self, rng: &mut Rng) -> String {
+        let mut arrangement = String::with_capacity(self.springs.len());
+        let mut state = 0;
+        for pos in 0..self.springs.len() - 1 {
+            let choices: Vec<(Spring, usize, u64)> = self.choices(pos, state).collect();
+            let total = choices.iter().fold(0u64, |total, choice| total.saturating_add(choice.2));
+            let mut pick = rng.range(0..=total - 1);
+            for (condition, to, ways) in choices {
+                if pick < ways {
+                    arrangement.push(condition.symbol());
+                    state = to;
+                    break;
+                }
+                pick -= ways;
+            }
         }
+        arrangement
     }
+}
 
-    fn next_line(&mut self) -> Option<SpringRow> {
+pub struct RowReader {
+    reader: AsciiReader,
+}
+
+impl RowReader {
+    pub fn new(input: Vec<u8>) -> Self {
+        Self { reader: AsciiReader::new(input) }
+    }
+
+    fn next_line(&mut self) -> Result<Option<SpringRow>, ParseError> {
         if self.reader.eof() {
-            return None;
+            return Ok(None);
         }
 
-        let springs: Vec<Spring> = self.reader.read_until(b' ').unwrap().iter().map(|s| {
-            match s {
+        let Some(spring_bytes) = self.reader.read_until(b' ') else {
+            return Err(self.reader.error("expected springs followed by a space"));
+        };
+        let mut springs = Vec::with_capacity(spring_bytes.len());
+        for spring in spring_bytes {
+            springs.push(match spring {
                 b'.' => Spring::Operational,
                 b'#' => Spring::Damaged,
-                _ => Spring::Unknown
-            }
-        }).collect();
+                b'?' => Spring::Unknown,
+                _ => return Err(ParseError::new(format!("unknown spring {:?}", *spring as char))),
+            });
+        }
         self.reader.skip(1);
 
         let mut groups = vec![];
         while let Some(value) = self.reader.read_next_number() {
-            groups.push(value as u32);
+            match u32::try_from(value) {
+                Ok(group) if group > 0 => groups.push(group),
+                _ => return Err(self.reader.error("damaged groups must be positive")),
+            }
         }
 
-        self.reader.skip(1);
+        match self.reader.peek() {
+            Some(b'\n') => self.reader.skip(1),
+            None => (),
+            Some(_) => return Err(self.reader.error("expected a damaged group")),
+        }
 
+        Ok(Some(SpringRow { springs, groups }))
+    }
+}
 
-        Some(SpringRow {
-            springs,
-            groups
-        })
+impl Iterator for RowReader {
+    type Item = Result<SpringRow, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_line().transpose()
     }
+}
 
-    fn count_arrangements(row: &SpringRow) -> u32 {
-        println!("Counting arrangements for: {:?}", row);
-        // Validate that the row spring groups are valid
-        // If an unknown spring is found create two new rows
-        // with the unknown replaced with damaged and operational respectively
-        // Count the arrangement for each row and return the sum
-
-        let mut group_iter = row.groups.iter();
-        let mut last_spring = None;
-        let mut current_group_remaining: Option<u32> = None;//group_iter.next().unwrap_or(&0);
-
-        for (idx, spring) in row.springs.iter().enumerate() {
-            match spring {
-                Spring::Unknown => {
-                    println!("Found unknown spring at index: {idx}. Testing twice. Once if damaged and once if operational");
-                    let mut new_row_damaged = row.clone();
-                    new_row_damaged.springs[idx] = Spring::Damaged;
-                    let mut new_row_operational = row.clone();
-                    new_row_operational.springs[idx] = Spring::Operational;
-                    return Self::count_arrangements(&new_row_damaged) + Self::count_arrangements(&new_row_operational);
-                },
-                Spring::Operational => {
-                    if let Some(current_group_remaining) = current_group_remaining {
-                        if current_group_remaining > 0 {
-                            println!("Found operational spring but expected damaged. Invalid arrangement");
-                            return 0;
-                        }
-                    }
-                    current_group_remaining = None;
-                },
-                Spring::Damaged => {
-                    if let Some(remaining) = current_group_remaining {
-                        if remaining == 0 {
-                            println!("Found damaged spring but expected operational. Invalid arrangement");
-                            return 0;
-                        } else {
-                            current_group_remaining = Some(remaining - 1);
-                        }
-                    } else {
-                        current_group_remaining = Some(*group_iter.next().unwrap_or(&0));
-                        if current_group_remaining.unwrap() == 0 {
-                            println!("Found damaged spring but no groups remaining. Invalid arrangement");
-                            return 0;
-                        }
-                        current_group_remaining = Some(current_group_remaining.unwrap() - 1);
-                    }
-                },
-            }
-            last_spring = Some(*spring);
-        }
+/// Flags shared by both parts. Each day picks its own fold when `--fold` is left out.
+#[derive(Args, Debug, Clone)]
+pub struct ArrangementOptions {
+    /// How many copies of each row to join with `?`, and of its groups
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    fold: Option<u32>,
+    /// Print arrangements of the Nth row, counting from 1, after folding it
+    #[clap(long, value_name = "N")]
+    show_row: Option<usize>,
+    /// Most arrangements to print
+    #[clap(long, default_value_t = 20)]
+    limit: usize,
+    /// Print arrangements picked at random instead of the first ones in order
+    #[clap(long)]
+    sample: bool,
+    /// Seed for --sample
+    #[clap(long, default_value_t = 0)]
+    seed: u64,
+}
 
-        if current_group_remaining.is_some() && current_group_remaining.unwrap() > 0 {
-            println!("Damage count for group not satisfied but no springs left. Invalid arrangement");
-            return 0;
+impl ArrangementOptions {
+    /// Sum the arrangements of every row after folding it, printing the chosen row's
+    /// arrangements along the way.
+    pub fn solve(&self, bytes: Vec<u8>, default_fold: u32) -> Result<u64, ParseError> {
+        let fold = self.fold.unwrap_or(default_fold) as usize;
+        let mut sum: u64 = 0;
+        let mut rows = 0;
+        for (idx, row) in RowReader::new(bytes).enumerate() {
+            let row = row?.unfold(fold);
+            rows += 1;
+            let arrangements = row.count_arrangements().ok_or_else(|| {
+                ParseError::new(format!("row {} has more than u64::MAX arrangements", idx + 1))
+            })?;
+            if self.show_row == Some(idx + 1) {
+                self.show(&row, arrangements);
+            }
+            sum = sum.checked_add(arrangements).ok_or_else(|| {
+                ParseError::new(String::from("the arrangement sum overflows a u64"))
+            })?;
         }
-
-        if group_iter.next().is_some() {
-            println!("Groups remaining but springs left. Invalid arrangement");
-            return 0;
+        match self.show_row {
+            Some(row) if row == 0 || row > rows => {
+                Err(ParseError::new(format!("there is no row {row}, only {rows} rows")))
+            }
+            _ => Ok(sum),
         }
-
-        println!("\nFound valid arrangement: {row}\n");
-        1
     }
 
-    fn solve(&mut self) -> u64 {
-        let mut sum: u64 = 0;
-        while let Some(row) = self.next_line() {
-            println!("\n\n\n");
-            let arrangements = Self::count_arrangements(&row) as u64;
-            println!("{:?}", row);
-            println!("Arrangements: {}", arrangements);
-            sum += arrangements;
+    fn show(&self, row: &SpringRow, arrangements: u64) {
+        println!("{row}: {arrangements} arrangements");
+        let shown = match self.sample {
+            true => row.sample(self.limit, &mut Rng(self.seed)),
+            false => row.arrangements(self.limit),
+        };
+        for arrangement in shown {
+            println!("  {arrangement}");
         }
-        sum
     }
 }
 
 #[derive(Parser, Debug)]
 pub struct Day12a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    #[clap(flatten)]
+    options: ArrangementOptions,
 }
 
 impl CommandImpl for Day12a {
     fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day12a: {answer}");
-        Ok(())
+        let options = self.options.clone();
+        self.input.for_each(|bytes| {
+            let answer = options.solve(bytes, 1)?;
+            println!("Day12a: {answer}");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+???.### 1,1,3
+.??..??...?##. 1,1,3
+?#?#?#?#?#?#?#? 1,3,1,6
+????.#...#... 4,1,1
+????.######..#####. 1,6,5
+?###???????? 3,2,1
+";
+
+    fn row(line: &str) -> SpringRow {
+        RowReader::new(line.as_bytes().to_vec()).next().unwrap().unwrap()
+    }
+
+    fn solve(input: &[u8]) -> u64 {
+        RowReader::new(input.to_vec()).map(|row| row.unwrap().count_arrangements().unwrap()).sum()
+    }
+
+    fn parse(input: &[u8]) -> Result<Vec<SpringRow>, ParseError> {
+        RowReader::new(input.to_vec()).collect()
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 21;
+            input real: "day12.txt" => 8419;
+        }
+    }
+
+    #[test]
+    fn arrangements() {
+        let last = row("?###???????? 3,2,1");
+        assert_eq!(last.count_arrangements(), Some(10));
+        assert_eq!(last.arrangements(3), [".###....##.#", ".###...##..#", ".###...##.#."]);
+        assert_eq!(last.arrangements(100).len(), 10);
+        let samples = last.sample(5, &mut Rng(7));
+        assert_eq!(samples.len(), 5);
+        assert!(samples.iter().all(|arrangement| arrangement.starts_with(".###.")));
+
+        let impossible = row("#.# 3");
+        assert_eq!(impossible.count_arrangements(), Some(0));
+        assert!(impossible.arrangements(10).is_empty());
+        assert!(impossible.sample(10, &mut Rng(0)).is_empty());
+    }
+
+    #[test]
+    fn folds() {
+        assert_eq!(row(".# 1").unfold(3).to_string(), ".#?.#?.# 1,1,1");
+        assert_eq!(row("???.### 1,1,3").unfold(5).count_arrangements(), Some(1));
+        assert_eq!(row("?###???????? 3,2,1").unfold(5).count_arrangements(), Some(506250));
+        // With no groups every ? is operational, so folding a lone ? keeps one arrangement
+        assert_eq!(row("? ").unfold(64).count_arrangements(), Some(1));
+        // 64 groups of one fit in 191 springs C(128, 64) ways, which is more than a u64 holds
+        assert_eq!(row("?? 1").unfold(64).count_arrangements(), None);
+    }
+
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
@@ -1,178 +1,404 @@
-use std::{path::PathBuf, fmt::{Display, Formatter, self}};
+use std::fmt;
 
-use clap::Parser;
+use clap::{Args, Parser};
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::{
+    render::{Render, RenderOptions, Rgb},
+    utils::{AsciiReader, Inputs, ParseError},
+};
 
 use super::{CommandImpl, DynError};
 
-struct Solver {
-    reader: AsciiReader,
+/// The widest or tallest pattern that fits in a mask.
+pub const MAX_SIDE: usize = u128::BITS as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Axis {
+    /// A line between two columns
+    Vertical,
+    /// A line between two rows
+    Horizontal,
 }
 
-#[derive(Debug, PartialEq)]
-enum Tile {
-    Ash,
-    Rock
+/// A line of reflection, `line` columns or rows in, and how many tiles its reflection gets
+/// wrong.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reflection {
+    pub axis: Axis,
+    pub line: usize,
+    pub mismatches: u32,
 }
 
-impl Display for Tile {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match self {
-            Tile::Ash => write!(f, "."),
-            Tile::Rock => write!(f, "#"),
+impl Reflection {
+    /// Columns left of a vertical line, or 100 per row above a horizontal one.
+    pub fn summary(&self) -> u64 {
+        match self.axis {
+            Axis::Vertical => self.line as u64,
+            Axis::Horizontal => self.line as u64 * 100,
         }
     }
 }
 
-struct Pattern {
-    tiles: Vec<Vec<Tile>>,
-    height: usize,
-    width: usize
+impl fmt::Display for Reflection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (axis, side) = match self.axis {
+            Axis::Vertical => ("vertical", "columns"),
+            Axis::Horizontal => ("horizontal", "rows"),
+        };
+        write!(f, "{axis} line after {} {side}: {} mismatches", self.line, self.mismatches)
+    }
+}
+
+/// A pattern with every row and every column packed into a mask, bit `i` set for rock at
+/// column or row `i`, so comparing two of them is one XOR and a popcount.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub rows: Vec<u128>,
+    pub columns: Vec<u128>,
 }
 
 impl Pattern {
-    fn new() -> Self {
-        Self {
-            tiles: Vec::new(),
-            height: 0,
-            width: 0
-        }
+    /// How many tiles differ between the lines reflected across the line after `line` of them.
+    fn mismatches(lines: &[u128], line: usize) -> u32 {
+        lines[..line]
+            .iter()
+            .rev()
+            .zip(&lines[line..])
+            .map(|(before, after)| (before ^ after).count_ones())
+            .sum()
     }
 
-    fn add_row(&mut self, row: Vec<Tile>) {
-        if self.tiles.is_empty() {
-            self.width = row.len();
-        } else if row.len() != self.width {
-            panic!("invalid row length");
-        }
-        self.tiles.push(row);
-        self.height += 1;
+    fn lines(axis: Axis, lines: &[u128]) -> impl Iterator<Item = Reflection> + '_ {
+        (1..lines.len()).map(move |line| Reflection {
+            axis,
+            line,
+            mismatches: Self::mismatches(lines, line),
+        })
     }
 
-    pub fn symmetry_column(&self) -> Option<usize> {
-        (0..(self.width-1)).find(|&x| self.check_vertical_symmetry(x))
+    /// Every line between two columns, then every line between two rows.
+    pub fn candidates(&self) -> impl Iterator<Item = Reflection> + '_ {
+        Self::lines(Axis::Vertical, &self.columns).chain(Self::lines(Axis::Horizontal, &self.rows))
     }
 
-    pub fn symmetry_row(&self) -> Option<usize> {
-        (0..(self.height-1)).find(|&y| self.check_horizontal_symmetry(y))
+    /// The first line whose reflection is off by exactly `smudges` tiles.
+    pub fn reflection(&self, smudges: u32) -> Option<Reflection> {
+        self.candidates().find(|candidate| candidate.mismatches == smudges)
     }
+}
 
-    fn check_vertical_symmetry(&self, column: usize) -> bool {
-        let mut left = column;
-        let mut right = column + 1;
-
-        loop {
-            for y in 0..self.height {
-                if self.tiles[y][left] != self.tiles[y][right] {
-                    return false;
-                }
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in &self.rows {
+            for x in 0..self.columns.len() {
+                write!(f, "{}", if row >> x & 1 == 1 { '#' } else { '.' })?;
             }
-            if left == 0 || right == self.width - 1 {
-                return true;
-            }
-            left -= 1;
-            right += 1;
+            writeln!(f)?;
         }
+        Ok(())
     }
+}
 
-    fn check_horizontal_symmetry(&self, row: usize) -> bool {
-        let mut top = row;
-        let mut bottom = row + 1;
+impl Render for Pattern {
+    fn size(&self) -> (usize, usize) {
+        (self.columns.len(), self.rows.len())
+    }
 
-        loop {
-            for x in 0..self.width {
-                if self.tiles[top][x] != self.tiles[bottom][x] {
-                    return false;
-                }
-            }
-            if top == 0 || bottom == self.height - 1 {
-                return true;
-            }
-            top -= 1;
-            bottom += 1;
+    fn color(&self, x: usize, y: usize) -> Rgb {
+        if self.rows[y] >> x & 1 == 1 {
+            Rgb(180, 180, 190)
+        } else {
+            Rgb(30, 30, 40)
         }
     }
 }
 
-impl Display for Pattern {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        for row in &self.tiles {
-            for tile in row {
-                write!(f, "{}", tile)?;
+/// Patterns drawn one below the other with a blank row between each, for `--image`.
+pub struct Sheet<'a>(pub &'a [Pattern]);
+
+impl Render for Sheet<'_> {
+    fn size(&self) -> (usize, usize) {
+        let width = self.0.iter().map(|pattern| pattern.columns.len()).max().unwrap_or(0);
+        let height = self.0.iter().map(|pattern| pattern.rows.len() + 1).sum::<usize>();
+        (width, height.saturating_sub(1))
+    }
+
+    fn color(&self, x: usize, mut y: usize) -> Rgb {
+        for pattern in self.0 {
+            if y < pattern.rows.len() {
+                return if x < pattern.columns.len() { pattern.color(x, y) } else { Rgb::BLACK };
             }
-            writeln!(f)?;
+            if y == pattern.rows.len() {
+                return Rgb::BLACK;
+            }
+            y -= pattern.rows.len() + 1;
         }
-        Ok(())
+        Rgb::BLACK
     }
 }
 
-impl Solver {
-    fn new(input: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(input),
-        }
+pub struct PatternReader {
+    reader: AsciiReader,
+    /// Line number of the next line, for errors
+    line: usize,
+}
+
+impl PatternReader {
+    pub fn new(input: Vec<u8>) -> Self {
+        Self { reader: AsciiReader::new(input), line: 1 }
     }
 
-    fn next_pattern(&mut self) -> Option<Pattern> {
-        let mut pattern = Pattern::new();
+    fn next_pattern(&mut self) -> Result<Option<Pattern>, ParseError> {
+        let mut rows: Vec<u128> = Vec::new();
+        let mut columns: Vec<u128> = Vec::new();
 
-        loop {
-            let next_row = self.reader.read_until(b'\n');
-            if next_row.is_none() {
-                break;
-            }
-            let next_row = next_row.unwrap();
-            if next_row.is_empty() || next_row[0] == b'\n' {
-                if pattern.tiles.is_empty() {
-                    self.reader.skip(1);
+        while let Some(next_row) = self.reader.read_line() {
+            let line = self.line;
+            self.line += 1;
+            if next_row.is_empty() {
+                if rows.is_empty() {
                     continue;
                 } else {
                     break;
                 }
             }
 
-            pattern.add_row(next_row.iter().map(|&b| match b {
-                b'.' => Tile::Ash,
-                b'#' => Tile::Rock,
-                _ => panic!("invalid tile"),
-            }).collect());
+            if rows.is_empty() {
+                if next_row.len() > MAX_SIDE {
+                    let msg = format!("line {line} is wider than {MAX_SIDE} tiles");
+                    return Err(ParseError::new(msg));
+                }
+                columns = vec![0; next_row.len()];
+            } else if next_row.len() != columns.len() {
+                let msg = format!(
+                    "line {line} is {} tiles wide, expected {}",
+                    next_row.len(),
+                    columns.len()
+                );
+                return Err(ParseError::new(msg));
+            }
+            if rows.len() == MAX_SIDE {
+                let msg = format!("pattern ending on line {line} is taller than {MAX_SIDE} tiles");
+                return Err(ParseError::new(msg));
+            }
 
-            self.reader.skip(1);
+            let y = rows.len();
+            let mut row = 0;
+            for (x, &tile) in next_row.iter().enumerate() {
+                match tile {
+                    b'#' => {
+                        row |= 1 << x;
+                        columns[x] |= 1 << y;
+                    }
+                    b'.' => (),
+                    _ => return Err(ParseError::new(format!("invalid tile {:?}", tile as char))),
+                }
+            }
+            rows.push(row);
         }
 
-        if pattern.tiles.is_empty() {
-            return None;
+        if rows.is_empty() {
+            return Ok(None);
         }
-        
-        Some(pattern)
+
+        Ok(Some(Pattern { rows, columns }))
     }
+}
 
-    fn solve(&mut self) -> u64 {
-        let mut sum = 0_u64;
+impl Iterator for PatternReader {
+    type Item = Result<Pattern, ParseError>;
 
-        while let Some(pattern) = self.next_pattern() {
-            if let Some(symmetry_column) = pattern.symmetry_column() {
-                sum += (symmetry_column + 1) as u64;
-            } else if let Some(symmetry_row) = pattern.symmetry_row() {
-                sum += ((symmetry_row + 1) * 100) as u64;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_pattern().transpose()
+    }
+}
+
+/// Flags shared by both parts. Each day picks its own smudge count when `--smudges` is left
+/// out.
+#[derive(Args, Debug, Clone)]
+pub struct SmudgeOptions {
+    /// How many tiles the reflection must get wrong
+    #[clap(long, value_name = "K")]
+    smudges: Option<u32>,
+    /// Print every possible line in every pattern with how many tiles its reflection gets wrong
+    #[clap(long)]
+    candidates: bool,
+    #[clap(flatten)]
+    render: RenderOptions,
+}
+
+impl SmudgeOptions {
+    pub fn solve(&self, bytes: Vec<u8>, default_smudges: u32) -> Result<u64, DynError> {
+        let smudges = self.smudges.unwrap_or(default_smudges);
+        let mut frames = self.render.frames()?;
+        let mut sheet = self.render.wants_image().then(Vec::new);
+        let mut sum = 0_u64;
+        for (idx, pattern) in PatternReader::new(bytes).enumerate() {
+            let pattern = pattern?;
+            if let Some(frames) = &mut frames {
+                frames.frame(&pattern);
+            }
+            if self.candidates {
+                println!("pattern {}:", idx + 1);
+                for candidate in pattern.candidates() {
+                    println!("  {candidate}");
+                }
+            }
+            let reflection = pattern.reflection(smudges).ok_or_else(|| {
+                ParseError::new(format!(
+                    "no line of reflection with {smudges} smudges in pattern {}:\n{pattern}",
+                    idx + 1
+                ))
+            })?;
+            sum += reflection.summary();
+            if let Some(sheet) = &mut sheet {
+                sheet.push(pattern);
             }
         }
-        
-        sum
+        if let Some(frames) = frames {
+            frames.finish()?;
+        }
+        if let Some(sheet) = sheet {
+            self.render.save(&Sheet(&sheet))?;
+        }
+        Ok(sum)
     }
 }
 
 #[derive(Parser, Debug)]
 pub struct Day13a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    #[clap(flatten)]
+    options: SmudgeOptions,
 }
 
 impl CommandImpl for Day13a {
     fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day 13 A: {answer}");
-        Ok(())
+        let options = self.options.clone();
+        self.input.for_each(|bytes| {
+            let answer = options.solve(bytes, 0)?;
+            println!("Day 13 A: {answer}");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.##..##.
+
+#...##..#
+#....#..#
+..##..###
+#####.##.
+#####.##.
+..##..###
+#....#..#
+";
+
+    fn solve(input: &[u8]) -> u64 {
+        parse(input).unwrap().iter().map(|pattern| pattern.reflection(0).unwrap().summary()).sum()
+    }
+
+    fn parse(input: &[u8]) -> Result<Vec<Pattern>, ParseError> {
+        PatternReader::new(input.to_vec()).collect()
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 405;
+        }
+    }
+
+    #[test]
+    fn candidates() {
+        let patterns = parse(EXAMPLE.as_bytes()).unwrap();
+        let counts: Vec<(Axis, usize, u32)> = patterns[0]
+            .candidates()
+            .filter(|candidate| candidate.mismatches <= 1)
+            .map(|candidate| (candidate.axis, candidate.line, candidate.mismatches))
+            .collect();
+        assert_eq!(
+            counts,
+            [(Axis::Vertical, 5, 0), (Axis::Horizontal, 3, 1), (Axis::Horizontal, 6, 1)]
+        );
+        assert_eq!(patterns[0].candidates().count(), 8 + 6);
+        assert_eq!(
+            patterns[1].reflection(1).unwrap().to_string(),
+            "horizontal line after 1 rows: 1 mismatches"
+        );
+        assert_eq!(patterns[0].reflection(2).map(|line| line.summary()), Some(1));
+        assert_eq!(patterns[0].reflection(100), None);
+        assert_eq!(
+            patterns[0].to_string(),
+            EXAMPLE.split("\n\n").next().unwrap().to_owned() + "\n"
+        );
+    }
+
+    #[test]
+    fn sheet_of_patterns() {
+        let patterns = parse(b"#.#\n...\n\n#\n#\n").unwrap();
+        let sheet = Sheet(&patterns);
+        assert_eq!(sheet.size(), (3, 5));
+        let canvas = sheet.canvas(1);
+        let rock = |x: usize, y: usize| canvas.pixels[y * 3 + x] == Rgb(180, 180, 190);
+        assert!(rock(0, 0) && !rock(1, 0) && rock(2, 0));
+        assert_eq!(canvas.pixels[6..9], [Rgb::BLACK; 3]);
+        assert!(rock(0, 3) && rock(0, 4) && !rock(1, 4));
+    }
+
+    #[test]
+    fn ragged_patterns() {
+        assert!(parse(b"#.#\n##\n").is_err());
+        assert!(parse(b"#.#\n###\n\n##\n#\n").is_err());
+        assert!(parse("#".repeat(129).as_bytes()).is_err());
+        assert!(parse("#\n".repeat(129).as_bytes()).is_err());
+        assert_eq!(parse(b"#.#\n###\n\n##\n..\n").unwrap().len(), 2);
+    }
+
+    proptest! {
+        #[test]
+        fn masks_match_tiles(rows in (1..20usize).prop_flat_map(|width| {
+            vec(vec(any::<bool>(), width), 1..20)
+        })) {
+            let text: String = rows
+                .iter()
+                .flat_map(|row| row.iter().map(|&rock| if rock { '#' } else { '.' }).chain(['\n']))
+                .collect();
+            let pattern = parse(text.as_bytes()).unwrap().remove(0);
+            for candidate in pattern.candidates() {
+                let line = candidate.line;
+                let tile = |y: usize, x: usize| match candidate.axis {
+                    Axis::Vertical => rows[y][x],
+                    Axis::Horizontal => rows[x][y],
+                };
+                let (across, along) = match candidate.axis {
+                    Axis::Vertical => (rows[0].len(), rows.len()),
+                    Axis::Horizontal => (rows.len(), rows[0].len()),
+                };
+                let expected = (0..line.min(across - line))
+                    .flat_map(|offset| (0..along).map(move |y| (offset, y)))
+                    .filter(|&(offset, y)| tile(y, line - 1 - offset) != tile(y, line + offset))
+                    .count();
+                prop_assert_eq!(candidate.mismatches as usize, expected);
+            }
+        }
+    }
+
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
@@ -1,24 +1,66 @@
-use std::{path::PathBuf, fmt::{self, Display, Formatter}};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
 
-use clap::Parser;
+use clap::{Args, Parser};
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::{
+    animation::AnimateOptions,
+    render::{Render, RenderOptions, Rgb},
+    utils::{AsciiReader, Inputs, ParseError},
+};
 
 use super::{CommandImpl, DynError};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Direction {
     North,
     East,
     South,
-    West
+    West,
+}
+
+/// The directions to tilt in, in order, for one spin.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Spin(pub Vec<Direction>);
+
+impl Spin {
+    /// Read a spin script such as `NWSE`, one letter per tilt.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if text.is_empty() {
+            return Err(String::from("the spin needs at least one direction"));
+        }
+        text.chars()
+            .map(|letter| match letter.to_ascii_uppercase() {
+                'N' => Ok(Direction::North),
+                'E' => Ok(Direction::East),
+                'S' => Ok(Direction::South),
+                'W' => Ok(Direction::West),
+                _ => Err(format!("{letter:?} is not one of N, E, S or W")),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+/// A count written out in full or in scientific notation, like `1000000000` or `1e9`.
+pub fn parse_count(text: &str) -> Result<u64, String> {
+    let (mantissa, exponent) = text.split_once(['e', 'E']).unwrap_or((text, "0"));
+    let invalid = || format!("{text:?} is not a whole number like 1000 or 1e9");
+    let mantissa: u64 = mantissa.parse().map_err(|_| invalid())?;
+    let exponent: u32 = exponent.parse().map_err(|_| invalid())?;
+    10u64
+        .checked_pow(exponent)
+        .and_then(|scale| mantissa.checked_mul(scale))
+        .ok_or_else(|| format!("{text} does not fit in a u64"))
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Tile {
     Empty,
     RollingRock,
-    FixedRock
+    FixedRock,
 }
 
 impl Display for Tile {
@@ -31,155 +73,159 @@ impl Display for Tile {
     }
 }
 
-
 #[derive(PartialEq, Hash, Eq, Clone)]
 pub struct Board {
-    tiles: Vec<Vec<Tile>>,
-    height: usize,
-    width: usize
+    /// Row by row
+    pub tiles: Vec<Tile>,
+    pub height: usize,
+    pub width: usize,
 }
 
 impl Board {
-    pub fn read(reader: &mut AsciiReader) -> Self {
-        let mut tiles: Vec<Vec<Tile>> = Vec::new();
-        let mut width = 0;
+    pub fn read(reader: &mut AsciiReader) -> Result<Self, ParseError> {
+        let mut tiles: Vec<Tile> = Vec::new();
+        let mut width = None;
         let mut height = 0;
-        
-        loop {
-            let next_row = reader.read_until(b'\n');
-            if next_row.is_none() {
-                break;
-            }
-            height += 1;
-            let next_row = next_row.unwrap();
-            if next_row.is_empty() || next_row[0] == b'\n' {
-                if tiles.is_empty() {
-                    reader.skip(1);
+
+        while let Some(next_row) = reader.read_line() {
+            if next_row.is_empty() {
+                if height == 0 {
                     continue;
                 } else {
                     break;
                 }
             }
 
-            tiles.push(next_row.iter().map(|&b| match b {
-                b'.' => Tile::Empty,
-                b'#' => Tile::FixedRock,
-                b'O' => Tile::RollingRock,
-                _ => panic!("invalid tile"),
-            }).collect());
-
-            reader.skip(1);
+            if *width.get_or_insert(next_row.len()) != next_row.len() {
+                let msg = format!("row {} is not as wide as the first", height + 1);
+                return Err(ParseError::new(msg));
+            }
+            for &b in next_row {
+                tiles.push(match b {
+                    b'.' => Tile::Empty,
+                    b'#' => Tile::FixedRock,
+                    b'O' => Tile::RollingRock,
+                    _ => return Err(ParseError::new(format!("invalid tile {:?}", b as char))),
+                });
+            }
+            height += 1;
         }
 
-        width = tiles[0].len();
+        let Some(width) = width else {
+            return Err(ParseError::new(String::from("board has no tiles")));
+        };
 
-        Self {
-            tiles,
-            width,
-            height
-        }
+        Ok(Self { tiles, width, height })
     }
 
+    /// The lines the rocks roll along when tilting towards `direction`, each as the index of
+    /// the tile the rocks roll towards, the step away from it and the number of tiles.
+    fn lines(&self, direction: Direction) -> impl Iterator<Item = (usize, isize, usize)> {
+        let (width, height) = (self.width, self.height);
+        let (count, first, across, along, length): (usize, usize, usize, isize, usize) =
+            match direction {
+                Direction::North => (width, 0, 1, width as isize, height),
+                Direction::South => (width, (height - 1) * width, 1, -(width as isize), height),
+                Direction::West => (height, 0, width, 1, width),
+                Direction::East => (height, width - 1, width, -1, width),
+            };
+        (0..count).map(move |line| (first + line * across, along, length))
+    }
+
+    /// Tilt the board so every rolling rock rolls as far as it can towards `direction`. Each
+    /// line is read once: the rolling rocks between two fixed rocks are counted and cleared,
+    /// then stacked against the nearer fixed rock or the edge.
     pub fn slide(&mut self, direction: Direction) {
-        match direction {
-            Direction::North => {
-                let mut rock_moved = true;
-                while rock_moved {
-                    rock_moved = false;
-                    for col in 0..self.width {
-                        for row in 1..self.height {
-                            let current_tile = &self.tiles[row][col];
-                            let tile_above = &self.tiles[row - 1][col];
-
-                            if current_tile == &Tile::RollingRock && tile_above == &Tile::Empty {
-                                rock_moved = true;
-                                self.tiles[row - 1][col] = Tile::RollingRock;
-                                self.tiles[row][col] = Tile::Empty;
-                            }
-                        }           
-                    }
-                }
-            },
-            Direction::East => {
-                let mut rock_moved = true;
-                while rock_moved {
-                    rock_moved = false;
-                    for row in 0..self.height {
-                        for col in (0..self.width-1) {
-                            let current_tile = &self.tiles[row][col];
-                            let east_tile = &self.tiles[row][col + 1];
-
-                            if current_tile == &Tile::RollingRock && east_tile == &Tile::Empty {
-                                rock_moved = true;
-                                self.tiles[row][col + 1] = Tile::RollingRock;
-                                self.tiles[row][col] = Tile::Empty;
-                            }
-                        }           
-                    }
-                }
-            },
-            Direction::South => {
-                let mut rock_moved = true;
-                while rock_moved {
-                    rock_moved = false;
-                    for col in 0..self.width {
-                        for row in (0..self.height-1).rev() {
-                            let current_tile = &self.tiles[row][col];
-                            let tile_below = &self.tiles[row + 1][col];
-
-                            if current_tile == &Tile::RollingRock && tile_below == &Tile::Empty {
-                                rock_moved = true;
-                                self.tiles[row + 1][col] = Tile::RollingRock;
-                                self.tiles[row][col] = Tile::Empty;
-                            }
-                        }           
+        for (first, along, length) in self.lines(direction) {
+            let at = |step: usize| first.wrapping_add_signed(along * step as isize);
+            let mut segment_start = 0;
+            let mut rolling = 0;
+            for step in 0..=length {
+                let tile = if step < length { self.tiles[at(step)] } else { Tile::FixedRock };
+                match tile {
+                    Tile::RollingRock => {
+                        rolling += 1;
+                        self.tiles[at(step)] = Tile::Empty;
                     }
-                }
-            },
-            Direction::West => {
-                let mut rock_moved = true;
-                while rock_moved {
-                    rock_moved = false;
-                    for row in 0..self.height {
-                        for col in (1..self.width).rev() {
-                            let current_tile = &self.tiles[row][col];
-                            let east_tile = &self.tiles[row][col - 1];
-
-                            if current_tile == &Tile::RollingRock && east_tile == &Tile::Empty {
-                                rock_moved = true;
-                                self.tiles[row][col - 1] = Tile::RollingRock;
-                                self.tiles[row][col] = Tile::Empty;
-                            }
-                        }           
+                    Tile::FixedRock => {
+                        for settled in segment_start..segment_start + rolling {
+                            self.tiles[at(settled)] = Tile::RollingRock;
+                        }
+                        segment_start = step + 1;
+                        rolling = 0;
                     }
+                    Tile::Empty => (),
                 }
-            },
+            }
         }
     }
 
-    pub fn cycle(&mut self) {
-        self.slide(Direction::North);
-        self.slide(Direction::West);
-        self.slide(Direction::South);
-        self.slide(Direction::East);
+    pub fn spin(&mut self, script: &[Direction]) {
+        for &direction in script {
+            self.slide(direction);
+        }
     }
 
+    /// Every rolling rock adds the number of rows from it to the south edge, inclusive.
     pub fn calculate_load(&self) -> u64 {
-        let mut load: u64 = 0;
-        for row in 0..self.height {
-            for col in 0..self.width {
-                if self.tiles[row][col] == Tile::RollingRock {
-                    load += self.height as u64 - row as u64;
-                }
+        self.tiles
+            .chunks(self.width)
+            .enumerate()
+            .map(|(row, tiles)| {
+                let rocks = tiles.iter().filter(|&&tile| tile == Tile::RollingRock).count();
+                rocks as u64 * (self.height - row) as u64
+            })
+            .sum()
+    }
+
+    /// Run the spin `cycles` times. Once the board comes back to a layout it had before, it
+    /// repeats from there, so the rest of the spins are skipped.
+    pub fn spin_cycles(self, script: &[Direction], cycles: u64) -> Spun {
+        self.spin_cycles_with(script, cycles, |_, _, _| ())
+    }
+
+    /// `spin_cycles`, calling `watch` after every tilt with the board, which spin it is
+    /// counting from one, and the direction it was tilted in.
+    pub fn spin_cycles_with<F>(mut self, script: &[Direction], cycles: u64, mut watch: F) -> Spun
+    where
+        F: FnMut(&Board, u64, Direction),
+    {
+        let mut seen: HashMap<Vec<Tile>, u64> = HashMap::new();
+        let mut loads = Vec::new();
+        for spins in 0..cycles {
+            if let Some(&start) = seen.get(&self.tiles) {
+                let period = spins - start;
+                let load = loads[(start + (cycles - start) % period) as usize];
+                return Spun { load, repeat: Some(Repeat { start, period }) };
+            }
+            seen.insert(self.tiles.clone(), spins);
+            loads.push(self.calculate_load());
+            for &direction in script {
+                self.slide(direction);
+                watch(&self, spins + 1, direction);
             }
         }
-        load
+        Spun { load: self.calculate_load(), repeat: None }
+    }
+}
+
+impl Render for Board {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn color(&self, x: usize, y: usize) -> Rgb {
+        match self.tiles[y * self.width + x] {
+            Tile::Empty => Rgb(20, 20, 30),
+            Tile::FixedRock => Rgb(110, 110, 120),
+            Tile::RollingRock => Rgb(230, 180, 60),
+        }
     }
 }
 
 impl Display for Board {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        for row in &self.tiles {
+        for row in self.tiles.chunks(self.width) {
             for tile in row {
                 write!(f, "{}", tile)?;
             }
@@ -189,36 +235,235 @@ impl Display for Board {
     }
 }
 
+/// From `start` spins on, the board repeats the same layouts every `period` spins.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Repeat {
+    pub start: u64,
+    pub period: u64,
+}
+
+/// The north load once every spin is done, and where the spins started repeating if they did
+/// before the end.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Spun {
+    pub load: u64,
+    pub repeat: Option<Repeat>,
+}
 
-struct Solver {
-    reader: AsciiReader,
+/// Flags shared by both parts. Each day fills in the ones left out.
+#[derive(Args, Debug, Clone)]
+pub struct SpinOptions {
+    /// Directions to tilt in order, such as NWSE
+    #[clap(long, value_parser = Spin::parse)]
+    spin: Option<Spin>,
+    /// How many times to run the spin, such as 1000 or 1e9
+    #[clap(long, value_parser = parse_count)]
+    cycles: Option<u64>,
+    #[clap(flatten)]
+    animate: AnimateOptions,
+    #[clap(flatten)]
+    render: RenderOptions,
 }
 
-impl Solver {
-    fn new(input: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(input),
+impl SpinOptions {
+    pub fn solve(&self, bytes: Vec<u8>, spin: &[Direction], cycles: u64) -> Result<Spun, DynError> {
+        let board = Board::read(&mut AsciiReader::new(bytes))?;
+        let spin = self.spin.as_ref().map_or(spin, |spin| &spin.0);
+        let cycles = self.cycles.unwrap_or(cycles);
+
+        let mut animation = self.animate.animation();
+        let mut frames = self.render.frames()?;
+        if let Some(animation) = &mut animation {
+            animation.frame("Start", &board.to_string());
+        }
+        if let Some(frames) = &mut frames {
+            frames.frame(&board);
         }
+        // The board after each spin, when it is needed for --image
+        let mut spun_boards = self.render.wants_image().then(|| vec![board.clone()]);
+        let spun = board.spin_cycles_with(spin, cycles, |board, spins, direction| {
+            if let Some(animation) = &mut animation {
+                animation.frame(&format!("Spin {spins}, tilted {direction:?}"), &board.to_string());
+            }
+            if let Some(frames) = &mut frames {
+                frames.frame(board);
+            }
+            if let Some(boards) = &mut spun_boards {
+                boards.truncate(spins as usize);
+                boards.push(board.clone());
+            }
+        });
+        if let Some(animation) = animation {
+            animation.finish()?;
+        }
+        if let Some(frames) = frames {
+            frames.finish()?;
+        }
+        if let Some(boards) = spun_boards {
+            // Once the spins repeat, the last board is one seen before
+            let last = match spun.repeat {
+                Some(Repeat { start, period }) => start + (cycles - start) % period,
+                None => cycles,
+            };
+            self.render.save(&boards[last as usize])?;
+        }
+        Ok(spun)
     }
+}
 
-    fn solve(&mut self) -> u64 {
-        let mut board = Board::read(&mut self.reader);
-        board.slide(Direction::North);
-        
-        board.calculate_load()
+/// Print the cycle, if one was found, before the load.
+pub fn report(day: &str, spun: Spun) {
+    if let Some(Repeat { start, period }) = spun.repeat {
+        println!("Spins repeat after {start} spins with a period of {period}");
     }
+    println!("{day}: {}", spun.load);
 }
 
 #[derive(Parser, Debug)]
 pub struct Day14a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    #[clap(flatten)]
+    options: SpinOptions,
 }
 
 impl CommandImpl for Day14a {
     fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day 14 A: {answer}");
-        Ok(())
+        let options = self.options.clone();
+        self.input.for_each(|bytes| {
+            report("Day 14 A", options.solve(bytes, &[Direction::North], 1)?);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....
+";
+
+    fn solve(input: &[u8]) -> u64 {
+        let mut board = parse(input).unwrap();
+        board.slide(Direction::North);
+        board.calculate_load()
+    }
+
+    fn parse(input: &[u8]) -> Result<Board, ParseError> {
+        Board::read(&mut AsciiReader::new(input.to_vec()))
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 136;
+        }
+    }
+
+    #[test]
+    fn spin_scripts() {
+        assert_eq!(Spin::parse("nwSE").unwrap().0.len(), 4);
+        assert!(Spin::parse("").is_err());
+        assert!(Spin::parse("NX").is_err());
+        assert_eq!(parse_count("1e9"), Ok(1_000_000_000));
+        assert_eq!(parse_count("25"), Ok(25));
+        assert_eq!(parse_count("3E2"), Ok(300));
+        assert!(parse_count("1e20").is_err());
+        assert!(parse_count("1.5e3").is_err());
+        assert!(parse_count("e").is_err());
+    }
+
+    #[test]
+    fn spin_cycle() {
+        let board = parse(EXAMPLE.as_bytes()).unwrap();
+        let mut once = board.clone();
+        once.spin(&Spin::parse("NWSE").unwrap().0);
+        assert_eq!(
+            once.to_string(),
+            "\
+.....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....
+"
+        );
+        let spun = board.clone().spin_cycles(&Spin::parse("NWSE").unwrap().0, 1_000_000_000);
+        assert_eq!(spun, Spun { load: 64, repeat: Some(Repeat { start: 3, period: 7 }) });
+
+        let mut tilts = vec![];
+        board.spin_cycles_with(&Spin::parse("NWSE").unwrap().0, 1, |board, spins, direction| {
+            tilts.push((spins, direction, board.to_string()));
+        });
+        assert_eq!(tilts.len(), 4);
+        assert_eq!(tilts[3], (1, Direction::East, once.to_string()));
+    }
+
+    /// Move rocks one tile at a time until none can move.
+    fn bubble(board: &mut Board, direction: Direction) {
+        let (dx, dy): (isize, isize) = match direction {
+            Direction::North => (0, -1),
+            Direction::East => (1, 0),
+            Direction::South => (0, 1),
+            Direction::West => (-1, 0),
+        };
+        let mut moved = true;
+        while moved {
+            moved = false;
+            for y in 0..board.height {
+                for x in 0..board.width {
+                    let (nx, ny) = (x.wrapping_add_signed(dx), y.wrapping_add_signed(dy));
+                    if nx >= board.width || ny >= board.height {
+                        continue;
+                    }
+                    let (from, to) = (y * board.width + x, ny * board.width + nx);
+                    if board.tiles[from] == Tile::RollingRock && board.tiles[to] == Tile::Empty {
+                        board.tiles.swap(from, to);
+                        moved = true;
+                    }
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn slide_matches_bubbling(
+            rows in (1..12usize).prop_flat_map(|width| {
+                vec(vec(prop::sample::select(b".#O".to_vec()), width), 1..12)
+            }),
+            script in vec(prop::sample::select(b"NESW".to_vec()), 1..5),
+        ) {
+            let text: Vec<u8> = rows.iter().flat_map(|row| [&row[..], b"\n"].concat()).collect();
+            let mut board = parse(&text).unwrap();
+            let mut expected = board.clone();
+            for direction in Spin::parse(std::str::from_utf8(&script).unwrap()).unwrap().0 {
+                board.slide(direction);
+                bubble(&mut expected, direction);
+                prop_assert_eq!(board.to_string(), expected.to_string());
+            }
+        }
+    }
+
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
@@ -1,61 +1,68 @@
-use std::{path::PathBuf, collections::HashMap};
-
 use clap::Parser;
 
-use crate::{utils::{AsciiReader, slurp_bytes}, commands::day14a::{Board, Direction}};
+use crate::utils::Inputs;
 
-use super::{CommandImpl, DynError};
-struct Solver {
-    reader: AsciiReader,
-}
+use super::{
+    day14a::{report, Direction, SpinOptions},
+    CommandImpl, DynError,
+};
 
-impl Solver {
-    fn new(input: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(input),
-        }
-    }
+const SPIN: [Direction; 4] = [Direction::North, Direction::West, Direction::South, Direction::East];
 
-    fn solve(&mut self) -> u64 {
-        let mut board = Board::read(&mut self.reader);
+#[derive(Parser, Debug)]
+pub struct Day14b {
+    #[clap(flatten)]
+    input: Inputs,
+    #[clap(flatten)]
+    options: SpinOptions,
+}
 
-        let mut seen: HashMap<Board, usize> = HashMap::new();
-        let mut counter = 0;
-        let num_cycles = 1_000_000_000;
+impl CommandImpl for Day14b {
+    fn main(&mut self) -> Result<(), DynError> {
+        let options = self.options.clone();
+        self.input.for_each(|bytes| {
+            report("Day 14 B", options.solve(bytes, &SPIN, 1_000_000_000)?);
+            Ok(())
+        })
+    }
+}
 
-        let (cycle_interval_start, cycle_interval) = loop {
-            if seen.contains_key(&board) {
-                break (seen[&board], counter - seen[&board])
-            }
-            seen.insert(board.clone(), counter);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::day14a::Board;
+    use crate::testing::{puzzle_tests, robustness_tests};
+    use crate::utils::{AsciiReader, ParseError};
 
-            board.cycle();
+    const EXAMPLE: &str = "\
+O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....
+";
 
-            counter += 1;
-        };
+    fn solve(input: &[u8]) -> u64 {
+        parse_and_spin(input).unwrap()
+    }
 
-        let idx = (num_cycles - cycle_interval_start) % cycle_interval + cycle_interval_start;
+    fn parse_and_spin(input: &[u8]) -> Result<u64, ParseError> {
+        let board = Board::read(&mut AsciiReader::new(input.to_vec()))?;
+        Ok(board.spin_cycles(&SPIN, 1_000_000_000).load)
+    }
 
-        for (b, i) in seen {
-            if i == idx {
-                return b.calculate_load();
-            }
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 64;
         }
-        
-        panic!("No board found at index {}", idx);
     }
-}
-
-#[derive(Parser, Debug)]
-pub struct Day14b {
-    #[clap(long, short)]
-    input: PathBuf,
-}
 
-impl CommandImpl for Day14b {
-    fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day 14 B: {answer}");
-        Ok(())
+    robustness_tests! {
+        parse_and_spin: EXAMPLE;
     }
 }
@@ -1,8 +1,6 @@
-use std::{path::PathBuf, collections::HashMap};
-
 use clap::Parser;
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::utils::{AsciiReader, Inputs};
 
 use super::{CommandImpl, DynError};
 struct Solver {
@@ -56,14 +54,46 @@ impl Solver {
 
 #[derive(Parser, Debug)]
 pub struct Day15a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
 }
 
 impl CommandImpl for Day15a {
     fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day 15 A: {answer}");
-        Ok(())
+        self.input.for_each(|bytes| {
+            let answer = Solver::new(bytes).solve();
+            println!("Day 15 A: {answer}");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+    use crate::utils::ParseError;
+
+    const EXAMPLE: &str = "\
+rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7
+";
+
+    fn solve(input: &[u8]) -> u64 {
+        Solver::new(input.to_vec()).solve()
+    }
+
+    /// Any bytes hash, so reading the steps cannot fail, only panic or hang.
+    fn parse(input: &[u8]) -> Result<u64, ParseError> {
+        Ok(solve(input))
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 1320;
+        }
+    }
+
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
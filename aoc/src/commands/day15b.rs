@@ -1,126 +1,326 @@
-use std::{path::PathBuf, ops::Index, collections::HashMap};
+use std::{collections::HashMap, fmt};
 
 use clap::Parser;
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::utils::{AsciiReader, Inputs, ParseError};
 
-use super::{CommandImpl, DynError, day15a::hash};
+use super::{day15a::hash, CommandImpl, DynError};
 
-#[derive(Debug, Clone)]
-enum Operation {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
     Remove,
     Insert(u32),
 }
 
-#[derive(Debug, Clone)]
-struct Step {
-    label: String,
-    hash: u8,
-    operation: Operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub label: String,
+    pub hash: u8,
+    pub operation: Operation,
 }
 
 impl Step {
-    fn new(raw: &[u8]) -> Self {
-        let mut label = String::new();
-        let mut idx = 0_usize;
-        let operation = loop {
-            let c = raw.index(idx);
-            match c {
-                b'=' => {
-                    let focal_length = String::from_utf8(raw[idx+1..].to_vec()).unwrap();
-                    let focal_length = focal_length.parse::<u32>().unwrap();
-                    break Operation::Insert(focal_length);
-                },
-                b'-' => {
-                    break Operation::Remove;
-                },
-                _ => label.push(*c as char)
+    pub fn new(raw: &[u8]) -> Result<Self, ParseError> {
+        let step_error = |msg: &str| {
+            ParseError::new(format!("{msg} in step {:?}", String::from_utf8_lossy(raw)))
+        };
+
+        let Some(idx) = raw.iter().position(|&c| c == b'=' || c == b'-') else {
+            return Err(step_error("missing '=' or '-'"));
+        };
+        let (label, operation) = raw.split_at(idx);
+        if label.is_empty() || !label.iter().all(u8::is_ascii_alphabetic) {
+            return Err(step_error("label must be letters"));
+        }
+
+        let operation = match operation {
+            [b'-'] => Operation::Remove,
+            [b'=', digits @ ..] if !digits.is_empty() && digits.iter().all(u8::is_ascii_digit) => {
+                let focal_length = digits
+                    .iter()
+                    .try_fold(0u32, |value, digit| {
+                        value.checked_mul(10)?.checked_add((digit - b'0') as u32)
+                    })
+                    .ok_or_else(|| step_error("focal length does not fit in a u32"))?;
+                Operation::Insert(focal_length)
             }
-            idx += 1;
+            [b'=', ..] => return Err(step_error("focal length must be a number")),
+            _ => return Err(step_error("unexpected text after '-'")),
         };
+        let label = String::from_utf8_lossy(label).into_owned();
         let hash = hash(label.as_bytes());
 
-        Self {
-            label,
-            hash,
-            operation
+        Ok(Self { label, hash, operation })
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.operation {
+            Operation::Remove => write!(f, "{}-", self.label),
+            Operation::Insert(focal_length) => write!(f, "{}={focal_length}", self.label),
         }
     }
 }
 
-struct Solver {
-    reader: AsciiReader,
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lens {
+    pub label: String,
+    pub focal_length: u32,
+}
+
+/// One box's lenses in the order they went in. Removing a lens leaves an empty slot behind
+/// instead of shifting the lenses after it, and `index` finds a label's slot directly, so both
+/// replacing and removing a lens take constant time. The slots are compacted once more than
+/// half of them are empty.
+#[derive(Debug, Clone, Default)]
+pub struct LensBox {
+    slots: Vec<Option<Lens>>,
+    index: HashMap<String, usize>,
 }
 
-impl Solver {
-    fn new(input: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(input),
+impl LensBox {
+    pub fn insert(&mut self, label: &str, focal_length: u32) {
+        match self.index.get(label) {
+            Some(&slot) => self.slots[slot].as_mut().unwrap().focal_length = focal_length,
+            None => {
+                self.index.insert(label.to_owned(), self.slots.len());
+                self.slots.push(Some(Lens { label: label.to_owned(), focal_length }));
+            }
         }
     }
 
-    fn next_step(&mut self) -> Option<Step> {
-        let data = self.reader.read_to(b',');
-        Some(Step::new(data?))
+    pub fn remove(&mut self, label: &str) {
+        if let Some(slot) = self.index.remove(label) {
+            self.slots[slot] = None;
+            if self.slots.len() > 2 * self.index.len() + 8 {
+                self.compact();
+            }
+        }
     }
 
-    pub fn solve(&mut self) -> u64 {
-        let mut sum = 0;
-        let mut boxes: HashMap<u8, Vec<Step>> = HashMap::new();
+    fn compact(&mut self) {
+        self.slots.retain(Option::is_some);
+        for (slot, lens) in self.slots.iter().enumerate() {
+            self.index.insert(lens.as_ref().unwrap().label.clone(), slot);
+        }
+    }
 
-        loop {
-            let step = self.next_step();
-            if step.is_none() {
-                break;
-            }
-            let step = step.unwrap();
-
-            let mut box_list = boxes.entry(step.hash).or_default();
-            let existing = box_list.iter().position(|s| s.label == step.label);
-
-            match step {
-                Step { operation: Operation::Insert(focal_length), .. } => {
-                    if existing.is_none() {
-                        box_list.push(step);
-                    } else {
-                        box_list[existing.unwrap()].operation = Operation::Insert(focal_length);
-                    }
-                },
-                Step { operation: Operation::Remove, hash, label } => {
-                    if let Some(idx) = existing {
-                        box_list.remove(idx);
-                    }
-                }
+    /// The lenses front to back.
+    pub fn lenses(&self) -> impl Iterator<Item = &Lens> {
+        self.slots.iter().flatten()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+}
+
+/// The 256 boxes of the lens facility, each picked by the hash of a lens's label.
+#[derive(Debug, Clone)]
+pub struct LensSystem {
+    boxes: Vec<LensBox>,
+}
+
+impl Default for LensSystem {
+    fn default() -> Self {
+        Self { boxes: vec![LensBox::default(); 256] }
+    }
+}
+
+impl LensSystem {
+    pub fn apply(&mut self, step: &Step) {
+        let lens_box = &mut self.boxes[step.hash as usize];
+        match step.operation {
+            Operation::Insert(focal_length) => lens_box.insert(&step.label, focal_length),
+            Operation::Remove => lens_box.remove(&step.label),
+        }
+    }
+
+    pub fn lens_box(&self, number: u8) -> &LensBox {
+        &self.boxes[number as usize]
+    }
+
+    /// Each lens adds its box number plus one, times its slot counting from one, times its
+    /// focal length.
+    pub fn focusing_power(&self) -> u64 {
+        let mut power = 0;
+        for (number, lens_box) in self.boxes.iter().enumerate() {
+            for (slot, lens) in lens_box.lenses().enumerate() {
+                power += (number as u64 + 1) * (slot as u64 + 1) * lens.focal_length as u64;
             }
         }
+        power
+    }
+}
 
-        for (hash, box_list) in boxes.iter() {
-            for (idx, step) in box_list.iter().enumerate() {
-                match step.operation {
-                    Operation::Insert(focal_length) => {
-                        sum += (hash + 1) as u64 * (idx + 1) as u64 * focal_length as u64;
-                    },
-                    Operation::Remove => {
-                        panic!("Remove operation in box list");
-                    }
-                }
+/// The boxes that hold lenses, one per line, like `Box 0: [rn 1] [cm 2]`.
+impl fmt::Display for LensSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (number, lens_box) in self.boxes.iter().enumerate().filter(|(_, b)| !b.is_empty()) {
+            write!(f, "Box {number}:")?;
+            for lens in lens_box.lenses() {
+                write!(f, " [{} {}]", lens.label, lens.focal_length)?;
             }
+            writeln!(f)?;
         }
+        Ok(())
+    }
+}
 
-        sum
+/// Reads the steps from the first line, which may only be followed by blank lines.
+pub struct StepReader {
+    reader: AsciiReader,
+    trailing: Option<Vec<u8>>,
+}
+
+impl StepReader {
+    pub fn new(mut input: Vec<u8>) -> Self {
+        let trailing =
+            input.iter().position(|&byte| byte == b'\n').map(|end| input.split_off(end + 1));
+        Self { reader: AsciiReader::new(input), trailing }
+    }
+}
+
+impl Iterator for StepReader {
+    type Item = Result<Step, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(step) = self.reader.read_to(b',') {
+            return Some(Step::new(step));
+        }
+        let trailing = self.trailing.take()?;
+        if trailing.iter().all(u8::is_ascii_whitespace) {
+            return None;
+        }
+        Some(Err(ParseError::new(String::from("expected the steps on a single line"))))
+    }
+}
+
+/// Run every step and return the focusing power, printing the boxes after each step when
+/// `trace` is set.
+pub fn run(input: Vec<u8>, trace: bool) -> Result<u64, ParseError> {
+    let mut system = LensSystem::default();
+    for step in StepReader::new(input) {
+        let step = step?;
+        system.apply(&step);
+        if trace {
+            println!("After \"{step}\":\n{system}");
+        }
     }
+    Ok(system.focusing_power())
 }
 
 #[derive(Parser, Debug)]
 pub struct Day15b {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    /// Print the boxes that hold lenses after every step
+    #[clap(long)]
+    trace: bool,
 }
 
 impl CommandImpl for Day15b {
     fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day 15 B: {answer}");
-        Ok(())
+        let trace = self.trace;
+        self.input.for_each(|bytes| {
+            let answer = run(bytes, trace)?;
+            println!("Day 15 B: {answer}");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7
+";
+
+    fn solve(input: &[u8]) -> u64 {
+        run(input.to_vec(), false).unwrap()
+    }
+
+    fn parse(input: &[u8]) -> Result<Vec<Step>, ParseError> {
+        StepReader::new(input.to_vec()).collect()
+    }
+
+    fn step(input: &[u8]) -> Result<Step, ParseError> {
+        Step::new(input)
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 145;
+        }
+    }
+
+    #[test]
+    fn trace() {
+        let mut system = LensSystem::default();
+        let mut states = Vec::new();
+        for step in parse(EXAMPLE.as_bytes()).unwrap() {
+            system.apply(&step);
+            states.push(format!("After \"{step}\":\n{system}"));
+        }
+        assert_eq!(states[0], "After \"rn=1\":\nBox 0: [rn 1]\n");
+        assert_eq!(states[4], "After \"qp-\":\nBox 0: [rn 1] [cm 2]\n");
+        assert_eq!(
+            states[10],
+            "After \"ot=7\":\nBox 0: [rn 1] [cm 2]\nBox 3: [ot 7] [ab 5] [pc 6]\n"
+        );
+    }
+
+    #[test]
+    fn replace_and_remove_keep_order() {
+        let mut lens_box = LensBox::default();
+        for (idx, label) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            lens_box.insert(label, idx as u32);
+        }
+        lens_box.remove("b");
+        lens_box.insert("c", 9);
+        lens_box.insert("b", 7);
+        lens_box.remove("missing");
+        // Enough removals to compact the slots
+        for round in 0..20 {
+            lens_box.insert("x", round);
+            lens_box.remove("x");
+        }
+        let lenses: Vec<(&str, u32)> =
+            lens_box.lenses().map(|lens| (lens.label.as_str(), lens.focal_length)).collect();
+        assert_eq!(lenses, [("a", 0), ("c", 9), ("d", 3), ("b", 7)]);
+        assert!(lens_box.slots.len() < 20);
+    }
+
+    #[test]
+    fn blank_lines_after_the_steps() {
+        assert_eq!(parse(b"rn=1,cm-\n\n").unwrap(), parse(b"rn=1,cm-").unwrap());
+        assert_eq!(solve(b"rn=1,cm-\n\n"), 1);
+        assert_eq!(solve(format!("{EXAMPLE}\n \n").as_bytes()), 145);
+    }
+
+    #[test]
+    fn malformed_steps() {
+        assert!(step(b"rn").is_err());
+        assert!(step(b"rn=").is_err());
+        assert!(step(b"rn=x").is_err());
+        assert!(step(b"rn=99999999999").is_err());
+        assert!(step(b"=1").is_err());
+        assert!(step(b"r1=1").is_err());
+        assert!(step(b"rn-1").is_err());
+        assert!(parse(b"rn=1,,cm-\n").is_err());
+        assert!(parse(b"rn=1\ncm-\n").is_err());
+        assert_eq!(
+            step(b"rn=12").unwrap(),
+            Step { label: String::from("rn"), hash: 0, operation: Operation::Insert(12) }
+        );
+    }
+
+    robustness_tests! {
+        parse: EXAMPLE;
+        step: "rn=1";
     }
 }
@@ -1,12 +1,20 @@
-use std::{path::PathBuf, fmt::{Display, Formatter}};
+use std::{
+    fmt::{Display, Formatter},
+    io::Write,
+};
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::{
+    animation::{AnimateOptions, Animation},
+    render::{Frames, Render, RenderOptions, Rgb},
+    utils::{AsciiReader, Inputs, ParseError},
+};
 
 use super::{CommandImpl, DynError};
 
-#[derive(Debug)]
+/// The way a beam is travelling.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Direction {
     North,
     South,
@@ -15,16 +23,29 @@ pub enum Direction {
 }
 
 impl Direction {
-    fn opposite(&self) -> Self {
+    pub const ALL: [Direction; 4] =
+        [Direction::North, Direction::South, Direction::East, Direction::West];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    fn bit(self) -> u8 {
+        1 << self.index()
+    }
+
+    /// How the puzzle statement draws a beam travelling this way.
+    pub fn arrow(self) -> char {
         match self {
-            Direction::North => Direction::South,
-            Direction::South => Direction::North,
-            Direction::East => Direction::West,
-            Direction::West => Direction::East,
+            Direction::North => '^',
+            Direction::South => 'v',
+            Direction::East => '>',
+            Direction::West => '<',
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum TileKind {
     Empty,
     VerticalSplitter,
@@ -33,76 +54,93 @@ enum TileKind {
     Backward,
 }
 
-impl Display for TileKind {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+impl TileKind {
+    fn symbol(self) -> char {
         match self {
-            TileKind::Empty => write!(f, "."),
-            TileKind::VerticalSplitter => write!(f, "|"),
-            TileKind::HorizontalSplitter => write!(f, "-"),
-            TileKind::Forward => write!(f, "/"),
-            TileKind::Backward => write!(f, "\\"),
+            TileKind::Empty => '.',
+            TileKind::VerticalSplitter => '|',
+            TileKind::HorizontalSplitter => '-',
+            TileKind::Forward => '/',
+            TileKind::Backward => '\\',
         }
     }
-}
-
-pub struct Tile {
-    kind: TileKind,
-    visited_north: bool,
-    visited_south: bool,
-    visited_east: bool,
-    visited_west: bool,
-}
 
-impl Tile {
-    fn new(kind: TileKind) -> Self {
-        Self {
-            kind,
-            visited_north: false,
-            visited_south: false,
-            visited_east: false,
-            visited_west: false,
+    /// The directions a beam leaves this tile in after entering it travelling `direction`.
+    fn exits(self, direction: Direction) -> &'static [Direction] {
+        use Direction::*;
+        match (self, direction) {
+            (TileKind::VerticalSplitter, East | West) => &[North, South],
+            (TileKind::HorizontalSplitter, North | South) => &[East, West],
+            (TileKind::Forward, North) | (TileKind::Backward, South) => &[East],
+            (TileKind::Forward, South) | (TileKind::Backward, North) => &[West],
+            (TileKind::Forward, East) | (TileKind::Backward, West) => &[North],
+            (TileKind::Forward, West) | (TileKind::Backward, East) => &[South],
+            (_, North) => &[North],
+            (_, South) => &[South],
+            (_, East) => &[East],
+            (_, West) => &[West],
         }
     }
+}
 
-    fn reset(&mut self) {
-        self.visited_north = false;
-        self.visited_south = false;
-        self.visited_east = false;
-        self.visited_west = false;
+impl Display for TileKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
     }
+}
 
-    fn visit(&mut self, direction: &Direction) {
-        match direction {
-            Direction::North => self.visited_north = true,
-            Direction::South => self.visited_south = true,
-            Direction::East => self.visited_east = true,
-            Direction::West => self.visited_west = true,
-        }
-    }
+/// Where a beam comes in: the first tile it lights and the way it is travelling there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    pub x: usize,
+    pub y: usize,
+    pub direction: Direction,
+}
 
-    fn visited_from(&self, direction: &Direction) -> bool {
-        match direction {
-            Direction::North => self.visited_north,
-            Direction::South => self.visited_south,
-            Direction::East => self.visited_east,
-            Direction::West => self.visited_west,
-        }
+impl Entry {
+    /// Read an entry written as `x,y,heading`, like `3,0,S`, with the heading as one of N, S,
+    /// E or W.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = || format!("{text:?} is not an entry like 3,0,S");
+        let mut parts = text.split(',');
+        let (Some(x), Some(y), Some(heading), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let direction = match heading.trim().to_ascii_uppercase().as_str() {
+            "N" => Direction::North,
+            "S" => Direction::South,
+            "E" => Direction::East,
+            "W" => Direction::West,
+            _ => return Err(invalid()),
+        };
+        let x = x.trim().parse().map_err(|_| invalid())?;
+        let y = y.trim().parse().map_err(|_| invalid())?;
+        Ok(Self { x, y, direction })
     }
+}
 
-    fn visited(&self) -> bool {
-        self.visited_north || self.visited_south || self.visited_east || self.visited_west
+impl Display for Entry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {}) heading {:?}", self.x, self.y, self.direction)
     }
 }
 
+/// The mirror layout. It never changes once read, so any number of beams can be traced
+/// through one map at the same time, each with its own `Energized` tiles.
+#[derive(Debug, Clone)]
 pub struct Map {
-    tiles: Vec<Vec<Tile>>,
+    width: usize,
+    height: usize,
+    tiles: Vec<TileKind>,
 }
 
 impl Display for Map {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for row in &self.tiles {
+        for row in self.tiles.chunks(self.width) {
             for tile in row {
-                write!(f, "{}", tile.kind)?
+                write!(f, "{tile}")?
             }
             writeln!(f)?;
         }
@@ -111,180 +149,407 @@ impl Display for Map {
 }
 
 impl Map {
-    pub fn new(reader: &mut AsciiReader) -> Self {
+    pub fn new(reader: &mut AsciiReader) -> Result<Self, ParseError> {
         let mut tiles = vec![];
-        loop {
-            let line = reader.read_line();
-            if line.is_none() || line.unwrap().is_empty() {
-                break;
+        let mut width = 0;
+        let mut height = 0;
+        while let Some(line) = reader.read_line().filter(|line| !line.is_empty()) {
+            if height > 0 && line.len() != width {
+                let msg = format!("row {} is not as wide as the first", height + 1);
+                return Err(ParseError::new(msg));
             }
-            let line = line.unwrap();
-            let row = line.iter().map(|c| {
-                match c {
-                    b'.' => Tile::new(TileKind::Empty),
-                    b'|' => Tile::new(TileKind::VerticalSplitter),
-                    b'-' => Tile::new(TileKind::HorizontalSplitter),
-                    b'/' => Tile::new(TileKind::Forward),
-                    b'\\' => Tile::new(TileKind::Backward),
-                    _ => panic!("Unexpected tile kind: {}", *c as char)
-                }
-            }).collect::<Vec<_>>();
-            tiles.push(row);
+            for c in line {
+                tiles.push(match c {
+                    b'.' => TileKind::Empty,
+                    b'|' => TileKind::VerticalSplitter,
+                    b'-' => TileKind::HorizontalSplitter,
+                    b'/' => TileKind::Forward,
+                    b'\\' => TileKind::Backward,
+                    _ => {
+                        let msg = format!("Unexpected tile kind: {}", *c as char);
+                        return Err(ParseError::new(msg));
+                    }
+                });
+            }
+            width = line.len();
+            height += 1;
         }
 
-        Self {
-            tiles,
+        if tiles.is_empty() {
+            return Err(ParseError::new(String::from("map has no tiles")));
         }
-    }
 
-    pub fn reset(&mut self) {
-        for row in &mut self.tiles {
-            for tile in row {
-                tile.reset();
-            }
-        }
+        Ok(Self { width, height, tiles })
     }
 
     pub fn height(&self) -> usize {
-        self.tiles.len()
+        self.height
     }
 
     pub fn width(&self) -> usize {
-        self.tiles[0].len()
+        self.width
+    }
+
+    /// The tile next to `tile` in `direction`, if it is on the map.
+    pub fn neighbor(&self, tile: usize, direction: Direction) -> Option<usize> {
+        let (x, y) = (tile % self.width, tile / self.width);
+        let on_map = match direction {
+            Direction::North => y > 0,
+            Direction::South => y + 1 < self.height,
+            Direction::East => x + 1 < self.width,
+            Direction::West => x > 0,
+        };
+        if !on_map {
+            return None;
+        }
+        Some(match direction {
+            Direction::North => tile - self.width,
+            Direction::South => tile + self.width,
+            Direction::East => tile + 1,
+            Direction::West => tile - 1,
+        })
     }
 
-    pub fn count_visited(&self) -> usize {
-        self.tiles.iter().map(|row| {
-            row.iter().filter(|tile| tile.visited()).count()
-        }).sum()
+    /// The tiles a beam lights after entering `tile` travelling `direction`, each with the
+    /// direction it is travelling when it gets there.
+    pub fn next(
+        &self,
+        tile: usize,
+        direction: Direction,
+    ) -> impl Iterator<Item = (usize, Direction)> + '_ {
+        self.tiles[tile]
+            .exits(direction)
+            .iter()
+            .filter_map(move |&exit| Some((self.neighbor(tile, exit)?, exit)))
     }
 
-    pub fn cast_ray(&mut self, x: usize, y: usize, initial_direction: Direction) {
-        let mut visit_next_stack = vec![(x, y, initial_direction)];
+    /// Every way in from outside the map: each row from the west and east edges, then each
+    /// column from the north and south edges.
+    pub fn edge_entries(&self) -> Vec<Entry> {
+        let (right, bottom) = (self.width - 1, self.height - 1);
+        let rows = (0..self.height).flat_map(|y| {
+            [
+                Entry { x: 0, y, direction: Direction::East },
+                Entry { x: right, y, direction: Direction::West },
+            ]
+        });
+        let columns = (0..self.width).flat_map(|x| {
+            [
+                Entry { x, y: 0, direction: Direction::South },
+                Entry { x, y: bottom, direction: Direction::North },
+            ]
+        });
+        rows.chain(columns).collect()
+    }
+
+    /// Check that `entry` is on the map, so a beam can be traced from it.
+    pub fn check_entry(&self, entry: Entry) -> Result<(), ParseError> {
+        if entry.x >= self.width || entry.y >= self.height {
+            let msg = format!("entry {entry} is outside the {}x{} map", self.width, self.height);
+            return Err(ParseError::new(msg));
+        }
+        Ok(())
+    }
 
-        while let Some((x, y, direction)) = visit_next_stack.pop() {
-            if x >= self.width() || y >= self.height() {
-                continue;
+    /// Draw `energized` over the map in `style`.
+    pub fn beam_map(&self, energized: &Energized, style: BeamStyle) -> String {
+        let mut text = String::new();
+        for (idx, &tile) in self.tiles.iter().enumerate() {
+            let (x, y) = (idx % self.width, idx / self.width);
+            let beams = energized.beams[idx].count_ones();
+            let symbol = match (style, tile, beams) {
+                (BeamStyle::Counts, _, 0) | (BeamStyle::Arrows, TileKind::Empty, 0) => '.',
+                (BeamStyle::Arrows, TileKind::Empty, 1) => {
+                    energized.directions(x, y).next().unwrap().arrow()
+                }
+                (BeamStyle::Arrows, TileKind::Empty, _) | (BeamStyle::Counts, _, _) => {
+                    char::from_digit(beams, 10).unwrap()
+                }
+                (BeamStyle::Arrows, _, _) => tile.symbol(),
+            };
+            text.push(symbol);
+            if idx % self.width == self.width - 1 {
+                text.push('\n');
             }
-            let mut tile = &mut self.tiles[y][x];
-            if tile.visited_from(&direction) {
-                continue;
+        }
+        text
+    }
+
+    /// Trace a beam from `entry` until every branch leaves the map or repeats itself.
+    pub fn energize(&self, entry: Entry) -> Energized {
+        self.energize_with(entry, |_, _| ())
+    }
+
+    /// `energize`, moving every beam one tile at a time and calling `watch` with the tiles lit
+    /// so far and the number of steps taken after each one.
+    pub fn energize_with<F>(&self, entry: Entry, mut watch: F) -> Energized
+    where
+        F: FnMut(&Energized, usize),
+    {
+        let mut energized = Energized { width: self.width, beams: vec![0; self.tiles.len()] };
+        let mut heads = vec![(entry.y * self.width + entry.x, entry.direction)];
+        let mut next_heads = vec![];
+
+        for steps in 1.. {
+            for (tile, direction) in heads.drain(..) {
+                if energized.beams[tile] & direction.bit() != 0 {
+                    continue;
+                }
+                energized.beams[tile] |= direction.bit();
+                next_heads.extend(self.next(tile, direction));
             }
-            tile.visit(&direction);
-
-            match tile.kind {
-                TileKind::Empty => {
-                    match direction {
-                        Direction::North => {
-                            visit_next_stack.push((x, y - 1, Direction::North));
-                        },
-                        Direction::South => {
-                            visit_next_stack.push((x, y + 1, Direction::South));
-                        },
-                        Direction::East => {
-                            visit_next_stack.push((x + 1, y, Direction::East));
-                        },
-                        Direction::West => {
-                            visit_next_stack.push((x - 1, y, Direction::West));
-                        },
-                    }
-                },
-                TileKind::VerticalSplitter => {
-                    match direction {
-                        Direction::North => {
-                            visit_next_stack.push((x, y - 1, Direction::North));
-                        },
-                        Direction::South => {
-                            visit_next_stack.push((x, y + 1, Direction::South));
-                        },
-                        Direction::East | Direction::West => {
-                            visit_next_stack.push((x, y - 1, Direction::North));
-                            visit_next_stack.push((x, y + 1, Direction::South));
-                        },
-                    }
-                },
-                TileKind::HorizontalSplitter => {
-                    match direction {
-                        Direction::North | Direction::South => {
-                            visit_next_stack.push((x + 1, y, Direction::East));
-                            visit_next_stack.push((x - 1, y, Direction::West));
-                        },
-                        Direction::East => {
-                            visit_next_stack.push((x + 1, y, Direction::East));
-                        },
-                        Direction::West => {
-                            visit_next_stack.push((x - 1, y, Direction::West));
-                        },
-                    }
-                },
-                TileKind::Forward => {
-                    match direction {
-                        Direction::North => {
-                            visit_next_stack.push((x + 1, y, Direction::East));
-                        },
-                        Direction::South => {
-                            visit_next_stack.push((x - 1, y, Direction::West));
-                        },
-                        Direction::East => {
-                            visit_next_stack.push((x, y - 1, Direction::North));
-                        },
-                        Direction::West => {
-                            visit_next_stack.push((x, y + 1, Direction::South));
-                        },
-                    }
-                },
-                TileKind::Backward => {
-                    match direction {
-                        Direction::North => {
-                            visit_next_stack.push((x - 1, y, Direction::West));
-                        },
-                        Direction::South => {
-                            visit_next_stack.push((x + 1, y, Direction::East));
-                        },
-                        Direction::East => {
-                            visit_next_stack.push((x, y + 1, Direction::South));
-                        },
-                        Direction::West => {
-                            visit_next_stack.push((x, y - 1, Direction::North));
-                        },
-                    }
-                },
-                _ => panic!("Unexpected tile kind: {}", tile.kind),
+            if next_heads.is_empty() {
+                break;
+            }
+            watch(&energized, steps);
+            std::mem::swap(&mut heads, &mut next_heads);
+        }
+
+        energized
+    }
+
+    /// Trace a beam from `entry`, drawing the beams with arrows on `animation` and as images
+    /// on `frames` after every step.
+    pub fn record<W: Write>(
+        &self,
+        entry: Entry,
+        animation: &mut Option<Animation<W>>,
+        frames: &mut Option<Frames>,
+    ) -> Energized {
+        let mut draw = |energized: &Energized, title: &str| {
+            if let Some(animation) = animation {
+                animation.frame(title, &self.beam_map(energized, BeamStyle::Arrows));
             }
+            if let Some(frames) = frames {
+                frames.frame(&Beams { map: self, energized });
+            }
+        };
+        let energized = self.energize_with(entry, |energized, steps| {
+            draw(energized, &format!("Step {steps}"));
+        });
+        draw(&energized, &format!("{} tiles energized", energized.count()));
+        energized
+    }
+}
+
+impl Render for Map {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn color(&self, x: usize, y: usize) -> Rgb {
+        match self.tiles[y * self.width + x] {
+            TileKind::Empty => Rgb(15, 15, 25),
+            _ => Rgb(120, 120, 140),
         }
     }
 }
 
-struct Solver {
-    reader: AsciiReader,
+/// A beam's path over the map, for images: lit tiles glow and mirrors and splitters a beam
+/// passes through are brightest.
+pub struct Beams<'a> {
+    pub map: &'a Map,
+    pub energized: &'a Energized,
 }
 
-impl Solver {
-    fn new(input: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(input),
+impl Render for Beams<'_> {
+    fn size(&self) -> (usize, usize) {
+        self.map.size()
+    }
+
+    fn color(&self, x: usize, y: usize) -> Rgb {
+        let lit = self.energized.beams[y * self.map.width + x] != 0;
+        match (self.map.tiles[y * self.map.width + x], lit) {
+            (TileKind::Empty, true) => Rgb(255, 190, 40),
+            (_, true) => Rgb(255, 250, 220),
+            (_, false) => self.map.color(x, y),
         }
     }
-    
-    fn solve(&mut self) -> u64 {
-        let mut map = Map::new(&mut self.reader);
+}
+
+/// How `--beams` draws a beam's path over the map.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum BeamStyle {
+    /// Like the puzzle statement: an arrow on each empty tile one beam crosses, or how many
+    /// beams cross it when there are more, with mirrors and splitters drawn as they are
+    Arrows,
+    /// How many directions beams crossed each tile in, mirrors and splitters included
+    Counts,
+}
+
+/// One beam's path through a map: the directions it entered each tile in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Energized {
+    width: usize,
+    beams: Vec<u8>,
+}
+
+impl Energized {
+    /// How many tiles at least one beam passes through.
+    pub fn count(&self) -> usize {
+        self.lit().filter(|&lit| lit).count()
+    }
 
-        map.cast_ray(0, 0, Direction::East);
+    /// Whether a beam passes through each tile, row by row.
+    pub fn lit(&self) -> impl Iterator<Item = bool> + '_ {
+        self.beams.iter().map(|&beams| beams != 0)
+    }
 
-        map.count_visited() as u64
+    /// The directions beams were travelling when they entered the tile at `x`, `y`.
+    pub fn directions(&self, x: usize, y: usize) -> impl Iterator<Item = Direction> {
+        let beams = self.beams[y * self.width + x];
+        Direction::ALL.into_iter().filter(move |direction| beams & direction.bit() != 0)
     }
 }
 
 #[derive(Parser, Debug)]
 pub struct Day16a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    /// Where the beam comes in, as x,y,heading, defaulting to the top-left tile heading east
+    #[clap(long, value_parser = Entry::parse)]
+    entry: Option<Entry>,
+    /// Print the beam's path over the map
+    #[clap(long, value_enum, value_name = "STYLE")]
+    beams: Option<BeamStyle>,
+    #[clap(flatten)]
+    animate: AnimateOptions,
+    #[clap(flatten)]
+    render: RenderOptions,
 }
 
 impl CommandImpl for Day16a {
     fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day 16 A: {answer}");
-        Ok(())
+        let entry = self.entry.unwrap_or(Entry { x: 0, y: 0, direction: Direction::East });
+        let (beams, animate, render) = (self.beams, self.animate.clone(), self.render.clone());
+        self.input.for_each(|bytes| {
+            let map = Map::new(&mut AsciiReader::new(bytes))?;
+            map.check_entry(entry)?;
+            let (mut animation, mut frames) = (animate.animation(), render.frames()?);
+            let energized = map.record(entry, &mut animation, &mut frames);
+            if let Some(animation) = animation {
+                animation.finish()?;
+            }
+            if let Some(frames) = frames {
+                frames.finish()?;
+            }
+            render.save(&Beams { map: &map, energized: &energized })?;
+            if let Some(style) = beams {
+                print!("{}", map.beam_map(&energized, style));
+            }
+            let answer = energized.count();
+            println!("Day 16 A: {answer}");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....
+";
+
+    fn solve(input: &[u8]) -> u64 {
+        let map = parse(input).unwrap();
+        map.energize(Entry { x: 0, y: 0, direction: Direction::East }).count() as u64
+    }
+
+    fn parse(input: &[u8]) -> Result<Map, ParseError> {
+        Map::new(&mut AsciiReader::new(input.to_vec()))
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 46;
+        }
+    }
+
+    #[test]
+    fn layout_and_beams() {
+        let map = parse(EXAMPLE.as_bytes()).unwrap();
+        assert_eq!(map.to_string(), EXAMPLE);
+        assert_eq!(map.edge_entries().len(), 40);
+
+        let energized = map.energize(Entry { x: 0, y: 0, direction: Direction::East });
+        // The beam down from the splitter at (5, 2) crosses the one the mirrors at (4, 7) and
+        // (4, 6) turn east.
+        assert_eq!(energized.directions(0, 0).collect::<Vec<_>>(), [Direction::East]);
+        assert_eq!(
+            energized.directions(5, 6).collect::<Vec<_>>(),
+            [Direction::South, Direction::East]
+        );
+        assert_eq!(energized.directions(9, 9).count(), 0);
+        assert!(parse(b"..\n.\n").is_err());
+        assert!(map.check_entry(Entry { x: 10, y: 0, direction: Direction::West }).is_err());
+        assert!(parse(b".x\n").is_err());
+        assert!(parse(b"\n").is_err());
+    }
+
+    #[test]
+    fn beam_maps() {
+        let map = parse(EXAMPLE.as_bytes()).unwrap();
+        let energized = map.energize(Entry::parse("0,0,E").unwrap());
+        assert_eq!(
+            map.beam_map(&energized, BeamStyle::Arrows),
+            r">|<<<\....
+|v-.\^....
+.v...|->>>
+.v...v^.|.
+.v...v^...
+.v...v^..\
+.v../2\\..
+<->-/vv|..
+.|<<<2-|.\
+.v//.|.v..
+"
+        );
+        let counts = map.beam_map(&energized, BeamStyle::Counts);
+        assert_eq!(counts.lines().nth(6), Some(".1..1221.."));
+        assert_eq!(counts.chars().filter(|c| c.is_ascii_digit()).count(), 46);
+
+        assert_eq!(Entry::parse("3, 0, s"), Ok(Entry { x: 3, y: 0, direction: Direction::South }));
+        assert!(Entry::parse("3,0").is_err());
+        assert!(Entry::parse("3,0,S,1").is_err());
+        assert!(Entry::parse("-1,0,S").is_err());
+        assert!(Entry::parse("1,0,Q").is_err());
+    }
+
+    #[test]
+    fn animated_beams() {
+        let map = parse(EXAMPLE.as_bytes()).unwrap();
+        let entry = Entry { x: 0, y: 0, direction: Direction::East };
+        let mut lit = vec![];
+        let energized = map.energize_with(entry, |energized, steps| {
+            lit.push((steps, energized.count()));
+        });
+        assert_eq!(energized, map.energize(entry));
+        assert_eq!(lit[..3], [(1, 1), (2, 2), (3, 3)]);
+        assert!(lit.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+
+        let mut animation = Some(Animation::new(Vec::new(), false, 1.0));
+        map.record(entry, &mut animation, &mut None);
+        assert_eq!(animation.unwrap().frames(), lit.len() + 1);
+
+        let canvas = Beams { map: &map, energized: &energized }.canvas(1);
+        let lit = canvas.pixels.iter().filter(|&&pixel| pixel != Rgb(15, 15, 25)).count();
+        let mirrors = EXAMPLE.bytes().filter(|tile| b"/\\|-".contains(tile)).count();
+        assert_eq!(canvas.pixels[0], Rgb(255, 190, 40));
+        assert!(lit >= 46 && lit <= 46 + mirrors);
+    }
+
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
@@ -1,60 +1,432 @@
-use std::path::PathBuf;
+use std::{num::NonZeroUsize, path::PathBuf, thread};
 
 use clap::Parser;
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::{
+    animation::AnimateOptions,
+    render::{Render, RenderOptions, Rgb},
+    utils::{AsciiReader, Inputs},
+};
 
-use super::{day16a::{Map, Direction}, CommandImpl, DynError};
+use super::{
+    day16a::{BeamStyle, Beams, Direction, Entry, Map},
+    CommandImpl, DynError,
+};
 
-
-struct Solver {
-    reader: AsciiReader,
+/// The beam graph with its strongly connected components collapsed. A node is a beam entering
+/// a tile in one direction and an edge is where it goes next. Every node in a component lights
+/// the same tiles, so the tiles lit from each cyclic component are worked out once and shared
+/// by every entry that reaches it.
+pub struct Components<'a> {
+    map: &'a Map,
+    /// The component of each node, numbered sinks first
+    component: Vec<u32>,
+    /// The tiles in each component
+    tiles: Vec<Vec<u32>>,
+    /// The components each component leads to
+    successors: Vec<Vec<u32>>,
+    /// The tiles lit from each component that contains a loop, as a bitset
+    shared: Vec<Option<Vec<u64>>>,
 }
 
-impl Solver {
-    fn new(input: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(input),
+impl<'a> Components<'a> {
+    pub fn new(map: &'a Map) -> Self {
+        let nodes = map.width() * map.height() * 4;
+        let node = |tile: usize, direction: Direction| (tile * 4 + direction.index()) as u32;
+        let edges = |from: u32| {
+            let (tile, direction) = (from as usize / 4, Direction::ALL[from as usize % 4]);
+            map.next(tile, direction).map(move |(tile, direction)| node(tile, direction))
+        };
+
+        // Tarjan's algorithm without recursion, which numbers the components sinks first.
+        const UNSEEN: u32 = u32::MAX;
+        let mut order = vec![UNSEEN; nodes];
+        let mut low = vec![0; nodes];
+        let mut on_stack = vec![false; nodes];
+        let mut component = vec![UNSEEN; nodes];
+        let mut stack = vec![];
+        let mut visited = 0;
+        let mut tiles: Vec<Vec<u32>> = vec![];
+        let mut cyclic = vec![];
+        for root in 0..nodes as u32 {
+            if order[root as usize] != UNSEEN {
+                continue;
+            }
+            let mut calls = vec![(root, edges(root).collect::<Vec<_>>())];
+            order[root as usize] = visited;
+            low[root as usize] = visited;
+            visited += 1;
+            stack.push(root);
+            on_stack[root as usize] = true;
+            while let Some((current, pending)) = calls.last_mut() {
+                let current = *current;
+                if let Some(next) = pending.pop() {
+                    if order[next as usize] == UNSEEN {
+                        order[next as usize] = visited;
+                        low[next as usize] = visited;
+                        visited += 1;
+                        stack.push(next);
+                        on_stack[next as usize] = true;
+                        calls.push((next, edges(next).collect()));
+                    } else if on_stack[next as usize] {
+                        low[current as usize] = low[current as usize].min(order[next as usize]);
+                    }
+                    continue;
+                }
+                calls.pop();
+                if let Some(&(parent, _)) = calls.last() {
+                    low[parent as usize] = low[parent as usize].min(low[current as usize]);
+                }
+                if low[current as usize] == order[current as usize] {
+                    let id = tiles.len() as u32;
+                    let mut members = vec![];
+                    loop {
+                        let member = stack.pop().unwrap();
+                        on_stack[member as usize] = false;
+                        component[member as usize] = id;
+                        members.push(member);
+                        if member == current {
+                            break;
+                        }
+                    }
+                    cyclic.push(members.len() > 1 || edges(current).any(|next| next == current));
+                    let mut lit: Vec<u32> = members.iter().map(|member| member / 4).collect();
+                    lit.sort_unstable();
+                    lit.dedup();
+                    tiles.push(lit);
+                }
+            }
         }
-    }
-    
-    fn solve(&mut self) -> u64 {
-        let mut map = Map::new(&mut self.reader);
 
-        let mut greatest = 0;
+        let mut successors = vec![vec![]; tiles.len()];
+        for from in 0..nodes as u32 {
+            for to in edges(from) {
+                let (from, to) = (component[from as usize], component[to as usize]);
+                if from != to {
+                    successors[from as usize].push(to);
+                }
+            }
+        }
+        for next in &mut successors {
+            next.sort_unstable();
+            next.dedup();
+        }
 
-        for y in 0..map.height() {
-            for (x, direction) in [(0, Direction::East), (map.width() - 1, Direction::West)] {
-                map.cast_ray(x, y, direction);
-                let visited = map.count_visited();
-                greatest = greatest.max(visited);
-                map.reset();
+        let mut components =
+            Self { map, component, tiles, successors, shared: vec![None; cyclic.len()] };
+        // Sinks come first, so every loop a component leads to already has its tiles.
+        let mut scratch = Scratch::new(components.tiles.len());
+        for (id, cyclic) in cyclic.into_iter().enumerate() {
+            if cyclic {
+                let lit = components.collect(id as u32, &mut scratch);
+                components.shared[id] = Some(lit);
             }
         }
+        components
+    }
 
-        for x in 0..map.width() {
-            for (y, direction) in [(0, Direction::South), (map.height() - 1, Direction::North)] {
-                map.cast_ray(x, y, direction);
-                let visited = map.count_visited();
-                greatest = greatest.max(visited);
-                map.reset();
+    fn collect(&self, start: u32, scratch: &mut Scratch) -> Vec<u64> {
+        let mut lit = vec![0u64; (self.map.width() * self.map.height()).div_ceil(64)];
+        scratch.generation += 1;
+        let mut stack = vec![start];
+        scratch.seen[start as usize] = scratch.generation;
+        while let Some(id) = stack.pop() {
+            if let Some(shared) = self.shared[id as usize].as_ref().filter(|_| id != start) {
+                for (word, shared) in lit.iter_mut().zip(shared) {
+                    *word |= shared;
+                }
+                continue;
+            }
+            for &tile in &self.tiles[id as usize] {
+                lit[tile as usize / 64] |= 1 << (tile % 64);
+            }
+            for &next in &self.successors[id as usize] {
+                if scratch.seen[next as usize] != scratch.generation {
+                    scratch.seen[next as usize] = scratch.generation;
+                    stack.push(next);
+                }
             }
         }
+        lit
+    }
+
+    /// How many tiles a beam from `entry` lights.
+    pub fn count(&self, entry: Entry) -> usize {
+        let tile = entry.y * self.map.width() + entry.x;
+        let id = self.component[tile * 4 + entry.direction.index()];
+        let lit = match &self.shared[id as usize] {
+            Some(lit) => lit.clone(),
+            None => self.collect(id, &mut Scratch::new(self.tiles.len())),
+        };
+        lit.iter().map(|word| word.count_ones() as usize).sum()
+    }
+}
+
+/// Marks which components one walk has seen, without clearing them between walks.
+struct Scratch {
+    seen: Vec<u32>,
+    generation: u32,
+}
+
+impl Scratch {
+    fn new(components: usize) -> Self {
+        Self { seen: vec![0; components], generation: 0 }
+    }
+}
+
+/// Count the tiles lit from every entry, splitting the entries between `threads` threads.
+pub fn count_in_parallel<F>(entries: &[Entry], threads: usize, count: F) -> Vec<usize>
+where
+    F: Fn(Entry) -> usize + Sync,
+{
+    let chunk = entries.len().div_ceil(threads.max(1)).max(1);
+    let count = &count;
+    thread::scope(|scope| {
+        let workers: Vec<_> = entries
+            .chunks(chunk)
+            .map(|chunk| {
+                scope.spawn(move || chunk.iter().map(|&entry| count(entry)).collect::<Vec<_>>())
+            })
+            .collect();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect()
+    })
+}
+
+/// The edge entry that lights the most tiles, and how many it lights. The first one wins a
+/// tie.
+pub fn best_entry(map: &Map, threads: usize, components: bool) -> (Entry, usize) {
+    let entries = map.edge_entries();
+    let counts = if components {
+        let components = Components::new(map);
+        count_in_parallel(&entries, threads, |entry| components.count(entry))
+    } else {
+        count_in_parallel(&entries, threads, |entry| map.energize(entry).count())
+    };
+    let best = (0..entries.len()).rev().max_by_key(|&idx| counts[idx]).unwrap();
+    (entries[best], counts[best])
+}
+
+/// How many edge entries light each tile, row by row.
+pub fn heatmap(map: &Map, threads: usize) -> Vec<u32> {
+    let entries = map.edge_entries();
+    let chunk = entries.len().div_ceil(threads.max(1)).max(1);
+    let tiles = map.width() * map.height();
+    thread::scope(|scope| {
+        let workers: Vec<_> = entries
+            .chunks(chunk)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut heat = vec![0u32; tiles];
+                    for &entry in chunk {
+                        for (heat, lit) in heat.iter_mut().zip(map.energize(entry).lit()) {
+                            *heat += lit as u32;
+                        }
+                    }
+                    heat
+                })
+            })
+            .collect();
+        let mut heat = vec![0u32; tiles];
+        for worker in workers {
+            for (total, heat) in heat.iter_mut().zip(worker.join().unwrap()) {
+                *total += heat;
+            }
+        }
+        heat
+    })
+}
+
+/// How many edge entries light each tile, for images: from black for tiles no entry lights,
+/// through red and yellow, to white for the tiles lit most often.
+pub struct Heatmap<'a> {
+    width: usize,
+    heat: &'a [u32],
+    hottest: u32,
+}
+
+impl<'a> Heatmap<'a> {
+    pub fn new(width: usize, heat: &'a [u32]) -> Self {
+        Self { width, heat, hottest: heat.iter().copied().max().unwrap_or(0).max(1) }
+    }
+}
+
+impl Render for Heatmap<'_> {
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.heat.len() / self.width)
+    }
 
-        greatest as u64
+    fn color(&self, x: usize, y: usize) -> Rgb {
+        let level = self.heat[y * self.width + x] as f64 / self.hottest as f64 * 3.0;
+        let channel = |offset: f64| ((level - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+        Rgb(channel(0.0), channel(1.0), channel(2.0))
     }
 }
 
 #[derive(Parser, Debug)]
 pub struct Day16b {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    /// How many threads share the edge entries, defaulting to one per core
+    #[clap(long, value_name = "N")]
+    threads: Option<NonZeroUsize>,
+    /// Collapse the beam graph into strongly connected components and share the tiles lit from
+    /// each loop between entries
+    #[clap(long)]
+    components: bool,
+    /// Print the entry that lights the most tiles
+    #[clap(long)]
+    show_entry: bool,
+    /// Print the best entry's beams over the map
+    #[clap(long, value_enum, value_name = "STYLE")]
+    beams: Option<BeamStyle>,
+    /// Write an image of how many edge entries light each tile, PPM or PNG going by its
+    /// extension
+    #[clap(long, value_name = "FILE")]
+    heatmap: Option<PathBuf>,
+    #[clap(flatten)]
+    animate: AnimateOptions,
+    #[clap(flatten)]
+    render: RenderOptions,
 }
 
 impl CommandImpl for Day16b {
     fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day 16 B: {answer}");
-        Ok(())
+        let threads = self
+            .threads
+            .or_else(|| thread::available_parallelism().ok())
+            .map_or(1, NonZeroUsize::get);
+        let (components, show_entry, beams) = (self.components, self.show_entry, self.beams);
+        let heatmap_path = self.heatmap.clone();
+        let (animate, render) = (self.animate.clone(), self.render.clone());
+        self.input.for_each(|bytes| {
+            let map = Map::new(&mut AsciiReader::new(bytes))?;
+            let (entry, answer) = best_entry(&map, threads, components);
+            if show_entry {
+                println!("Best entry: {entry}");
+            }
+            if let Some(style) = beams {
+                print!("{}", map.beam_map(&map.energize(entry), style));
+            }
+            let (mut animation, mut frames) = (animate.animation(), render.frames()?);
+            if animation.is_some() || frames.is_some() || render.wants_image() {
+                let energized = map.record(entry, &mut animation, &mut frames);
+                render.save(&Beams { map: &map, energized: &energized })?;
+            }
+            if let Some(animation) = animation {
+                animation.finish()?;
+            }
+            if let Some(frames) = frames {
+                frames.finish()?;
+            }
+            if let Some(path) = &heatmap_path {
+                let heat = heatmap(&map, threads);
+                let heatmap = Heatmap::new(map.width(), &heat);
+                heatmap.canvas(render.image.scale.get()).save(path)?;
+            }
+            println!("Day 16 B: {answer}");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+    use crate::utils::ParseError;
+
+    const EXAMPLE: &str = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....
+";
+
+    fn parse(input: &[u8]) -> Map {
+        Map::new(&mut AsciiReader::new(input.to_vec())).unwrap()
+    }
+
+    fn solve(input: &[u8]) -> u64 {
+        best_entry(&parse(input), 4, false).1 as u64
+    }
+
+    fn solve_with_components(input: &[u8]) -> u64 {
+        best_entry(&parse(input), 4, true).1 as u64
+    }
+
+    fn parse_and_solve(input: &[u8]) -> Result<usize, ParseError> {
+        let map = Map::new(&mut AsciiReader::new(input.to_vec()))?;
+        Ok(best_entry(&map, 2, true).1)
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 51;
+        }
+        solve_with_components {
+            example components: EXAMPLE => 51;
+        }
+    }
+
+    #[test]
+    fn best_entry_of_example() {
+        let (entry, count) = best_entry(&parse(EXAMPLE.as_bytes()), 1, false);
+        assert_eq!(entry, Entry { x: 3, y: 0, direction: Direction::South });
+        assert_eq!(count, 51);
+    }
+
+    #[test]
+    fn heatmap_of_example() {
+        let map = parse(EXAMPLE.as_bytes());
+        let heat = heatmap(&map, 3);
+        assert_eq!(heat, heatmap(&map, 1));
+        // Every edge entry lights the tile it comes in on.
+        assert!(map.edge_entries().iter().all(|entry| heat[entry.y * 10 + entry.x] > 0));
+        let total: u32 = heat.iter().sum();
+        let counts = count_in_parallel(&map.edge_entries(), 1, |entry| map.energize(entry).count());
+        assert_eq!(total as usize, counts.iter().sum::<usize>());
+
+        let path = std::env::temp_dir().join(format!("day16-heatmap-{}.ppm", std::process::id()));
+        Heatmap::new(map.width(), &heat).canvas(1).save(&path).unwrap();
+        let image = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(image.starts_with(b"P6\n10 10\n255\n"));
+        assert_eq!(image.len(), b"P6\n10 10\n255\n".len() + 300);
+    }
+
+    /// A square map of up to 12 by 12 tiles, mostly empty.
+    fn map() -> impl Strategy<Value = String> {
+        (1..=12usize).prop_flat_map(|side| {
+            proptest::collection::vec(
+                prop_oneof![6 => Just('.'), 1 => Just('/'), 1 => Just('\\'), 1 => Just('|'),
+                    1 => Just('-')],
+                side * side,
+            )
+            .prop_map(move |tiles| {
+                tiles.chunks(side).flat_map(|row| row.iter().copied().chain(['\n'])).collect()
+            })
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn components_match_tracing(text in map()) {
+            let map = parse(text.as_bytes());
+            let components = Components::new(&map);
+            for entry in map.edge_entries() {
+                prop_assert_eq!(components.count(entry), map.energize(entry).count());
+            }
+        }
+    }
+
+    robustness_tests! {
+        parse_and_solve: EXAMPLE;
     }
 }
@@ -1,53 +1,73 @@
-use std::{path::PathBuf, fmt::{Display, Formatter}, cmp::Ordering};
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fmt::{Display, Formatter},
+};
 
-use clap::Parser;
+use clap::{Args, Parser};
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::utils::{AsciiReader, Inputs, ParseError};
 
 use super::{CommandImpl, DynError};
 
-#[derive(Debug, Default)]
-pub struct Costs {
-    /// Estimated cost to end position
-    pub h: u64,
-    /// Cost to get to this position
-    pub g: u64,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
 }
 
-impl Costs {
-    fn f(&self) -> u64 {
-        self.h + self.g
+impl Direction {
+    const ALL: [Direction; 4] =
+        [Direction::North, Direction::East, Direction::South, Direction::West];
+
+    fn turns(self) -> [Direction; 2] {
+        match self {
+            Direction::North | Direction::South => [Direction::East, Direction::West],
+            Direction::East | Direction::West => [Direction::North, Direction::South],
+        }
+    }
+
+    /// How the puzzle statement draws a step in this direction.
+    fn arrow(self) -> char {
+        match self {
+            Direction::North => '^',
+            Direction::East => '>',
+            Direction::South => 'v',
+            Direction::West => '<',
+        }
     }
 }
 
-#[derive(Debug)]
-pub struct Tile {
-    weight: u8,
-    traversed: bool,
-    costs: Option<Costs>,
-    from: Option<(usize, usize)>,
+/// How far a crucible must and may go in a straight line. A crucible can only turn or stop at
+/// the end once it has gone `min_run` blocks, and must turn after `max_run`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crucible {
+    pub min_run: usize,
+    pub max_run: usize,
 }
 
-impl Tile {
-    fn new(weight: u8) -> Self {
-        Self {
-            weight,
-            traversed: false,
-            costs: None,
-            from: None,
-        }
-    }
+/// The cheapest way to the factory: the heat lost, and each block entered with the direction
+/// it was entered in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub heat_loss: u64,
+    pub steps: Vec<(usize, usize, Direction)>,
 }
 
+#[derive(Debug, Clone)]
 pub struct Map {
-    tiles: Vec<Vec<Tile>>,
+    width: usize,
+    height: usize,
+    heat: Vec<u8>,
 }
 
 impl Display for Map {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for row in &self.tiles {
-            for tile in row {
-                write!(f, "{}", tile.weight)?;
+        for row in self.heat.chunks(self.width) {
+            for heat in row {
+                write!(f, "{heat}")?;
             }
             writeln!(f)?;
         }
@@ -56,178 +76,266 @@ impl Display for Map {
 }
 
 impl Map {
-    pub fn new(reader: &mut AsciiReader) -> Self {
-        let mut tiles = Vec::new();
-        while let Some(line) = reader.read_line() {
-            let mut row = line.iter().map(|c| Tile::new(c - b'0')).collect();
-            tiles.push(row);
+    pub fn new(reader: &mut AsciiReader) -> Result<Self, ParseError> {
+        let mut heat = Vec::new();
+        let mut width = 0;
+        let mut height = 0;
+        while let Some(line) = reader.read_line().filter(|line| !line.is_empty()) {
+            if height > 0 && line.len() != width {
+                let msg = format!("row {} is not as wide as the first", height + 1);
+                return Err(ParseError::new(msg));
+            }
+            for c in line {
+                match c {
+                    b'1'..=b'9' => heat.push(c - b'0'),
+                    _ => {
+                        let msg = format!("heat loss must be 1-9, found {:?}", *c as char);
+                        return Err(ParseError::new(msg));
+                    }
+                }
+            }
+            width = line.len();
+            height += 1;
+        }
+
+        if heat.is_empty() {
+            return Err(ParseError::new(String::from("map has no blocks")));
         }
 
-        Self { tiles }
+        Ok(Self { width, height, heat })
     }
 
-    fn distance(&self, from: (usize, usize), to: (usize, usize)) -> u64 {
-        let (x1, y1) = from;
-        let (x2, y2) = to;
-        ((x1 as i64 - x2 as i64).abs() + (y1 as i64 - y2 as i64).abs()) as u64
+    fn step(&self, tile: usize, direction: Direction) -> Option<usize> {
+        let (x, y) = (tile % self.width, tile / self.width);
+        match direction {
+            Direction::North if y > 0 => Some(tile - self.width),
+            Direction::East if x + 1 < self.width => Some(tile + 1),
+            Direction::South if y + 1 < self.height => Some(tile + self.width),
+            Direction::West if x > 0 => Some(tile - 1),
+            _ => None,
+        }
     }
 
-    fn calc_position_cost(&mut self, position: (usize, usize), from: (usize, usize), start: (usize, usize), end: (usize, usize)) {
-        if position == start {
-            return;
+    /// Dijkstra's algorithm from the top-left block to the bottom-right one, over states of
+    /// (block, heading, blocks gone in a straight line). The start counts as heading both
+    /// east and south having gone nowhere yet. `None` when no route obeys the crucible's runs.
+    pub fn min_heat_loss(&self, crucible: Crucible) -> Option<Route> {
+        let end = self.heat.len() - 1;
+        if end == 0 {
+            // A single block is already the factory, so the crucible never has to move
+            return Some(Route { heat_loss: 0, steps: vec![] });
         }
-        println!("calc_position_cost: {:?} -> {:?}", from, position);
-
-        let g_cost = self.tiles[from.1][from.0].costs.as_ref().unwrap().g + self.tiles[position.1][position.0].weight as u64;
-        let h_cost = self.distance(position, end);
-        let total = g_cost + h_cost;
-
-        let tile = &mut self.tiles[position.1][position.0];
-        if tile.costs.is_none() || tile.costs.as_ref().unwrap().f() > total {
-            tile.costs = Some(Costs { h: h_cost, g: g_cost });
-            tile.from = Some(from);
-            println!("costs: {:?}", tile.costs);
-        }   
-    }
-
-    pub fn min_cost(&mut self, start: (usize, usize), end: (usize, usize)) -> u64 {
-        let mut cost = 0;
-        println!("min_cost: {:?} -> {:?}", start, end);
-
-        self.tiles[start.1][start.0].costs = Some(Default::default());
-        self.tiles[start.1][start.0].traversed = true;
-
-        let mut visited: Vec<(usize, usize)> = vec![];
-
-        loop {
-            println!("visited: {:?}", visited);
-            let (x, y) = {
-                if visited.is_empty() {
-                    println!("visited is empty");
-                    start
-                } else {
-                    let mut min_cost = u64::MAX;
-                    let mut min_positions: Vec<(usize, usize)> = vec![];
-                    for position in &visited {
-                        let tile = &self.tiles[position.1][position.0];
-                        if tile.traversed {
-                            continue;
-                        }
-                        let tile_cost = tile.costs.as_ref().unwrap().f();
-                        match tile_cost.cmp(&min_cost) {
-                            Ordering::Less => {
-                                min_cost = tile_cost;
-                                min_positions.clear();
-                                min_positions.push(*position);
-                            },
-                            Ordering::Equal => {
-                                min_positions.push(*position);
-                            },
-                            Ordering::Greater => {},
-                        }
-                    }
+        // No run can be longer than the map, which keeps the state table small for any limit.
+        let max_run = crucible.max_run.min(self.width.max(self.height));
+        let runs = max_run + 1;
+        let state = |tile: usize, direction: Direction, run: usize| {
+            (tile * 4 + direction as usize) * runs + run
+        };
 
-                    println!("min_positions: {:?}", min_positions);
-
-                    if min_positions.len() > 1 {
-                        let mut min_h_cost = u64::MAX;
-                        let mut min_h_positions: Vec<(usize, usize)> = vec![];
-                        for position in &min_positions {
-                            let tile = &self.tiles[position.1][position.0];
-                            let tile_cost = tile.costs.as_ref().unwrap().h;
-                            match tile_cost.cmp(&min_h_cost) {
-                                Ordering::Less => {
-                                    min_h_cost = tile_cost;
-                                    min_h_positions.clear();
-                                    min_h_positions.push(*position);
-                                },
-                                Ordering::Equal => {
-                                    min_h_positions.push(*position);
-                                },
-                                Ordering::Greater => {},
-                            }
-                        }
-                        min_h_positions[0]
-                    } else {
-                        min_positions[0]
-                    }
-                }
-            };
-            let tile = &mut self.tiles[y][x];
-            println!("position: {:?} tile: {:?}", (x, y), tile);
-            tile.traversed = true;
-
-            if x == end.0 && y == end.1 {
-                println!("Found end");
-                break;
+        let mut best = vec![u64::MAX; self.heat.len() * 4 * runs];
+        let mut from = vec![usize::MAX; best.len()];
+        let mut queue = BinaryHeap::new();
+        for direction in [Direction::East, Direction::South] {
+            best[state(0, direction, 0)] = 0;
+            queue.push(Reverse((0, 0, direction as usize, 0)));
+        }
+
+        while let Some(Reverse((heat_loss, tile, direction, run))) = queue.pop() {
+            let direction = Direction::ALL[direction];
+            let current = state(tile, direction, run);
+            if heat_loss > best[current] {
+                continue;
+            }
+            if tile == end && run >= crucible.min_run {
+                return Some(self.route(heat_loss, current, &from, runs));
             }
 
-            for position in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)].iter() {
-                if position.1 >= self.tiles.len() || position.0 >= self.tiles[0].len() || position == &start {
+            let straight = (run < max_run).then_some((direction, run + 1));
+            let turns = direction.turns().map(|turn| (turn, 1));
+            let turns = turns.iter().copied().filter(|_| run >= crucible.min_run);
+            for (next_direction, next_run) in straight.into_iter().chain(turns) {
+                let Some(next) = self.step(tile, next_direction) else {
                     continue;
-                }
-                self.calc_position_cost(*position, (x, y), start, end);
-                if !visited.contains(position) {
-                    visited.push(*position);
+                };
+                let next_heat_loss = heat_loss + self.heat[next] as u64;
+                let next_state = state(next, next_direction, next_run);
+                if next_heat_loss < best[next_state] {
+                    best[next_state] = next_heat_loss;
+                    from[next_state] = current;
+                    queue.push(Reverse((next_heat_loss, next, next_direction as usize, next_run)));
                 }
             }
         }
 
-        let mut current = end;
-        loop {
-            let tile = &self.tiles[current.1][current.0];
-            // println!("tile: {:?}", tile);
-            if let Some(from) = tile.from {
-                // println!("from: {:?}", from);
-                cost += self.tiles[from.1][from.0].weight as u64;
-                if from == start {
-                    break;
-                }
-                current = from;
-                self.tiles[from.1][from.0].weight = 0;
-            } else {
-                break;
-            }
-        }
+        None
+    }
 
-        println!("map: \n{}", self);
+    fn route(&self, heat_loss: u64, mut state: usize, from: &[usize], runs: usize) -> Route {
+        let mut steps = vec![];
+        while from[state] != usize::MAX {
+            let tile = state / runs / 4;
+            let direction = Direction::ALL[state / runs % 4];
+            steps.push((tile % self.width, tile / self.width, direction));
+            state = from[state];
+        }
+        steps.reverse();
+        Route { heat_loss, steps }
+    }
 
-        cost
+    /// The map with each block on `route` replaced by the direction it was entered in.
+    pub fn overlay(&self, route: &Route) -> String {
+        let mut text: Vec<Vec<char>> = self
+            .heat
+            .chunks(self.width)
+            .map(|row| row.iter().map(|&heat| (b'0' + heat) as char).collect())
+            .collect();
+        for &(x, y, direction) in &route.steps {
+            text[y][x] = direction.arrow();
+        }
+        text.into_iter().flat_map(|row| row.into_iter().chain(['\n'])).collect()
     }
 }
 
-struct Solver {
-    reader: AsciiReader,
+/// Flags shared by both parts. Each day picks its own crucible when a run length is left out.
+#[derive(Args, Debug, Clone)]
+pub struct CrucibleOptions {
+    /// Blocks the crucible must go in a straight line before it can turn or stop
+    #[clap(long, value_name = "N")]
+    min_run: Option<usize>,
+    /// Blocks the crucible may go in a straight line before it has to turn
+    #[clap(long, value_name = "N")]
+    max_run: Option<usize>,
+    /// Print the map with the cheapest route drawn over it
+    #[clap(long)]
+    show_path: bool,
 }
 
-impl Solver {
-    fn new(input: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(input),
+impl CrucibleOptions {
+    pub fn solve(&self, bytes: Vec<u8>, default: Crucible) -> Result<u64, ParseError> {
+        let crucible = Crucible {
+            min_run: self.min_run.unwrap_or(default.min_run),
+            max_run: self.max_run.unwrap_or(default.max_run),
+        };
+        if crucible.max_run == 0 || crucible.min_run > crucible.max_run {
+            let msg = format!(
+                "runs of {} to {} blocks leave the crucible nowhere to go",
+                crucible.min_run, crucible.max_run
+            );
+            return Err(ParseError::new(msg));
         }
-    }
-    
-    fn solve(&mut self) -> u64 {
-        let mut map = Map::new(&mut self.reader);
 
-        println!("width: {}", map.tiles[0].len());
-        println!("height: {}", map.tiles.len());
-        println!("map:\n{}", map);
-        
-        map.min_cost((0,0), (map.tiles.len() - 1, map.tiles[0].len() - 1))
+        let map = Map::new(&mut AsciiReader::new(bytes))?;
+        let route = map.min_heat_loss(crucible).ok_or_else(|| {
+            let msg = format!(
+                "no route reaches the factory going {} to {} blocks at a time",
+                crucible.min_run, crucible.max_run
+            );
+            ParseError::new(msg)
+        })?;
+        if self.show_path {
+            print!("{}", map.overlay(&route));
+        }
+        Ok(route.heat_loss)
     }
 }
 
 #[derive(Parser, Debug)]
 pub struct Day17a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    #[clap(flatten)]
+    options: CrucibleOptions,
 }
 
 impl CommandImpl for Day17a {
     fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day 17 A: {answer}");
-        Ok(())
+        let options = self.options.clone();
+        self.input.for_each(|bytes| {
+            let answer = options.solve(bytes, Crucible { min_run: 1, max_run: 3 })?;
+            println!("Day 17 A: {answer}");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533
+";
+
+    fn solve(input: &[u8]) -> u64 {
+        parse(input).unwrap().min_heat_loss(Crucible { min_run: 1, max_run: 3 }).unwrap().heat_loss
+    }
+
+    fn parse(input: &[u8]) -> Result<Map, ParseError> {
+        Map::new(&mut AsciiReader::new(input.to_vec()))
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 102;
+        }
+    }
+
+    #[test]
+    fn show_path() {
+        let map = parse(EXAMPLE.as_bytes()).unwrap();
+        let route = map.min_heat_loss(Crucible { min_run: 1, max_run: 3 }).unwrap();
+        let heat_loss: u64 =
+            route.steps.iter().map(|&(x, y, _)| map.heat[y * map.width + x] as u64).sum();
+        assert_eq!(heat_loss, route.heat_loss);
+        // The statement's route ties with others, so check the rules instead of the drawing.
+        let directions: Vec<Direction> = route.steps.iter().map(|step| step.2).collect();
+        assert!(directions.windows(4).all(|run| run.iter().any(|&step| step != run[0])));
+        assert_eq!(route.steps.last().map(|&(x, y, _)| (x, y)), Some((12, 12)));
+        let overlay = map.overlay(&route);
+        assert_eq!(overlay.lines().next(), Some("2>>34^>>>1323"));
+        assert_eq!(overlay.chars().filter(|c| "^>v<".contains(*c)).count(), route.steps.len());
+    }
+
+    #[test]
+    fn runs_that_cannot_finish() {
+        let map = parse(b"19\n").unwrap();
+        assert_eq!(map.min_heat_loss(Crucible { min_run: 1, max_run: 3 }).unwrap().heat_loss, 9);
+        assert_eq!(map.min_heat_loss(Crucible { min_run: 2, max_run: 3 }), None);
+        let options = CrucibleOptions { min_run: Some(4), max_run: Some(3), show_path: false };
+        assert!(options
+            .solve(EXAMPLE.as_bytes().to_vec(), Crucible { min_run: 1, max_run: 3 })
+            .is_err());
+    }
+
+    #[test]
+    fn single_block() {
+        let map = parse(b"7").unwrap();
+        let route = map.min_heat_loss(Crucible { min_run: 4, max_run: 10 }).unwrap();
+        assert_eq!(route, Route { heat_loss: 0, steps: vec![] });
+        assert_eq!(map.overlay(&route), "7\n");
+    }
+
+    #[test]
+    fn trailing_blank_line() {
+        let map = parse(format!("{EXAMPLE}\n").as_bytes()).unwrap();
+        assert_eq!(map.to_string(), EXAMPLE);
+        assert_eq!(parse(b"19\n\n").unwrap().to_string(), "19\n");
+    }
+
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
@@ -0,0 +1,81 @@
+use clap::Parser;
+
+use crate::utils::Inputs;
+
+use super::{
+    day17a::{Crucible, CrucibleOptions},
+    CommandImpl, DynError,
+};
+
+/// An ultra crucible goes at least four blocks in a straight line and at most ten.
+pub const ULTRA: Crucible = Crucible { min_run: 4, max_run: 10 };
+
+#[derive(Parser, Debug)]
+pub struct Day17b {
+    #[clap(flatten)]
+    input: Inputs,
+    #[clap(flatten)]
+    options: CrucibleOptions,
+}
+
+impl CommandImpl for Day17b {
+    fn main(&mut self) -> Result<(), DynError> {
+        let options = self.options.clone();
+        self.input.for_each(|bytes| {
+            let answer = options.solve(bytes, ULTRA)?;
+            println!("Day 17 B: {answer}");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ULTRA;
+    use crate::commands::day17a::Map;
+    use crate::testing::{puzzle_tests, robustness_tests};
+    use crate::utils::{AsciiReader, ParseError};
+
+    const EXAMPLE: &str = "\
+2413432311323
+3215453535623
+3255245654254
+3446585845452
+4546657867536
+1438598798454
+4457876987766
+3637877979653
+4654967986887
+4564679986453
+1224686865563
+2546548887735
+4322674655533
+";
+
+    const UNFORTUNATE: &str = "\
+111111111111
+999999999991
+999999999991
+999999999991
+999999999991
+";
+
+    fn solve(input: &[u8]) -> u64 {
+        parse(input).unwrap().min_heat_loss(ULTRA).unwrap().heat_loss
+    }
+
+    fn parse(input: &[u8]) -> Result<Map, ParseError> {
+        Map::new(&mut AsciiReader::new(input.to_vec()))
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 94;
+            example unfortunate: UNFORTUNATE => 71;
+        }
+    }
+
+    robustness_tests! {
+        parse: EXAMPLE;
+    }
+}
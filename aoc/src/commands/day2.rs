@@ -1,35 +1,101 @@
-use std::{
-    fs::File,
-    io::{BufReader, Read},
-    path::PathBuf,
-};
+use std::{collections::BTreeMap, fmt, str::FromStr};
 
 use clap::Parser;
 
+use crate::utils::{Inputs, ParseError};
+
 use super::{CommandImpl, DynError};
 
 #[derive(Parser, Debug)]
 pub struct Day2 {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    /// How many cubes of a color the bag holds, as `color=count`. Repeat for each color; the
+    /// default bag is red=12, green=13 and blue=14
+    #[clap(long = "limit", value_name = "COLOR=COUNT", value_parser = parse_limit)]
+    limits: Vec<(String, u32)>,
+    /// Print each game's minimal cube set and the draws that break the limits
+    #[clap(long)]
+    report: bool,
     #[clap(skip)]
     read_idx: usize,
 }
 
+fn parse_limit(limit: &str) -> Result<(String, u32), String> {
+    let (color, count) = limit.split_once('=').ok_or("expected COLOR=COUNT")?;
+    if color.is_empty() || !color.bytes().all(|byte| byte.is_ascii_lowercase()) {
+        return Err(format!("color {color:?} must be lowercase letters"));
+    }
+    let count = count.parse().map_err(|err| format!("invalid count {count:?}: {err}"))?;
+    Ok((color.to_string(), count))
+}
+
+/// The most cubes of each color the bag holds. Colors the bag does not list hold none.
 #[derive(Debug)]
+struct Bag {
+    limits: BTreeMap<String, u32>,
+}
+
+impl Default for Bag {
+    fn default() -> Self {
+        Self::new(&[
+            (String::from("red"), 12),
+            (String::from("green"), 13),
+            (String::from("blue"), 14),
+        ])
+    }
+}
+
+impl Bag {
+    fn new(limits: &[(String, u32)]) -> Self {
+        Self { limits: limits.iter().cloned().collect() }
+    }
+
+    fn limit(&self, color: &str) -> u32 {
+        self.limits.get(color).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
 struct CubeSet {
-    blue: u8,
-    red: u8,
-    green: u8,
+    counts: BTreeMap<String, u32>,
 }
 
 impl CubeSet {
     fn new() -> Self {
-        Self { blue: 0, red: 0, green: 0 }
+        Self::default()
     }
 
     fn get_power(&self) -> u64 {
-        self.blue as u64 * self.red as u64 * self.green as u64
+        self.counts.values().map(|&count| count as u64).product()
+    }
+}
+
+impl fmt::Display for CubeSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, (color, count)) in self.counts.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{count} {color}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A draw that showed more cubes of a color than the bag holds.
+#[derive(Debug, PartialEq)]
+struct Violation {
+    draw: usize,
+    color: String,
+    count: u32,
+    limit: u32,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { draw, color, count, limit } = self;
+        write!(f, "draw {}: {count} {color} is more than the {limit} in the bag", draw + 1)
     }
 }
 
@@ -40,17 +106,36 @@ struct Game {
 }
 
 impl Game {
-    fn get_minimal_cube_set(&self) -> CubeSet {
+    /// The fewest cubes of each color that make the game possible. Every color in the bag is
+    /// counted, so a game that never draws one of them has no power.
+    fn get_minimal_cube_set(&self, bag: &Bag) -> CubeSet {
         let mut min_cube_set = CubeSet::new();
+        for color in bag.limits.keys() {
+            min_cube_set.counts.insert(color.clone(), 0);
+        }
 
         for cube_set in self.cube_sets.iter() {
-            min_cube_set.blue = min_cube_set.blue.max(cube_set.blue);
-            min_cube_set.red = min_cube_set.red.max(cube_set.red);
-            min_cube_set.green = min_cube_set.green.max(cube_set.green);
+            for (color, &count) in cube_set.counts.iter() {
+                let min_count = min_cube_set.counts.entry(color.clone()).or_default();
+                *min_count = (*min_count).max(count);
+            }
         }
 
         min_cube_set
     }
+
+    fn violations(&self, bag: &Bag) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for (draw, cube_set) in self.cube_sets.iter().enumerate() {
+            for (color, &count) in cube_set.counts.iter() {
+                let limit = bag.limit(color);
+                if count > limit {
+                    violations.push(Violation { draw, color: color.clone(), count, limit });
+                }
+            }
+        }
+        violations
+    }
 }
 
 struct GameIterator<'a> {
@@ -62,188 +147,138 @@ impl<'a> GameIterator<'a> {
     fn new(buffer: &'a [u8]) -> Self {
         Self { buffer, read_idx: 0 }
     }
+}
 
-    fn get_next_number(&mut self) -> u16 {
-        let mut number_slice_start = self.read_idx;
-
-        while self.buffer[self.read_idx].is_ascii_digit() {
-            self.read_idx += 1;
-        }
-
-        let number: u16 = std::str::from_utf8(&self.buffer[number_slice_start..self.read_idx])
-            .unwrap()
-            .parse()
-            .unwrap();
+impl<'a> Iterator for GameIterator<'a> {
+    type Item = Result<Game, ParseError>;
 
-        number
+    fn next(&mut self) -> Option<Self::Item> {
+        read_game(self.buffer, &mut self.read_idx).transpose()
     }
 }
 
-impl<'a> Iterator for GameIterator<'a> {
-    type Item = Game;
+fn read_number<T: FromStr>(buffer: &[u8], read_idx: &mut usize) -> Result<T, ParseError> {
+    let number_slice_start = *read_idx;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        // Check if we are at the end of the file
-        if self.read_idx >= self.buffer.len() {
-            return None;
-        }
+    while *read_idx < buffer.len() && buffer[*read_idx].is_ascii_digit() {
+        *read_idx += 1;
+    }
 
-        // Get game number
-        // Move ahead 5 characters to skip the text "Game "
-        self.read_idx += 5;
+    std::str::from_utf8(&buffer[number_slice_start..*read_idx])
+        .unwrap()
+        .parse()
+        .map_err(|_| ParseError::new(format!("expected a number at byte {number_slice_start}")))
+}
 
-        let game_number = self.get_next_number();
+fn expect(buffer: &[u8], read_idx: &mut usize, expected: &[u8]) -> Result<(), ParseError> {
+    if !buffer[(*read_idx).min(buffer.len())..].starts_with(expected) {
+        let expected = String::from_utf8_lossy(expected);
+        return Err(ParseError::new(format!("expected {expected:?} at byte {read_idx}")));
+    }
+    *read_idx += expected.len();
+    Ok(())
+}
 
-        // println!("Game number: {game_number}");
+/// Parse one "Game N: ..." line starting at `read_idx`, or `None` at the end of the buffer.
+fn read_game(buffer: &[u8], read_idx: &mut usize) -> Result<Option<Game>, ParseError> {
+    // Check if we are at the end of the file
+    if *read_idx >= buffer.len() {
+        return Ok(None);
+    }
 
-        // Move past the colon and space after the game number
-        self.read_idx += 2;
+    expect(buffer, read_idx, b"Game ")?;
+    let game_number = read_number(buffer, read_idx)?;
+    expect(buffer, read_idx, b": ")?;
 
-        let mut cube_sets: Vec<CubeSet> = vec![];
+    let mut cube_sets: Vec<CubeSet> = vec![];
 
-        // Read in this games values
-        'outer: loop {
-            let mut cube_set = CubeSet::new();
+    // Read in this games values
+    'outer: loop {
+        let mut cube_set = CubeSet::new();
 
-            loop {
-                let cube_count = self.get_next_number();
-                // Move past space after cube count
-                self.read_idx += 1;
+        loop {
+            let cube_count = read_number(buffer, read_idx)?;
+            expect(buffer, read_idx, b" ")?;
 
-                match self.buffer[self.read_idx] {
-                    b'b' => {
-                        self.read_idx += 4;
-                        cube_set.blue = cube_count as u8;
-                    }
-                    b'r' => {
-                        self.read_idx += 3;
-                        cube_set.red = cube_count as u8;
-                    }
-                    b'g' => {
-                        self.read_idx += 5;
-                        cube_set.green = cube_count as u8;
-                    }
-                    _ => {
-                        panic!("Unexpected color: {}", self.buffer[self.read_idx] as char);
-                    }
-                }
+            let color_start = *read_idx;
+            while buffer.get(*read_idx).is_some_and(u8::is_ascii_lowercase) {
+                *read_idx += 1;
+            }
+            if color_start == *read_idx {
+                return Err(ParseError::new(format!("expected a color at byte {read_idx}")));
+            }
+            let color = String::from_utf8_lossy(&buffer[color_start..*read_idx]).into_owned();
+            if cube_set.counts.insert(color, cube_count).is_some() {
+                return Err(ParseError::new(format!("color drawn twice at byte {color_start}")));
+            }
 
-                // Check for a comma or semicolon
-                if self.buffer[self.read_idx] == b',' {
-                    self.read_idx += 2;
-                } else if self.buffer[self.read_idx] == b';' {
+            // Check for a comma or semicolon
+            match buffer.get(*read_idx) {
+                Some(b',') => expect(buffer, read_idx, b", ")?,
+                Some(b';') => {
                     cube_sets.push(cube_set);
-                    self.read_idx += 2;
+                    expect(buffer, read_idx, b"; ")?;
                     break;
-                } else if (self.buffer[self.read_idx] == b'\n') {
+                }
+                Some(b'\n') | None => {
                     cube_sets.push(cube_set);
-                    self.read_idx += 1;
+                    *read_idx += 1;
                     break 'outer;
-                } else {
-                    panic!("Unexpected character: {}", self.buffer[self.read_idx] as char);
+                }
+                Some(&other) => {
+                    return Err(ParseError::new(format!(
+                        "unexpected character {:?} at byte {read_idx}",
+                        other as char
+                    )));
                 }
             }
         }
-
-        Some(Game { number: game_number, cube_sets })
     }
+
+    Ok(Some(Game { number: game_number, cube_sets }))
 }
 
 impl Day2 {
-    fn part_one(&mut self) {
-        let f: File = File::open(self.input.as_path()).unwrap();
-        let mut buffer = vec![];
-        let mut reader = BufReader::new(f);
-        reader.read_to_end(buffer.as_mut()).unwrap();
-
-        let mut game_sum = 0;
-
-        loop {
-            // Get game number
-            // Move ahead 5 characters to skip the text "Game "
-            self.read_idx += 5;
-            let mut game_number_slice_start = self.read_idx;
-
-            let game_number = self.get_next_number(&buffer);
-
-            // println!("Game number: {game_number}");
-
-            // Move past the colon and space after the game number
-            self.read_idx += 2;
-
-            let mut cube_sets: Vec<CubeSet> = vec![];
-
-            // Read in this games values
-            'outer: loop {
-                let mut cube_set = CubeSet::new();
-
-                loop {
-                    let cube_count = self.get_next_number(&buffer);
-                    // Move past space after cube count
-                    self.read_idx += 1;
-
-                    match buffer[self.read_idx] {
-                        b'b' => {
-                            self.read_idx += 4;
-                            cube_set.blue = cube_count as u8;
-                        }
-                        b'r' => {
-                            self.read_idx += 3;
-                            cube_set.red = cube_count as u8;
-                        }
-                        b'g' => {
-                            self.read_idx += 5;
-                            cube_set.green = cube_count as u8;
-                        }
-                        _ => {
-                            panic!("Unexpected color: {}", buffer[self.read_idx] as char);
-                        }
-                    }
-
-                    // Check for a comma or semicolon
-                    if buffer[self.read_idx] == b',' {
-                        self.read_idx += 2;
-                    } else if buffer[self.read_idx] == b';' {
-                        cube_sets.push(cube_set);
-                        self.read_idx += 2;
-                        break;
-                    } else if (buffer[self.read_idx] == b'\n') {
-                        cube_sets.push(cube_set);
-                        self.read_idx += 1;
-                        break 'outer;
-                    } else {
-                        panic!("Unexpected character: {}", buffer[self.read_idx] as char);
-                    }
-                }
+    fn bag(&self) -> Result<Bag, ParseError> {
+        for (idx, (color, _)) in self.limits.iter().enumerate() {
+            if self.limits[..idx].iter().any(|(other, _)| other == color) {
+                return Err(ParseError::new(format!("--limit gives {color} more than once")));
             }
+        }
+        if self.limits.is_empty() {
+            Ok(Bag::default())
+        } else {
+            Ok(Bag::new(&self.limits))
+        }
+    }
 
-            // println!("Cube sets: {:?}", cube_sets);
+    fn part_one(&mut self, buffer: &[u8]) -> Result<u64, ParseError> {
+        self.read_idx = 0;
+        let bag = self.bag()?;
 
-            // Check if the game can be played with only 12 red cubes, 13 green cubes, and 14 blue cubes
-            if self.game_is_possible(cube_sets.as_slice()) {
-                game_sum += game_number;
-            }
+        let mut game_sum = 0;
 
-            // Check if we are at the end of the file
-            if self.read_idx >= buffer.len() {
-                break;
+        while let Some(game) = self.get_next_game(buffer)? {
+            // println!("Cube sets: {:?}", game.cube_sets);
+
+            // Check if the game can be played with the cubes in the bag
+            if game.violations(&bag).is_empty() {
+                game_sum += game.number as u64;
             }
         }
 
-        println!("Game sum: {}", game_sum);
+        Ok(game_sum)
     }
 
-    fn part_two(&mut self) {
-        let f: File = File::open(self.input.as_path()).unwrap();
-        let mut buffer = vec![];
-        let mut reader = BufReader::new(f);
-        reader.read_to_end(buffer.as_mut()).unwrap();
+    fn part_two(&mut self, buffer: &[u8]) -> Result<u64, ParseError> {
+        self.read_idx = 0;
+        let bag = self.bag()?;
 
         let mut power_sum: u64 = 0;
         loop {
-            let game = self.get_next_game(&buffer);
+            let game = self.get_next_game(buffer)?;
             if let Some(game) = game {
-                power_sum += game.get_minimal_cube_set().get_power();
+                power_sum += game.get_minimal_cube_set(&bag).get_power();
             } else {
                 break;
             }
@@ -251,122 +286,216 @@ impl Day2 {
 
         // 62031 too low
 
-        println!("Power sum: {}", power_sum);
+        Ok(power_sum)
     }
 
-    fn part_two_with_iter(&mut self) {
-        let f: File = File::open(self.input.as_path()).unwrap();
-        let mut buffer = vec![];
-        let mut reader = BufReader::new(f);
-        reader.read_to_end(buffer.as_mut()).unwrap();
-
+    fn part_two_with_iter(buffer: &[u8]) -> Result<u64, ParseError> {
+        let bag = Bag::default();
         let mut power_sum: u64 = 0;
-        for game in GameIterator::new(&buffer) {
-            power_sum += game.get_minimal_cube_set().get_power();
+        for game in GameIterator::new(buffer) {
+            power_sum += game?.get_minimal_cube_set(&bag).get_power();
         }
 
-        println!("Power sum: {}", power_sum);
+        Ok(power_sum)
     }
 
-    fn game_is_possible(&self, cube_sets: &[CubeSet]) -> bool {
-        let max_red = 12;
-        let max_green = 13;
-        let max_blue = 14;
+    fn get_next_game(&mut self, buffer: &[u8]) -> Result<Option<Game>, ParseError> {
+        read_game(buffer, &mut self.read_idx)
+    }
+}
 
-        for cube_set in cube_sets {
-            if cube_set.red > max_red || cube_set.green > max_green || cube_set.blue > max_blue {
-                return false;
+impl CommandImpl for Day2 {
+    fn main(&mut self) -> Result<(), DynError> {
+        let bag = self.bag()?;
+        let report = self.report;
+
+        self.input.for_each(|bytes| {
+            let mut game_sum: u64 = 0;
+            let mut power_sum: u64 = 0;
+            for game in GameIterator::new(&bytes) {
+                let game = game?;
+                let violations = game.violations(&bag);
+                let min_cube_set = game.get_minimal_cube_set(&bag);
+                if violations.is_empty() {
+                    game_sum += game.number as u64;
+                }
+                power_sum += min_cube_set.get_power();
+
+                if report {
+                    let verdict = if violations.is_empty() { "possible" } else { "impossible" };
+                    let power = min_cube_set.get_power();
+                    println!(
+                        "Game {}: {verdict}, needs {min_cube_set} (power {power})",
+                        game.number
+                    );
+                    for violation in violations {
+                        println!("  {violation}");
+                    }
+                }
             }
-        }
 
-        true
+            println!("Day2a: {game_sum}");
+            println!("Day2b: {power_sum}");
+            Ok(())
+        })
     }
+}
 
-    fn get_next_number(&mut self, buffer: &[u8]) -> u16 {
-        let mut number_slice_start = self.read_idx;
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use proptest::{collection::vec, prelude::*};
 
-        while buffer[self.read_idx].is_ascii_digit() {
-            self.read_idx += 1;
-        }
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
 
-        let number: u16 = std::str::from_utf8(&buffer[number_slice_start..self.read_idx])
-            .unwrap()
-            .parse()
-            .unwrap();
+    const EXAMPLE: &str = "\
+Game 1: 3 blue, 4 red; 1 red, 2 green, 6 blue; 2 green
+Game 2: 1 blue, 2 green; 3 green, 4 blue, 1 red; 1 green, 1 blue
+Game 3: 8 green, 6 blue, 20 red; 5 blue, 4 red, 13 green; 5 green, 1 red
+Game 4: 1 green, 3 red, 6 blue; 3 green, 6 red; 3 green, 15 blue, 14 red
+Game 5: 6 red, 1 blue, 3 green; 2 blue, 1 red, 2 green
+";
 
-        number
+    fn part_one(input: &[u8]) -> u64 {
+        parse_part_one(input).unwrap()
     }
 
-    fn get_next_game(&mut self, buffer: &[u8]) -> Option<Game> {
-        // Check if we are at the end of the file
-        if self.read_idx >= buffer.len() {
-            return None;
-        }
+    fn part_two(input: &[u8]) -> u64 {
+        Day2::part_two_with_iter(input).unwrap()
+    }
 
-        // Get game number
-        // Move ahead 5 characters to skip the text "Game "
-        self.read_idx += 5;
+    fn parse_part_one(input: &[u8]) -> Result<u64, ParseError> {
+        day2(&[]).part_one(input)
+    }
 
-        let game_number = self.get_next_number(&buffer);
+    fn parse_games(input: &[u8]) -> Result<u64, ParseError> {
+        Day2::part_two_with_iter(input)
+    }
 
-        // println!("Game number: {game_number}");
+    puzzle_tests! {
+        part_one {
+            example statement: EXAMPLE => 8;
+            input real: "day2_input.txt" => 2551;
+        }
+        part_two {
+            example statement: EXAMPLE => 2286;
+            input real: "day2_input.txt" => 62811;
+        }
+    }
 
-        // Move past the colon and space after the game number
-        self.read_idx += 2;
+    /// One draw of cubes: every color appears at most once, in a shuffled order.
+    fn draw() -> impl Strategy<Value = String> {
+        let colors = prop::sample::subsequence(vec!["red", "green", "blue"], 1..=3).prop_shuffle();
+        (colors, vec(1..=20u8, 3)).prop_map(|(colors, counts)| {
+            colors
+                .iter()
+                .zip(counts)
+                .map(|(color, count)| format!("{count} {color}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    }
 
-        let mut cube_sets: Vec<CubeSet> = vec![];
+    fn games() -> impl Strategy<Value = String> {
+        vec(vec(draw(), 1..=4), 1..=6).prop_map(|games| {
+            games
+                .iter()
+                .enumerate()
+                .map(|(idx, draws)| format!("Game {}: {}", idx + 1, draws.join("; ")))
+                .collect::<Vec<_>>()
+                .join("\n")
+                + "\n"
+        })
+    }
 
-        // Read in this games values
-        'outer: loop {
-            let mut cube_set = CubeSet::new();
+    fn day2(args: &[&str]) -> Day2 {
+        Day2::parse_from(["day2", "--input", "-"].iter().chain(args))
+    }
 
-            loop {
-                let cube_count = self.get_next_number(&buffer);
-                // Move past space after cube count
-                self.read_idx += 1;
+    #[test]
+    fn custom_limits() {
+        let mut larger_bag =
+            day2(&["--limit", "red=20", "--limit", "green=13", "--limit", "blue=15"]);
+        assert_eq!(larger_bag.part_one(EXAMPLE.as_bytes()).unwrap(), 15);
+        assert_eq!(larger_bag.part_two(EXAMPLE.as_bytes()).unwrap(), 2286);
+    }
 
-                match buffer[self.read_idx] {
-                    b'b' => {
-                        self.read_idx += 4;
-                        cube_set.blue = cube_count as u8;
-                    }
-                    b'r' => {
-                        self.read_idx += 3;
-                        cube_set.red = cube_count as u8;
-                    }
-                    b'g' => {
-                        self.read_idx += 5;
-                        cube_set.green = cube_count as u8;
-                    }
-                    _ => {
-                        panic!("Unexpected color: {}", buffer[self.read_idx] as char);
-                    }
-                }
+    #[test]
+    fn arbitrary_colors() {
+        let input = b"Game 1: 2 purple, 1 red\nGame 2: 3 red; 1 red\n";
+        // The default bag holds no purple cubes
+        assert_eq!(day2(&[]).part_one(input).unwrap(), 2);
+        let mut bag = day2(&["--limit", "purple=2", "--limit", "red=3"]);
+        assert_eq!(bag.part_one(input).unwrap(), 3);
+        // Game 2 never draws purple
+        assert_eq!(bag.part_two(input).unwrap(), 2);
+    }
 
-                // Check for a comma or semicolon
-                if buffer[self.read_idx] == b',' {
-                    self.read_idx += 2;
-                } else if buffer[self.read_idx] == b';' {
-                    cube_sets.push(cube_set);
-                    self.read_idx += 2;
-                    break;
-                } else if (buffer[self.read_idx] == b'\n') {
-                    cube_sets.push(cube_set);
-                    self.read_idx += 1;
-                    break 'outer;
-                } else {
-                    panic!("Unexpected character: {}", buffer[self.read_idx] as char);
-                }
+    #[test]
+    fn reports_violations() {
+        let bag = Bag::default();
+        let games: Vec<Game> = GameIterator::new(EXAMPLE.as_bytes()).map(Result::unwrap).collect();
+
+        let violations: Vec<String> =
+            games[3].violations(&bag).iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            violations,
+            [
+                "draw 3: 15 blue is more than the 14 in the bag",
+                "draw 3: 14 red is more than the 12 in the bag",
+            ]
+        );
+        assert!(games[0].violations(&bag).is_empty());
+
+        let min_cube_set = games[2].get_minimal_cube_set(&bag);
+        assert_eq!(min_cube_set.to_string(), "6 blue, 13 green, 20 red");
+        assert_eq!(min_cube_set.get_power(), 1560);
+    }
+
+    #[test]
+    fn limit_arguments() {
+        assert_eq!(parse_limit("red=12"), Ok((String::from("red"), 12)));
+        for limit in ["red", "=3", "Red=3", "red=-1", "red=x"] {
+            assert!(parse_limit(limit).is_err(), "{limit}");
+        }
+        assert!(Day2::try_parse_from(["day2", "--input", "-", "--limit", "red"]).is_err());
+        let repeated = day2(&["--limit", "red=3", "--limit", "blue=1", "--limit", "red=5"]);
+        assert!(repeated.bag().is_err());
+    }
+
+    /// Both answers worked out by splitting the text, without any of the parsers above.
+    fn split_reference(input: &str) -> (u64, u64) {
+        let (mut possible, mut power) = (0, 0);
+        for line in input.lines() {
+            let (game, draws) = line.split_once(": ").unwrap();
+            let number: u64 = game.strip_prefix("Game ").unwrap().parse().unwrap();
+            let mut most = [0u64; 3];
+            for cubes in draws.split([';', ',']) {
+                let (count, color) = cubes.trim().split_once(' ').unwrap();
+                let color = ["red", "green", "blue"].iter().position(|&c| c == color).unwrap();
+                most[color] = most[color].max(count.parse().unwrap());
             }
+            if most[0] <= 12 && most[1] <= 13 && most[2] <= 14 {
+                possible += number;
+            }
+            power += most.iter().product::<u64>();
         }
+        (possible, power)
+    }
 
-        Some(Game { number: game_number, cube_sets })
+    proptest! {
+        #[test]
+        fn parsers_match_split_reference(input in games()) {
+            let (possible, power) = split_reference(&input);
+            prop_assert_eq!(part_one(input.as_bytes()), possible);
+            prop_assert_eq!(day2(&[]).part_two(input.as_bytes()).unwrap(), power);
+            prop_assert_eq!(part_two(input.as_bytes()), power);
+        }
     }
-}
 
-impl CommandImpl for Day2 {
-    fn main(&mut self) -> Result<(), DynError> {
-        self.part_two_with_iter();
-        Ok(())
+    robustness_tests! {
+        parse_part_one: EXAMPLE;
+        parse_games: EXAMPLE;
     }
 }
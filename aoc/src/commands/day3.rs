@@ -1,293 +1,346 @@
-use std::{path::PathBuf, fs::File, io::{BufReader, Read}};
+use std::fmt::Write;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+
+use crate::utils::{Inputs, ParseError};
 
 use super::{CommandImpl, DynError};
 
 #[derive(Parser, Debug)]
 pub struct Day3 {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    /// Print the graph of part numbers and the symbols they touch instead of the answers
+    #[clap(long, value_enum)]
+    export: Option<Export>,
 }
 
-impl Day3 {
-    fn part_one(&mut self) {
-        let f: File = File::open(self.input.as_path()).unwrap();
-        let mut input = vec![];
-        let mut reader = BufReader::new(f);
-        reader.read_to_end(input.as_mut()).unwrap();
-
-//         let input = b"467..114..
-// ...*......
-// ..35..633.
-// ......#...
-// 617*......
-// .....+.58.
-// ..592.....
-// ......755.
-// ...$.*....
-// .664.598..
-//         ";
-
-        let mut sum: u64 = 0;
-        let mut read_idx: usize = 0;
-        let mut number_idx: Option<usize> = None;
-
-        // Find the width of the input
-        let mut width = 0;
-        loop {
-            width += 1;
-            if input[read_idx] == b'\n' {
-                break;
-            }
-            read_idx += 1;
-        }
-        read_idx = 0;
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Export {
+    Dot,
+    Json,
+}
 
-        // println!("Width: {}", width);
+/// A number in the schematic, covering columns `start..end` of its row.
+#[derive(Debug, Clone, PartialEq)]
+struct Number {
+    value: u32,
+    row: usize,
+    start: usize,
+    end: usize,
+}
 
-        'outer: loop {
-            // Find a number
-            while !input[read_idx].is_ascii_digit() {
-                read_idx += 1;
-                if read_idx >= input.len() {
-                    break 'outer;
-                }
-            }
-            number_idx = Some(read_idx);
-
-            // Check if any digit is adjacent to a symbol
-            let mut adjacent_to_symbol = false;
-            'check_adjacent: loop {
-                // println!("read_idx: {}", read_idx);
-                let mut north_idx = None;
-                let mut east_idx = None;
-                let mut north_east_idx = None;
-                let mut south_idx = None;
-                let mut south_east_idx = None;
-                let mut west_idx = None;
-                let mut south_west_idx = None;
-                let mut north_west_idx = None;
-
-                // North: Any position after first row
-                if read_idx > width {
-                    north_idx = Some(read_idx - width);
-                }
-                // East: Any position not in last column
-                if (read_idx + 1) % width != width {
-                    east_idx = Some(read_idx + 1);
-                }
-                // South: Any position before last row
-                if read_idx < input.len() - width {
-                    south_idx = Some(read_idx + width);
-                }
-                // West: Any position not in first column
-                if read_idx % width != 0 {
-                    west_idx = Some(read_idx - 1);
-                }
+/// Any tile that is neither a digit nor a `.`.
+#[derive(Debug, Clone, PartialEq)]
+struct Symbol {
+    symbol: u8,
+    row: usize,
+    col: usize,
+}
 
-                // North-East: Any position after first row and before last column
-                if north_idx.is_some() && east_idx.is_some() {
-                    north_east_idx = Some(north_idx.unwrap() + 1);
-                }
-                // South-East: Any position before last row and before last column
-                if south_idx.is_some() && east_idx.is_some() {
-                    south_east_idx = Some(south_idx.unwrap() + 1);
-                }
+/// An engine schematic as numbers, symbols, and an edge for every number touching a symbol,
+/// diagonals included. Edges are `(number, symbol)` indices, ordered by number.
+#[derive(Debug)]
+struct Schematic {
+    numbers: Vec<Number>,
+    symbols: Vec<Symbol>,
+    edges: Vec<(usize, usize)>,
+}
 
-                // North-West: Any position after first row and before last column
-                if north_idx.is_some() && west_idx.is_some() {
-                    north_west_idx = Some(north_idx.unwrap() - 1);
-                }
-                // South-West: Any position before last row and before last column
-                if south_idx.is_some() && west_idx.is_some() {
-                    south_west_idx = Some(south_idx.unwrap() - 1);
-                }
+impl Schematic {
+    /// Read a schematic of equally long rows whose part numbers fit in a `u32`.
+    fn new(input: &[u8]) -> Result<Self, ParseError> {
+        let end = input.iter().rposition(|&byte| byte != b'\n').map_or(0, |idx| idx + 1);
+        let input = &input[..end];
+        let rows: Vec<&[u8]> = input.split(|&byte| byte == b'\n').collect();
+        let width = rows[0].len();
+
+        let mut numbers = Vec::new();
+        let mut symbols = Vec::new();
+        // The symbol on each tile, so numbers can look up their neighbors
+        let mut symbol_at: Vec<Option<usize>> = vec![None; width * rows.len()];
+
+        for (row, tiles) in rows.iter().enumerate() {
+            if tiles.len() != width {
+                return Err(ParseError::new(format!("row {} is not {width} tiles wide", row + 1)));
+            }
 
-                for idx in [north_idx, north_east_idx, east_idx, south_east_idx, south_idx, south_west_idx, west_idx, north_west_idx].iter().flatten() {
-                    if input[*idx] != b'.' && input[*idx] != b'\n' && !input[*idx].is_ascii_digit() {
-                        // println!("idx: {}", idx);
-                        adjacent_to_symbol = true;
-                        break 'check_adjacent;
+            let mut col = 0;
+            while col < width {
+                let tile = tiles[col];
+                if tile.is_ascii_digit() {
+                    let start = col;
+                    while col < width && tiles[col].is_ascii_digit() {
+                        col += 1;
                     }
+                    let value =
+                        std::str::from_utf8(&tiles[start..col]).unwrap().parse().map_err(|_| {
+                            ParseError::new(format!(
+                                "part number on row {} does not fit in a u32",
+                                row + 1
+                            ))
+                        })?;
+                    numbers.push(Number { value, row, start, end: col });
+                    continue;
                 }
-                read_idx += 1;
-
-                if !input[read_idx].is_ascii_digit() {
-                    break;
+                if tile != b'.' {
+                    symbol_at[row * width + col] = Some(symbols.len());
+                    symbols.push(Symbol { symbol: tile, row, col });
                 }
+                col += 1;
             }
+        }
 
-            // Find end of number
-            while input[read_idx].is_ascii_digit() {
-                read_idx += 1;
+        let mut edges = Vec::new();
+        for (number_idx, number) in numbers.iter().enumerate() {
+            let rows = number.row.saturating_sub(1)..(number.row + 2).min(rows.len());
+            for row in rows {
+                for col in number.start.saturating_sub(1)..(number.end + 1).min(width) {
+                    if let Some(symbol_idx) = symbol_at[row * width + col] {
+                        edges.push((number_idx, symbol_idx));
+                    }
+                }
             }
+        }
 
-            // println!("\n\nDigit: {} Adjacent to symbol: {}", String::from_utf8(input[number_idx.unwrap()..read_idx].to_vec()).unwrap(), adjacent_to_symbol);
+        Ok(Self { numbers, symbols, edges })
+    }
 
-            if adjacent_to_symbol {
-                let number = String::from_utf8(input[number_idx.unwrap()..read_idx].to_vec()).unwrap().parse::<u32>().unwrap();
-                sum += number as u64;
-            }
-            // panic!()
+    /// The numbers touching each symbol, in the order they were read.
+    fn symbol_neighbors(&self) -> Vec<Vec<usize>> {
+        let mut neighbors = vec![Vec::new(); self.symbols.len()];
+        for &(number_idx, symbol_idx) in &self.edges {
+            neighbors[symbol_idx].push(number_idx);
         }
+        neighbors
+    }
 
-        // 556590 is too high
-        // 556367
-        // 463346 is too low
-        println!("Sum: {}", sum);
+    /// A gear is any `*` touching exactly two numbers.
+    fn gears(&self) -> Vec<(usize, [usize; 2])> {
+        self.symbol_neighbors()
+            .into_iter()
+            .enumerate()
+            .filter(|(symbol_idx, _)| self.symbols[*symbol_idx].symbol == b'*')
+            .filter_map(|(symbol_idx, neighbors)| Some((symbol_idx, neighbors.try_into().ok()?)))
+            .collect()
+    }
 
+    fn part_number_sum(&self) -> u64 {
+        let mut is_part = vec![false; self.numbers.len()];
+        for &(number_idx, _) in &self.edges {
+            is_part[number_idx] = true;
+        }
+        self.numbers
+            .iter()
+            .zip(is_part)
+            .filter(|(_, is_part)| *is_part)
+            .map(|(n, _)| n.value as u64)
+            .sum()
+    }
 
+    fn gear_ratio_sum(&self) -> u64 {
+        self.gears()
+            .iter()
+            .map(|(_, [a, b])| self.numbers[*a].value as u64 * self.numbers[*b].value as u64)
+            .sum()
     }
 
-    fn part_two(&mut self) {
-        let f: File = File::open(self.input.as_path()).unwrap();
-        let mut input = vec![];
-        let mut reader = BufReader::new(f);
-        reader.read_to_end(input.as_mut()).unwrap();
-//         let input = b"467..114..
-// ...*......
-// ..35..633.
-// ......#...
-// 617*......
-// .....+.58.
-// ..592.....
-// ......755.
-// ...$.*....
-// .664.598..
-//         ";
-
-        let mut sum: u64 = 0;
-        let mut read_idx: usize = 0;
-        let mut number_idx: Option<usize> = None;
-
-        // Find the width of the input
-        let mut width = 0;
-        loop {
-            width += 1;
-            if input[read_idx] == b'\n' {
-                break;
-            }
-            read_idx += 1;
+    fn to_dot(&self) -> String {
+        let gears: Vec<usize> = self.gears().iter().map(|(symbol_idx, _)| *symbol_idx).collect();
+        let mut dot = String::from("graph schematic {\n");
+        for (idx, number) in self.numbers.iter().enumerate() {
+            let Number { value, row, start, end } = number;
+            writeln!(dot, "    n{idx} [label=\"{value}\" row={row} start={start} end={end}];")
+                .unwrap();
         }
-        read_idx = 0;
-
-        'outer: loop {
-            // Find a '*'
-            while input[read_idx] != b'*' {
-                read_idx += 1;
-                if read_idx >= input.len() {
-                    break 'outer;
-                }
-            }
-
-            let mut north_idx = None;
-            let mut east_idx = None;
-            let mut north_east_idx = None;
-            let mut south_idx = None;
-            let mut south_east_idx = None;
-            let mut west_idx = None;
-            let mut south_west_idx = None;
-            let mut north_west_idx = None;
-
-            // North: Any position after first row
-            if read_idx > width {
-                north_idx = Some(read_idx - width);
-            }
-            // East: Any position not in last column
-            if (read_idx + 1) % width != width {
-                east_idx = Some(read_idx + 1);
-            }
-            // South: Any position before last row
-            if read_idx < input.len() - width {
-                south_idx = Some(read_idx + width);
-            }
-            // West: Any position not in first column
-            if read_idx % width != 0 {
-                west_idx = Some(read_idx - 1);
-            }
+        for (idx, symbol) in self.symbols.iter().enumerate() {
+            let label = escape(symbol.symbol);
+            let Symbol { row, col, .. } = symbol;
+            let color = if gears.contains(&idx) { " color=red" } else { "" };
+            writeln!(dot, "    s{idx} [label=\"{label}\" shape=box row={row} col={col}{color}];")
+                .unwrap();
+        }
+        for (number_idx, symbol_idx) in &self.edges {
+            writeln!(dot, "    n{number_idx} -- s{symbol_idx};").unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
 
-            // North-East: Any position after first row and before last column
-            if north_idx.is_some() && east_idx.is_some() {
-                north_east_idx = Some(north_idx.unwrap() + 1);
-            }
-            // South-East: Any position before last row and before last column
-            if south_idx.is_some() && east_idx.is_some() {
-                south_east_idx = Some(south_idx.unwrap() + 1);
-            }
+    fn to_json(&self) -> String {
+        let gears: Vec<usize> = self.gears().iter().map(|(symbol_idx, _)| *symbol_idx).collect();
+        let numbers: Vec<String> = self
+            .numbers
+            .iter()
+            .map(|Number { value, row, start, end }| {
+                format!(
+                    "{{\"value\": {value}, \"row\": {row}, \"start\": {start}, \"end\": {end}}}"
+                )
+            })
+            .collect();
+        let symbols: Vec<String> = self
+            .symbols
+            .iter()
+            .enumerate()
+            .map(|(idx, Symbol { symbol, row, col })| {
+                let symbol = escape(*symbol);
+                let gear = gears.contains(&idx);
+                let position = format!("\"row\": {row}, \"col\": {col}");
+                format!("{{\"symbol\": \"{symbol}\", {position}, \"gear\": {gear}}}")
+            })
+            .collect();
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|(number_idx, symbol_idx)| format!("[{number_idx}, {symbol_idx}]"))
+            .collect();
+
+        let numbers = numbers.join(",\n    ");
+        let symbols = symbols.join(",\n    ");
+        let edges = edges.join(", ");
+        format!(
+            "{{\n  \"numbers\": [\n    {numbers}\n  ],\n  \"symbols\": [\n    {symbols}\n  ],\n  \
+             \"edges\": [{edges}]\n}}\n"
+        )
+    }
+}
 
-            // North-West: Any position after first row and before last column
-            if north_idx.is_some() && west_idx.is_some() {
-                north_west_idx = Some(north_idx.unwrap() - 1);
-            }
-            // South-West: Any position before last row and before last column
-            if south_idx.is_some() && west_idx.is_some() {
-                south_west_idx = Some(south_idx.unwrap() - 1);
-            }
+/// A symbol as text that is safe inside both a DOT and a JSON string.
+fn escape(symbol: u8) -> String {
+    match symbol {
+        b'"' | b'\\' => format!("\\{}", symbol as char),
+        b' '..=b'~' => (symbol as char).to_string(),
+        _ => format!("\\u{symbol:04x}"),
+    }
+}
 
-            let mut numeric_neighbors: Vec<usize> = Vec::with_capacity(8);
-            // The order of these matters for the neighbor check below. Check in order of increasing idx value
-            for idx in [north_west_idx, north_idx, north_east_idx, east_idx, west_idx, south_west_idx, south_idx, south_east_idx].into_iter().flatten() {
-                if input[idx].is_ascii_digit() {
-                    numeric_neighbors.push(idx);
-                }
-            }
+impl Day3 {
+    fn part_one(&self, input: &[u8]) -> Result<u64, ParseError> {
+        // 556590 is too high
+        // 556367
+        // 463346 is too low
+        Ok(Schematic::new(input)?.part_number_sum())
+    }
 
-            // A gear is any * symbol that is adjacent to exactly two part numbers. 
-            if numeric_neighbors.len() < 2 {
-                read_idx += 1;
-                continue;
-            }
+    fn part_two(&self, input: &[u8]) -> Result<u64, ParseError> {
+        Ok(Schematic::new(input)?.gear_ratio_sum())
+    }
+}
 
-            // Numeric neighbors may be touching each other, which would reduce the total number neighbor count
-            let mut neighbors: Vec<usize> = Vec::with_capacity(2);
-            let mut neighbor_start = numeric_neighbors[0];
-            let mut last_neighbor = neighbor_start;
-            for neighbor in numeric_neighbors[1..].iter() {
-                if *neighbor != last_neighbor + 1 {
-                    neighbors.push(neighbor_start);
-                    neighbor_start = *neighbor;
+impl CommandImpl for Day3 {
+    fn main(&mut self) -> Result<(), DynError> {
+        let export = self.export;
+        self.input.for_each(|bytes| {
+            let schematic = Schematic::new(&bytes)?;
+            match export {
+                Some(Export::Dot) => print!("{}", schematic.to_dot()),
+                Some(Export::Json) => print!("{}", schematic.to_json()),
+                None => {
+                    println!("Day3a: {}", schematic.part_number_sum());
+                    println!("Day3b: {}", schematic.gear_ratio_sum());
                 }
-                last_neighbor = *neighbor;
             }
-            neighbors.push(neighbor_start);
-            read_idx += 1;
+            Ok(())
+        })
+    }
+}
 
-            if neighbors.len() != 2 {
-                continue;
-            }
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+467..114..
+...*......
+..35..633.
+......#...
+617*......
+.....+.58.
+..592.....
+......755.
+...$.*....
+.664.598..
+";
+
+    fn part_one(input: &[u8]) -> u64 {
+        parse_part_one(input).unwrap()
+    }
 
-            let gear_ratio = self.get_number(&input[..], neighbors[0]) * self.get_number(&input[..], neighbors[1]);
-            sum += gear_ratio as u64;
-        }
-        println!("sum: {}", sum);
+    fn part_two(input: &[u8]) -> u64 {
+        parse_part_two(input).unwrap()
+    }
 
+    fn parse_part_one(input: &[u8]) -> Result<u64, ParseError> {
+        Day3::parse_from(["day3", "--input", "-"]).part_one(input)
     }
 
-    fn get_number(&self, input: &[u8], idx: usize) -> u32 {
-        let mut right_idx = idx + 1;
-        let mut left_idx = if idx > 0 { idx - 1 } else { 0 };
-        let mut start_idx = idx;
-        let mut end_idx = idx;
-        while input[left_idx].is_ascii_digit() {
-            start_idx = left_idx;
-            if left_idx == 0 {
-                break;
-            }
-            left_idx -= 1;
+    fn parse_part_two(input: &[u8]) -> Result<u64, ParseError> {
+        Day3::parse_from(["day3", "--input", "-"]).part_two(input)
+    }
+
+    puzzle_tests! {
+        part_one {
+            example statement: EXAMPLE => 4361;
+            example no_trailing_newline: "1*1" => 2;
+            input real: "day3_input.txt" => 556367;
         }
-        while input[right_idx].is_ascii_digit() {
-            end_idx = right_idx;
-            right_idx += 1;
+        part_two {
+            example statement: EXAMPLE => 467835;
+            example no_trailing_newline: "2*3" => 6;
+            input real: "day3_input.txt" => 89471771;
         }
-        // println!("Found: {:?}", String::from_utf8(input[start_idx..end_idx + 1].to_vec()));
-        String::from_utf8(input[start_idx..end_idx + 1].to_vec()).unwrap().parse::<u32>().unwrap()
     }
+
+    #[test]
+    fn model() {
+        let schematic = Schematic::new(EXAMPLE.as_bytes()).unwrap();
+        assert_eq!(schematic.numbers.len(), 10);
+        assert_eq!(schematic.numbers[0], Number { value: 467, row: 0, start: 0, end: 3 });
+        assert_eq!(schematic.symbols.len(), 6);
+        assert_eq!(schematic.symbols[0], Symbol { symbol: b'*', row: 1, col: 3 });
+        // 114 and 58 touch nothing; 467 and 35 share the first gear
+        assert_eq!(schematic.edges.len(), 8);
+        assert_eq!(schematic.gears(), [(0, [0, 2]), (5, [7, 9])]);
+    }
+
+    #[test]
+    fn export() {
+        let schematic = Schematic::new(b"12*3\n\"...\n").unwrap();
+        assert_eq!(
+            schematic.to_dot(),
+            r#"graph schematic {
+    n0 [label="12" row=0 start=0 end=2];
+    n1 [label="3" row=0 start=3 end=4];
+    s0 [label="*" shape=box row=0 col=2 color=red];
+    s1 [label="\"" shape=box row=1 col=0];
+    n0 -- s0;
+    n0 -- s1;
+    n1 -- s0;
+}
+"#
+        );
+        assert_eq!(
+            schematic.to_json(),
+            r#"{
+  "numbers": [
+    {"value": 12, "row": 0, "start": 0, "end": 2},
+    {"value": 3, "row": 0, "start": 3, "end": 4}
+  ],
+  "symbols": [
+    {"symbol": "*", "row": 0, "col": 2, "gear": true},
+    {"symbol": "\"", "row": 1, "col": 0, "gear": false}
+  ],
+  "edges": [[0, 0], [0, 1], [1, 0]]
 }
+"#
+        );
+    }
 
-impl CommandImpl for Day3 {
-    fn main(&mut self) -> Result<(), DynError> {
-        self.part_two();
-        Ok(())
+    robustness_tests! {
+        parse_part_one: EXAMPLE;
+        parse_part_two: EXAMPLE;
     }
 }
@@ -1,187 +1,388 @@
-use std::path::PathBuf;
+use std::{collections::VecDeque, fmt};
 
 use clap::Parser;
 
-use crate::utils::slurp_bytes;
+use crate::utils::{Inputs, ParseError};
 
 use super::{CommandImpl, DynError};
 
 #[derive(Parser, Debug)]
 pub struct Day4 {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    /// Print every card's matches, points and final number of copies
+    #[clap(long)]
+    breakdown: bool,
+    /// Trace which cards won the copies of the Nth card in the input
+    #[clap(long, value_name = "N")]
+    explain: Option<usize>,
 }
 
-pub struct CardScorer<'a> {
+/// A scratchcard, reduced to its label and how many of its numbers are winning numbers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Card {
+    pub id: u32,
+    pub matches: u32,
+}
+
+impl Card {
+    /// The first match is worth one point and every match after it doubles the points.
+    pub fn points(&self) -> Result<u32, ParseError> {
+        match self.matches {
+            0 => Ok(0),
+            matches => 1u32
+                .checked_shl(matches - 1)
+                .ok_or_else(|| ParseError::new(format!("{matches} matches overflow a u32 score"))),
+        }
+    }
+}
+
+pub struct CardReader<'a> {
     bytes: &'a [u8],
     read_idx: usize,
 }
 
-impl<'a> CardScorer<'a> {
-    fn new(bytes: &'a [u8]) -> Self {
-        Self {
-            bytes,
-            read_idx: 0,
-        }
+impl<'a> CardReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, read_idx: 0 }
     }
+}
 
-    fn read_next_number(&mut self) -> Option<u32> {
-        while self.bytes[self.read_idx] == b' ' {
-            self.read_idx += 1;
-        }
+impl<'a> Iterator for CardReader<'a> {
+    type Item = Result<Card, ParseError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        read_card(self.bytes, &mut self.read_idx).transpose()
+    }
+}
 
-        let mut num = 0;
-        while self.bytes[self.read_idx].is_ascii_digit() {
-            num *= 10;
-            num += (self.bytes[self.read_idx] - b'0') as u32;
-            self.read_idx += 1;
-        }
+fn skip_spaces(bytes: &[u8], read_idx: &mut usize) {
+    while bytes.get(*read_idx) == Some(&b' ') {
+        *read_idx += 1;
+    }
+}
 
-        while self.bytes[self.read_idx] == b' ' {
-            self.read_idx += 1;
-        }
-        // *read_idx += 1;
-        Some(num)
+fn read_number(bytes: &[u8], read_idx: &mut usize) -> Result<u32, ParseError> {
+    let start = *read_idx;
+    let mut num: u32 = 0;
+    while let Some(digit) = bytes.get(*read_idx).filter(|byte| byte.is_ascii_digit()) {
+        num = num.saturating_mul(10).saturating_add((digit - b'0') as u32);
+        *read_idx += 1;
+    }
+    if *read_idx == start {
+        return Err(ParseError::new(format!("expected a number at byte {start}")));
     }
+    Ok(num)
 }
 
-impl<'a> Iterator for CardScorer<'a> {
-    type Item = u32;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.read_idx >= self.bytes.len() - 1 {
-            return None;
-        }
+/// Read the next card, or `None` past the last card.
+fn read_card(bytes: &[u8], read_idx: &mut usize) -> Result<Option<Card>, ParseError> {
+    if *read_idx >= bytes.len() {
+        return Ok(None);
+    }
+
+    // Skip past "Card " text
+    if !bytes[*read_idx..].starts_with(b"Card ") {
+        return Err(ParseError::new(format!("expected \"Card\" at byte {read_idx}")));
+    }
+    *read_idx += 4;
+    skip_spaces(bytes, read_idx);
+    let id = read_number(bytes, read_idx)?;
+    if bytes.get(*read_idx) != Some(&b':') {
+        return Err(ParseError::new(format!("expected ':' at byte {read_idx}")));
+    }
+    *read_idx += 1;
 
-        // Find beginning of numbers
-        // Skip past "Card " text
-        self.read_idx += 4;
-        while self.bytes[self.read_idx] != b':' {
-            self.read_idx += 1;
+    let mut map: [u8; 100] = [0; 100];
+    let mut number = |bytes: &[u8], read_idx: &mut usize| {
+        let start = *read_idx;
+        match read_number(bytes, read_idx)? {
+            value @ 0..=99 => Ok(value as usize),
+            _ => Err(ParseError::new(format!("card numbers must be below 100 at byte {start}"))),
         }
-        // Skip white space after colon
-        self.read_idx += 2;
+    };
 
-        let mut map: [u8; 100] = [0; 100];
+    loop {
+        skip_spaces(bytes, read_idx);
+        if bytes.get(*read_idx) == Some(&b'|') {
+            break;
+        }
+        map[number(bytes, read_idx)?] = 1;
+    }
+    // Skip past pipe
+    *read_idx += 1;
 
-        while let Some(value) = self.read_next_number() {
-            map[value as usize] = 1;
-            if self.bytes[self.read_idx] == b'|' {
+    let mut matches: u32 = 0;
+    loop {
+        skip_spaces(bytes, read_idx);
+        match bytes.get(*read_idx) {
+            Some(b'\n') => {
+                *read_idx += 1;
                 break;
             }
+            None => break,
+            Some(_) => matches += map[number(bytes, read_idx)?] as u32,
         }
-        // Skip past pipe and whitespace
-        self.read_idx += 2;
+    }
 
-        let mut points: u32 = 0;
-        while let Some(value) = self.read_next_number() {
-            points += map[value as usize] as u32;
+    Ok(Some(Card { id, matches }))
+}
 
-            if self.bytes[self.read_idx] == b'\n' {
-                break;
-            }
+/// A card after every card before it has handed out its copies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tally {
+    /// 1-based position in the input, which is what copies are won by
+    pub position: usize,
+    pub card: Card,
+    pub copies: u64,
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Tally { card, copies, .. } = self;
+        write!(f, "Card {}: {} matches, ", card.id, card.matches)?;
+        match card.points() {
+            Ok(points) => write!(f, "{points} points")?,
+            Err(_) => write!(f, "2^{} points", card.matches - 1)?,
+        }
+        write!(f, ", {copies} copies")
+    }
+}
+
+/// Hands out copies as cards stream past. Only the copies owed to the coming cards are kept, so
+/// memory is bounded by the most matches on a card rather than the number of cards.
+#[derive(Debug, Default)]
+pub struct Cascade {
+    position: usize,
+    owed: VecDeque<u64>,
+}
+
+impl Cascade {
+    pub fn push(&mut self, card: Card) -> Result<Tally, ParseError> {
+        let overflow = || ParseError::new(String::from("card copies overflow a u64"));
+        let copies = self.owed.pop_front().unwrap_or(0).checked_add(1).ok_or_else(overflow)?;
+
+        // Each copy of this card wins one copy of each of the next `matches` cards
+        let matches = card.matches as usize;
+        if self.owed.len() < matches {
+            self.owed.resize(matches, 0);
+        }
+        for owed in self.owed.iter_mut().take(matches) {
+            *owed = owed.checked_add(copies).ok_or_else(overflow)?;
         }
 
-        Some(points)
+        self.position += 1;
+        Ok(Tally { position: self.position, card, copies })
+    }
+}
+
+/// Both answers. Points fail on their own once they overflow, since copies never need them.
+#[derive(Debug)]
+pub struct Totals {
+    pub points: Result<u32, ParseError>,
+    pub cards: u64,
+}
+
+/// The cards that won copies of one card, with how many copies each of them had.
+#[derive(Debug, PartialEq)]
+pub struct Explanation {
+    pub tally: Tally,
+    pub sources: Vec<Tally>,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Tally { position, card, copies, .. } = self.tally;
+        writeln!(f, "Card {} (card #{position} in the input) has {copies} copies:", card.id)?;
+        writeln!(f, "  1 original")?;
+        for source in &self.sources {
+            let Tally { position, card, copies, .. } = source;
+            writeln!(
+                f,
+                "  {copies} won by card {} (#{position}, {} matches)",
+                card.id, card.matches
+            )?;
+        }
+        Ok(())
     }
 }
 
 impl Day4 {
-    pub fn part_one(bytes: &[u8]) {
-        let mut read_idx: usize = 0;
-        let mut sum = 0;
-        while let Some(sore) = Self::get_next_card_score(bytes, &mut read_idx) {
-            sum += sore;
+    /// Score every card in one pass, calling `visit` with each card's tally as it is settled.
+    pub fn tally(
+        bytes: &[u8],
+        mut visit: impl FnMut(&Tally) -> Result<(), ParseError>,
+    ) -> Result<Totals, ParseError> {
+        let mut cascade = Cascade::default();
+        let mut totals = Totals { points: Ok(0), cards: 0 };
+        for card in CardReader::new(bytes) {
+            let tally = cascade.push(card?)?;
+            visit(&tally)?;
+            if let Ok(points) = totals.points {
+                totals.points = tally.card.points().and_then(|card_points| {
+                    points
+                        .checked_add(card_points)
+                        .ok_or_else(|| ParseError::new(String::from("total points overflow a u32")))
+                });
+            }
+            totals.cards = u64::checked_add(totals.cards, tally.copies)
+                .ok_or_else(|| ParseError::new(String::from("card copies overflow a u64")))?;
         }
-        println!("Part 1: {}", sum);
+        Ok(totals)
+    }
+
+    pub fn part_one(bytes: &[u8]) -> Result<u32, ParseError> {
         // 20117
+        Self::tally(bytes, |_| Ok(()))?.points
+    }
+
+    pub fn part_two(bytes: &[u8]) -> Result<u64, ParseError> {
+        //13768818
+        Ok(Self::tally(bytes, |_| Ok(()))?.cards)
+    }
+
+    /// Find the cards whose matches reached the `position`th card.
+    pub fn explain(bytes: &[u8], position: usize) -> Result<Explanation, ParseError> {
+        let mut sources = Vec::new();
+        let mut target = None;
+        let mut count = 0;
+        Self::tally(bytes, |tally| {
+            count = tally.position;
+            if tally.position < position && tally.position + tally.card.matches as usize >= position
+            {
+                sources.push(*tally);
+            } else if tally.position == position {
+                target = Some(*tally);
+            }
+            Ok(())
+        })?;
+        let tally = target.ok_or_else(|| {
+            ParseError::new(format!("there is no card #{position}, only {count} cards"))
+        })?;
+        Ok(Explanation { tally, sources })
     }
+}
 
-    pub fn part_two(bytes: &[u8]) {
-        let mut read_idx: usize = 0;
-        let mut card_count = 0;
-        let mut card_copy_map = [1; 10000];
-        for (idx, score) in CardScorer::new(bytes).enumerate() {
-            for map_idx in (idx+1)..(1+idx+score as usize) {
-                if map_idx > card_copy_map.len() {
-                    break;
+impl CommandImpl for Day4 {
+    fn main(&mut self) -> Result<(), DynError> {
+        let breakdown = self.breakdown;
+        let explain = self.explain;
+        self.input.for_each(|bytes| {
+            if let Some(position) = explain {
+                print!("{}", Self::explain(&bytes, position)?);
+            }
+            let totals = Self::tally(&bytes, |tally| {
+                if breakdown {
+                    println!("{tally}");
                 }
-                card_copy_map[map_idx] += card_copy_map[idx];
+                Ok(())
+            })?;
+            match totals.points {
+                Ok(points) => println!("Day4a: {points}"),
+                Err(err) => println!("Day4a: {err}"),
             }
-            card_count += card_copy_map[idx];
-        }
-        //13768818
-        println!("Part 2: {}", card_count);
+            println!("Day4b: {}", totals.cards);
+            Ok(())
+        })
     }
+}
 
-    fn read_next_number(bytes: &[u8], read_idx: &mut usize) -> Option<u32> {
-        while bytes[*read_idx] == b' ' {
-            *read_idx += 1;
-        }
+#[cfg(test)]
+mod tests {
+    use super::{Card, Day4, Tally};
+    use crate::testing::{puzzle_tests, robustness_tests};
+    use crate::utils::ParseError;
 
-        let mut num = 0;
-        while bytes[*read_idx].is_ascii_digit() {
-            num *= 10;
-            num += (bytes[*read_idx] - b'0') as u32;
-            *read_idx += 1;
-        }
+    const EXAMPLE: &str = "\
+Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
+Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
+Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
+Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
+Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
+";
 
-        while bytes[*read_idx] == b' ' {
-            *read_idx += 1;
-        }
-        // *read_idx += 1;
-        Some(num)
+    fn part_one(input: &[u8]) -> u32 {
+        Day4::part_one(input).unwrap()
     }
 
-    fn get_next_card_score(bytes: &[u8], read_idx: &mut usize) -> Option<u32> {
-        if *read_idx >= bytes.len() - 1 {
-            return None;
-        }
+    fn part_two(input: &[u8]) -> u64 {
+        Day4::part_two(input).unwrap()
+    }
 
-        // Find beginning of numbers
-        // Skip past "Card " text
-        *read_idx += 4;
-        while bytes[*read_idx] != b':' {
-            *read_idx += 1;
-        }
-        // Skip white space after colon
-        *read_idx += 2;
+    fn parse_part_one(input: &[u8]) -> Result<u32, ParseError> {
+        Day4::part_one(input)
+    }
 
-        let mut map: [u8; 100] = [0; 100];
+    fn parse_part_two(input: &[u8]) -> Result<u64, ParseError> {
+        Day4::part_two(input)
+    }
 
-        while let Some(value) = Self::read_next_number(bytes, read_idx) {
-            map[value as usize] = 1;
-            if bytes[*read_idx] == b'|' {
-                break;
-            }
+    puzzle_tests! {
+        part_one {
+            example statement: EXAMPLE => 13;
+            input real: "day4.txt" => 20117;
         }
-        // Skip past pipe and whitespace
-        *read_idx += 2;
-
-        let mut points: u32 = 0;
-        while let Some(value) = Self::read_next_number(bytes, read_idx) {
-            if map[value as usize] == 1 {
-                if points > 0 {
-                    points *= 2;
-                } else {
-                    points = 1;
-                }
-            }
-
-            if bytes[*read_idx] == b'\n' {
-                break;
-            }
+        part_two {
+            example statement: EXAMPLE => 30;
+            input real: "day4.txt" => 13768818;
+            input big: "day4_big.txt" => 688440900;
         }
+    }
 
-        Some(points)
+    #[test]
+    fn breakdown() {
+        let mut tallies = Vec::new();
+        Day4::tally(EXAMPLE.as_bytes(), |tally| {
+            tallies.push((tally.card.matches, tally.card.points().unwrap(), tally.copies));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(tallies, [(4, 8, 1), (2, 2, 2), (2, 2, 4), (1, 1, 8), (0, 0, 14), (0, 0, 1)]);
     }
 
+    #[test]
+    fn explain() {
+        let explanation = Day4::explain(EXAMPLE.as_bytes(), 5).unwrap();
+        let sources: Vec<_> = explanation.sources.iter().map(|s| (s.card.id, s.copies)).collect();
+        assert_eq!(sources, [(1, 1), (3, 4), (4, 8)]);
+        assert_eq!(
+            explanation.tally,
+            Tally { position: 5, card: Card { id: 5, matches: 0 }, copies: 14 }
+        );
+        assert_eq!(
+            explanation.to_string(),
+            "\
+Card 5 (card #5 in the input) has 14 copies:
+  1 original
+  1 won by card 1 (#1, 4 matches)
+  4 won by card 3 (#3, 2 matches)
+  8 won by card 4 (#4, 1 matches)
+"
+        );
+        assert!(Day4::explain(EXAMPLE.as_bytes(), 7).is_err());
+    }
 
-}
+    #[test]
+    fn copies_past_the_points_limit() {
+        // 33 matches would score 2^32 points, which only part one needs
+        let numbers: Vec<String> = (0..33).map(|number| number.to_string()).collect();
+        let numbers = numbers.join(" ");
+        let input = format!("Card 1: {numbers} | {numbers}\nCard 2: 5 | 6\n");
+        assert!(Day4::part_one(input.as_bytes()).is_err());
+        assert_eq!(Day4::part_two(input.as_bytes()).unwrap(), 3);
 
-impl CommandImpl for Day4 {
-    fn main(&mut self) -> Result<(), DynError> {
-        let bytes = slurp_bytes(self.input.as_path()).unwrap();
-        Self::part_two(&bytes);
-        Ok(())
+        let mut lines = Vec::new();
+        Day4::tally(input.as_bytes(), |tally| {
+            lines.push(tally.to_string());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(lines[0], "Card 1: 33 matches, 2^32 points, 1 copies");
+        assert_eq!(lines[1], "Card 2: 0 matches, 0 points, 2 copies");
+    }
+
+    robustness_tests! {
+        parse_part_one: EXAMPLE;
+        parse_part_two: EXAMPLE;
     }
 }
@@ -1,31 +1,194 @@
-use std::path::PathBuf;
+use clap::{Args, Parser};
 
-use clap::Parser;
-
-use crate::utils::slurp_bytes;
+use crate::utils::{Inputs, ParseError};
 
 use super::{CommandImpl, DynError};
 
 #[derive(Parser, Debug)]
 pub struct Day5a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    #[clap(flatten)]
+    queries: Queries,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MapRange {
-    pub destination_range_start: u32,
-    pub source_range_start: u32,
-    pub range_length: u32,
+    pub destination_range_start: u64,
+    pub source_range_start: u64,
+    pub range_length: u64,
 }
 
 impl MapRange {
-    fn map(&self, seed: u32) -> Option<u32> {
-        if seed < self.source_range_start || seed >= self.source_range_start + self.range_length {
+    fn map(&self, seed: u64) -> Option<u64> {
+        let offset = seed.checked_sub(self.source_range_start)?;
+        if offset >= self.range_length {
             return None;
         }
+        Some(self.destination_range_start + offset)
+    }
+}
+
+/// Values `start..=last` of the input map to `destination + (value - start)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Piece {
+    pub start: u64,
+    pub last: u64,
+    pub destination: u64,
+}
+
+impl Piece {
+    fn map(&self, value: u64) -> u64 {
+        self.destination + (value - self.start)
+    }
+
+    fn destination_last(&self) -> u64 {
+        self.map(self.last)
+    }
+}
+
+/// A piecewise map over every `u64`, as pieces sorted by `start` that cover `0..=u64::MAX`
+/// without gaps. Each almanac map is one of these, and so is their composition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Piecewise {
+    pieces: Vec<Piece>,
+}
+
+impl Piecewise {
+    pub fn identity() -> Self {
+        Self { pieces: vec![Piece { start: 0, last: u64::MAX, destination: 0 }] }
+    }
+
+    /// One almanac map. Values outside every range map to themselves, and where ranges overlap
+    /// the first one listed wins, as it does when mapping values range by range.
+    pub fn from_ranges(ranges: &[MapRange]) -> Self {
+        let ranges: Vec<&MapRange> = ranges.iter().filter(|range| range.range_length > 0).collect();
+        let mut starts = vec![0];
+        for range in &ranges {
+            starts.push(range.source_range_start);
+            starts.extend((range.source_range_start).checked_add(range.range_length));
+        }
+        starts.sort_unstable();
+        starts.dedup();
+
+        let mut pieces = Vec::with_capacity(starts.len());
+        for (idx, &start) in starts.iter().enumerate() {
+            let last = starts.get(idx + 1).map_or(u64::MAX, |next| next - 1);
+            let destination = ranges.iter().find_map(|range| range.map(start)).unwrap_or(start);
+            pieces.push(Piece { start, last, destination });
+        }
+        Self::merged(pieces)
+    }
+
+    pub fn from_maps(maps: &[Vec<MapRange>]) -> Self {
+        maps.iter().fold(Self::identity(), |composed, map| composed.then(&Self::from_ranges(map)))
+    }
+
+    /// Join neighboring pieces that continue the same shift.
+    fn merged(pieces: Vec<Piece>) -> Self {
+        let mut merged: Vec<Piece> = Vec::with_capacity(pieces.len());
+        for piece in pieces {
+            match merged.last_mut() {
+                Some(last) if last.destination_last().checked_add(1) == Some(piece.destination) => {
+                    last.last = piece.last;
+                }
+                _ => merged.push(piece),
+            }
+        }
+        Self { pieces: merged }
+    }
+
+    /// The map applying `self` and then `next`.
+    pub fn then(&self, next: &Piecewise) -> Self {
+        let mut pieces = Vec::new();
+        for piece in &self.pieces {
+            // Split the values this piece maps to wherever `next` starts a new piece
+            let mut value = piece.destination;
+            let mut start = piece.start;
+            loop {
+                let inner = next.piece_for(value);
+                let span = (inner.last - value).min(piece.last - start);
+                pieces.push(Piece { start, last: start + span, destination: inner.map(value) });
+                if start + span == piece.last {
+                    break;
+                }
+                start += span + 1;
+                value += span + 1;
+            }
+        }
+        Self::merged(pieces)
+    }
+
+    fn piece_for(&self, value: u64) -> &Piece {
+        let idx = self.pieces.partition_point(|piece| piece.start <= value);
+        &self.pieces[idx - 1]
+    }
+
+    pub fn apply(&self, value: u64) -> u64 {
+        self.piece_for(value).map(value)
+    }
+
+    /// Every value that maps to `target`, in increasing order.
+    pub fn inverse(&self, target: u64) -> Vec<u64> {
+        let mut sources: Vec<u64> = self
+            .pieces
+            .iter()
+            .filter(|piece| (piece.destination..=piece.destination_last()).contains(&target))
+            .map(|piece| piece.start + (target - piece.destination))
+            .collect();
+        sources.sort_unstable();
+        sources
+    }
+
+    pub fn pieces(&self) -> &[Piece] {
+        &self.pieces
+    }
 
-        Some(self.destination_range_start + (seed - self.source_range_start))
+    /// The values where the map stops being a single shift, which is where every piece but the
+    /// first starts.
+    pub fn breakpoints(&self) -> Vec<u64> {
+        self.pieces.iter().skip(1).map(|piece| piece.start).collect()
+    }
+
+    /// The lowest value any of `start..=last` maps to, and the first input that reaches it.
+    pub fn lowest(&self, start: u64, last: u64) -> (u64, u64) {
+        let first = self.pieces.partition_point(|piece| piece.last < start);
+        self.pieces[first..]
+            .iter()
+            .take_while(|piece| piece.start <= last)
+            .map(|piece| {
+                let seed = piece.start.max(start);
+                (piece.map(seed), seed)
+            })
+            .min()
+            .unwrap()
+    }
+}
+
+/// Questions about the composed seed to location map, shared by both parts.
+#[derive(Args, Debug, Clone)]
+pub struct Queries {
+    /// List the seeds that end up at this location
+    #[clap(long, value_name = "LOCATION")]
+    inverse: Vec<u64>,
+    /// Print every piece of the composed seed to location map
+    #[clap(long)]
+    breakpoints: bool,
+}
+
+impl Queries {
+    pub fn print(&self, seed_to_location: &Piecewise) {
+        if self.breakpoints {
+            for piece in seed_to_location.pieces() {
+                let Piece { start, last, destination } = piece;
+                println!("seeds {start}..={last} -> locations {destination}..");
+            }
+        }
+        for &location in &self.inverse {
+            let seeds = seed_to_location.inverse(location);
+            let seeds: Vec<String> = seeds.iter().map(u64::to_string).collect();
+            println!("location {location} <- seeds [{}]", seeds.join(", "));
+        }
     }
 }
 
@@ -35,115 +198,287 @@ pub struct Solver5A<'a> {
 }
 
 impl<'a> Solver5A<'a> {
-
-    fn new(bytes: &'a [u8]) -> Self {
-        Self {
-            bytes,
-            read_idx: 0,
-        }
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, read_idx: 0 }
     }
 
-    fn read_next_number(&mut self) -> Option<u32> {
-        if self.read_idx >= self.bytes.len() || self.bytes[self.read_idx] == b'\n' {
-            return None;
+    pub fn read_next_number(&mut self) -> Option<u64> {
+        let start = self.read_idx;
+        while self.bytes.get(self.read_idx) == Some(&b' ') {
+            self.read_idx += 1;
         }
 
-        while self.bytes[self.read_idx] == b' ' {
+        let digits_start = self.read_idx;
+        let mut num: u64 = 0;
+        while let Some(digit) = self.bytes.get(self.read_idx).filter(|byte| byte.is_ascii_digit()) {
+            let next = num.checked_mul(10).and_then(|num| num.checked_add((digit - b'0') as u64));
+            let Some(next) = next else {
+                self.read_idx = start;
+                return None;
+            };
+            num = next;
             self.read_idx += 1;
         }
 
-        let mut num = 0;
-        while self.bytes[self.read_idx].is_ascii_digit() {
-            num *= 10;
-            num += (self.bytes[self.read_idx] - b'0') as u32;
-            self.read_idx += 1;
+        if self.read_idx == digits_start {
+            self.read_idx = start;
+            return None;
         }
 
-        while self.bytes[self.read_idx] == b' ' {
+        while self.bytes.get(self.read_idx) == Some(&b' ') {
             self.read_idx += 1;
         }
         // *read_idx += 1;
         Some(num)
     }
 
-    fn get_seeds(&mut self) -> Vec<u32> {
-        // Skip past "seeds:" text
-        self.read_idx = 6;
+    pub fn error(&self, msg: &str) -> ParseError {
+        ParseError::new(format!("{msg} at byte {}", self.read_idx))
+    }
+
+    fn get_seeds(&mut self) -> Result<Vec<u64>, ParseError> {
         let mut seeds = Vec::new();
         while let Some(seed) = self.read_next_number() {
             seeds.push(seed);
         }
-        seeds
+        Ok(seeds)
+    }
+
+    /// Step past the newline at the end of a line, which the last line may leave out.
+    fn end_line(&mut self) -> Result<(), ParseError> {
+        match self.bytes.get(self.read_idx) {
+            Some(b'\n') => {
+                self.read_idx += 1;
+                Ok(())
+            }
+            None => Ok(()),
+            Some(_) => Err(self.error("expected the end of the line")),
+        }
     }
 
     fn move_to_next_line(&mut self) {
-        while self.bytes[self.read_idx] != b'\n' {
+        while self.read_idx < self.bytes.len() && self.bytes[self.read_idx] != b'\n' {
             self.read_idx += 1;
         }
-        self.read_idx += 1;
+        self.read_idx = (self.read_idx + 1).min(self.bytes.len());
     }
 
-    fn get_next_map_range(&mut self) -> Option<MapRange> {
+    fn get_next_map_range(&mut self) -> Result<Option<MapRange>, ParseError> {
+        let Some(destination_range_start) = self.read_next_number() else {
+            return Ok(None);
+        };
+        let mut next_number =
+            || self.read_next_number().ok_or_else(|| self.error("expected a number"));
         let map_range = MapRange {
-            destination_range_start: self.read_next_number()?,
-            source_range_start: self.read_next_number().unwrap(),
-            range_length: self.read_next_number().unwrap(),
+            destination_range_start,
+            source_range_start: next_number()?,
+            range_length: next_number()?,
         };
 
-        self.move_to_next_line();
+        let limit = u64::MAX as u128 + 1;
+        let length = map_range.range_length as u128;
+        if map_range.source_range_start as u128 + length > limit
+            || map_range.destination_range_start as u128 + length > limit
+        {
+            return Err(self.error("map range runs past u64::MAX"));
+        }
+
+        self.end_line()?;
 
-        Some(map_range)
+        Ok(Some(map_range))
     }
 
-    fn solve(&mut self) -> u32 {
-        // Get seeds
-        let mut seeds: Vec<u32> = self.get_seeds();
-        let mut mapped_seeds: Vec<u32> = Vec::new();
-        // println!("seeds({}): {:?}", seeds.len(), seeds);
-        while self.read_idx < self.bytes.len() - 1 {
-            // Move read idx to beginning of the next map
-            self.read_idx += 1;
+    fn get_maps(&mut self) -> Result<Vec<Vec<MapRange>>, ParseError> {
+        let mut maps = Vec::new();
+        while self.read_idx < self.bytes.len() {
+            // Each map is a blank line, a "x-to-y map:" header and then its ranges
+            self.end_line()?;
+            let header_start = self.read_idx;
             self.move_to_next_line();
-            self.move_to_next_line();
-    
-            // Map seeds according to map
-            while let Some(map_range) = self.get_next_map_range() {
-                // dbg!(map_range);
-
-                seeds.retain(|seed| {
-                    let mapped = map_range.map(*seed);
-                    if let Some(mapped) = mapped {
-                        mapped_seeds.push(mapped);
-                        return false;
-                    }
-                    true
-                });
-
-                // if seeds.is_empty() {
-                //     break;
-                // }
+            if !self.bytes[header_start..self.read_idx].ends_with(b" map:\n") {
+                let msg = format!("expected a map header at byte {header_start}");
+                return Err(ParseError::new(msg));
             }
 
-            // Move all values back into seeds to be mapped again
-            seeds.append(&mut mapped_seeds);
+            let mut map = Vec::new();
+            while let Some(map_range) = self.get_next_map_range()? {
+                map.push(map_range);
+            }
+            maps.push(map);
+        }
+        Ok(maps)
+    }
 
-            // println!("-----------------------------------");
+    /// Parse the almanac, with `read_seeds` reading what follows "seeds:" so each part can decide
+    /// what the seed numbers mean.
+    pub fn parse_with<S>(
+        &mut self,
+        read_seeds: impl FnOnce(&mut Self) -> Result<S, ParseError>,
+    ) -> Result<(S, Vec<Vec<MapRange>>), ParseError> {
+        if !self.bytes.starts_with(b"seeds:") {
+            return Err(self.error("expected \"seeds:\""));
         }
+        // Skip past "seeds:" text
+        self.read_idx = 6;
+        let seeds = read_seeds(self)?;
+        self.end_line()?;
+        Ok((seeds, self.get_maps()?))
+    }
 
-        // println!("seeds({}): {:?}", seeds.len(), seeds);
+    fn parse(&mut self) -> Result<(Vec<u64>, Vec<Vec<MapRange>>), ParseError> {
+        self.parse_with(Self::get_seeds)
+    }
 
-        *seeds.iter().min().unwrap()
+    fn solve(&mut self) -> Result<u64, ParseError> {
+        let (seeds, maps) = self.parse()?;
+        lowest_location(&seeds, &Piecewise::from_maps(&maps))
     }
 }
 
+fn lowest_location(seeds: &[u64], seed_to_location: &Piecewise) -> Result<u64, ParseError> {
+    seeds
+        .iter()
+        .map(|&seed| seed_to_location.apply(seed))
+        .min()
+        .ok_or_else(|| ParseError::new(String::from("no seeds")))
+}
+
 impl CommandImpl for Day5a {
     fn main(&mut self) -> Result<(), DynError> {
-        let bytes = slurp_bytes(self.input.as_path()).unwrap();
-        let answer = Solver5A::new(&bytes[..]).solve();
-        println!("5A: {answer}");
+        let queries = self.queries.clone();
+        self.input.for_each(|bytes| {
+            let (seeds, maps) = Solver5A::new(&bytes[..]).parse()?;
+            let seed_to_location = Piecewise::from_maps(&maps);
+            queries.print(&seed_to_location);
+            let answer = lowest_location(&seeds, &seed_to_location)?;
+            println!("5A: {answer}");
+            // 224439347 is too low
+            // 289863851
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4
+";
+
+    fn solve(input: &[u8]) -> u64 {
+        Solver5A::new(input).solve().unwrap()
+    }
+
+    fn parse(input: &[u8]) -> Result<(Vec<u64>, Vec<Vec<MapRange>>), ParseError> {
+        Solver5A::new(input).parse()
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 35;
+            input real: "day5.txt" => 289863851;
+        }
+    }
+
+    /// Map a seed one almanac map at a time.
+    fn map_stepwise(seed: u64, maps: &[Vec<MapRange>]) -> u64 {
+        maps.iter().fold(seed, |value, map| {
+            map.iter().find_map(|range| range.map(value)).unwrap_or(value)
+        })
+    }
+
+    #[test]
+    fn composed_map() {
+        let (_, maps) = parse(EXAMPLE.as_bytes()).unwrap();
+        let seed_to_location = Piecewise::from_maps(&maps);
+        for seed in 0..=120 {
+            assert_eq!(seed_to_location.apply(seed), map_stepwise(seed, &maps));
+        }
+        assert_eq!(seed_to_location.inverse(46), [82]);
+        assert_eq!(seed_to_location.inverse(35), [13]);
+        let breakpoints = seed_to_location.breakpoints();
+        assert!(breakpoints.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(breakpoints.contains(&98) && breakpoints.contains(&100));
+    }
+
+    #[test]
+    fn values_past_u32() {
+        let input = "\
+seeds: 4294967296 18446744073709551615
+
+a-to-b map:
+10 4294967296 1
+0 18446744073709551610 6
+";
+        assert_eq!(solve(input.as_bytes()), 5);
+        let (_, maps) = parse(input.as_bytes()).unwrap();
+        let seed_to_location = Piecewise::from_maps(&maps);
+        assert_eq!(seed_to_location.apply(u64::MAX), 5);
+        assert_eq!(seed_to_location.inverse(10), [10, 4294967296]);
+        assert!(parse(b"seeds: 1\n\na-to-b map:\n0 18446744073709551615 2\n").is_err());
+    }
+
+    fn map_range() -> impl Strategy<Value = MapRange> {
+        (0..200u64, 0..200u64, 0..50u64).prop_map(
+            |(destination_range_start, source_range_start, range_length)| MapRange {
+                destination_range_start,
+                source_range_start,
+                range_length,
+            },
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn composed_map_matches_stepwise(
+            maps in prop::collection::vec(prop::collection::vec(map_range(), 0..5), 0..5),
+            seed in 0..300u64,
+        ) {
+            let seed_to_location = Piecewise::from_maps(&maps);
+            let location = seed_to_location.apply(seed);
+            prop_assert_eq!(location, map_stepwise(seed, &maps));
+            prop_assert!(seed_to_location.inverse(location).contains(&seed));
+        }
+    }
 
-        // 224439347 is too low
-        // 289863851
-        Ok(())
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
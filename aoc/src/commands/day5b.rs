@@ -1,218 +1,165 @@
-use std::path::PathBuf;
-
 use clap::Parser;
 
-use crate::utils::slurp_bytes;
+use crate::utils::{Inputs, ParseError};
 
-use super::{CommandImpl, DynError};
+use super::{
+    day5a::{MapRange, Piecewise, Queries, Solver5A},
+    CommandImpl, DynError,
+};
 
 #[derive(Parser, Debug)]
 pub struct Day5b {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    #[clap(flatten)]
+    queries: Queries,
 }
 
-#[derive(Debug, Clone)]
+/// Seeds `start..=end`.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Range {
-    pub start: u32,
-    pub end: u32,
+    pub start: u64,
+    pub end: u64,
 }
 
-#[derive(Debug)]
-pub struct MapRange {
-    pub destination_range_start: u32,
-    pub source_range_start: u32,
-    pub range_length: u32,
+/// The lowest location any seed reaches, with the first seed that reaches it and its range.
+#[derive(Debug, PartialEq)]
+pub struct Lowest {
+    pub location: u64,
+    pub seed: u64,
+    pub range: Range,
 }
 
-#[derive(Debug)]
-pub struct MappedRangeExtraction {
-    pub mapped_range: Range,
-    pub unmapped_ranges: Vec<Range>,
+pub struct Solver5B<'a> {
+    almanac: Solver5A<'a>,
 }
 
-impl MapRange {
-    fn map(&self, seed: u32) -> Option<u32> {
-        if seed < self.source_range_start || seed >= self.source_range_start + self.range_length {
-            return None;
-        }
-
-        Some(self.destination_range_start + (seed - self.source_range_start))
+impl<'a> Solver5B<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { almanac: Solver5A::new(bytes) }
     }
 
-    fn overlaps(&self, range: &Range) -> bool {
-        // If range begins after end of other range
-        if range.start > self.source_range_start + self.range_length {
-            return false;
-        }
-        // If range ends before start of other range
-        if range.end < self.source_range_start {
-            return false;
+    /// Read the seed line as pairs of a first seed and a length.
+    fn get_seed_ranges(almanac: &mut Solver5A) -> Result<Vec<Range>, ParseError> {
+        let mut ranges = Vec::new();
+        while let Some(start) = almanac.read_next_number() {
+            let length = almanac
+                .read_next_number()
+                .ok_or_else(|| almanac.error("expected a seed range length"))?;
+            if length == 0 || start as u128 + length as u128 > u64::MAX as u128 + 1 {
+                return Err(almanac.error("seed range is empty or runs past u64::MAX"));
+            }
+            ranges.push(Range { start, end: start + (length - 1) });
         }
-        // if range.start > self.source_range_start && range.start < self.source_range_start + self.range_length {
-        //     return true;
-        // }
-        // if range.end > self.source_range_start && range.end < self.source_range_start + self.range_length {
-        //     return true;
-        // }
-        true
+        Ok(ranges)
     }
 
-    fn extract_mapped_range(&self, range: &Range) -> Option<MappedRangeExtraction> {
-        // println!("Checking range: {:?} against {} {}", range, self.source_range_start, self.source_range_start + self.range_length - 1);
-        if !self.overlaps(range) {
-            // println!("Does not overlap");
-            return None;
-        }
-
-        let mut unmapped_ranges = Vec::new();
-
-        let mut contained_start = range.start;
-        let mut contained_end = range.end;
-
-        if range.start < self.source_range_start {
-            unmapped_ranges.push(Range { start: range.start, end: self.source_range_start - 1 });
-            contained_start = self.source_range_start;
-        }
-        if range.end > self.source_range_start + self.range_length {
-            unmapped_ranges.push(Range { start: self.source_range_start + self.range_length, end: range.end});
-            contained_end = self.source_range_start + self.range_length - 1;
-        }
-
-        // println!("contained_start: {}, contained_end: {}", contained_start, contained_end);
-
-        let mapped_range = Range {
-            start: self.map(contained_start).unwrap(),
-            end: self.map(contained_end).unwrap(),
-        };
-
-        // println!("mapped_range: {:?}\nunmapped_ranges: {:?}", mapped_range, unmapped_ranges);
+    fn parse(&mut self) -> Result<(Vec<Range>, Vec<Vec<MapRange>>), ParseError> {
+        self.almanac.parse_with(Self::get_seed_ranges)
+    }
 
-        Some(MappedRangeExtraction { mapped_range, unmapped_ranges})
+    fn solve(&mut self) -> Result<u64, ParseError> {
+        let (seed_ranges, maps) = self.parse()?;
+        Ok(lowest_location(&seed_ranges, &Piecewise::from_maps(&maps))?.location)
     }
 }
 
-pub struct Solver5B<'a> {
-    bytes: &'a [u8],
-    read_idx: usize,
+/// Check each seed range against the pieces of the composed map it overlaps, since a piece's
+/// lowest location is always at its first seed.
+fn lowest_location(
+    seed_ranges: &[Range],
+    seed_to_location: &Piecewise,
+) -> Result<Lowest, ParseError> {
+    seed_ranges
+        .iter()
+        .map(|range| {
+            let (location, seed) = seed_to_location.lowest(range.start, range.end);
+            Lowest { location, seed, range: range.clone() }
+        })
+        .min_by_key(|lowest| (lowest.location, lowest.seed))
+        .ok_or_else(|| ParseError::new(String::from("no seeds")))
 }
 
-impl<'a> Solver5B<'a> {
-
-    fn new(bytes: &'a [u8]) -> Self {
-        Self {
-            bytes,
-            read_idx: 0,
-        }
+impl CommandImpl for Day5b {
+    fn main(&mut self) -> Result<(), DynError> {
+        let queries = self.queries.clone();
+        self.input.for_each(|bytes| {
+            let (seed_ranges, maps) = Solver5B::new(&bytes[..]).parse()?;
+            let seed_to_location = Piecewise::from_maps(&maps);
+            queries.print(&seed_to_location);
+            let Lowest { location, seed, range } =
+                lowest_location(&seed_ranges, &seed_to_location)?;
+            println!("5B: {location}");
+            println!("from seed {seed} in the range {}..={}", range.start, range.end);
+            Ok(())
+        })
     }
+}
 
-    fn read_next_number(&mut self) -> Option<u32> {
-        if self.read_idx >= self.bytes.len() || self.bytes[self.read_idx] == b'\n' {
-            return None;
-        }
-
-        while self.bytes[self.read_idx] == b' ' {
-            self.read_idx += 1;
-        }
-
-        let mut num = 0;
-        while self.bytes[self.read_idx].is_ascii_digit() {
-            num *= 10;
-            num += (self.bytes[self.read_idx] - b'0') as u32;
-            self.read_idx += 1;
-        }
-
-        while self.bytes[self.read_idx] == b' ' {
-            self.read_idx += 1;
-        }
-        // *read_idx += 1;
-        Some(num)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4
+";
+
+    fn solve(input: &[u8]) -> u64 {
+        Solver5B::new(input).solve().unwrap()
     }
 
-    fn get_seed_ranges(&mut self) -> Vec<Range> {
-        // Skip past "seeds:" text
-        self.read_idx = 6;
-        let mut ranges = Vec::new();
-        loop {
-            let start = self.read_next_number();
-            if start.is_none() {
-                break;
-            }
-            let start = start.unwrap();
-            let length = self.read_next_number().unwrap();
-            ranges.push(Range { start, end: start + length - 1 });
-            if self.bytes[self.read_idx] == b'\n' {
-                break;
-            }
-        }
-        ranges
+    fn parse(input: &[u8]) -> Result<(Vec<Range>, Vec<Vec<MapRange>>), ParseError> {
+        Solver5B::new(input).parse()
     }
 
-    fn move_to_next_line(&mut self) {
-        while self.bytes[self.read_idx] != b'\n' {
-            self.read_idx += 1;
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 46;
+            input real: "day5.txt" => 60568880;
         }
-        self.read_idx += 1;
     }
 
-    fn get_next_map_range(&mut self) -> Option<MapRange> {
-        let map_range = MapRange {
-            destination_range_start: self.read_next_number()?,
-            source_range_start: self.read_next_number().unwrap(),
-            range_length: self.read_next_number().unwrap(),
-        };
-
-        self.move_to_next_line();
-
-        Some(map_range)
-    }
-
-    fn solve(&mut self) -> u32 {
-        // Get seeds
-        let mut unmapped_ranges = self.get_seed_ranges();
-        self.read_idx += 1;
-
-        // println!("seeds({}): {:?}", seeds.len(), seeds);
-        while self.read_idx < self.bytes.len() - 1 {
-            // Move read idx to beginning of the next map
-            self.move_to_next_line();
-            self.move_to_next_line();
-
-            let mut new_unmapped_ranges = Vec::new();
-            let mut new_mapped_ranges = Vec::new();
-
-            // Map seeds according to map
-            while let Some(map_range) = self.get_next_map_range() {
-                // println!("**found map_range: {:?}", map_range);
-                for range in unmapped_ranges.clone().iter() {
-                    if let Some(mapped_range_extraction) = map_range.extract_mapped_range(range) {
-                        // println!("mapped_range_extraction for {:?}: {:?}", range, mapped_range_extraction);
-                        new_mapped_ranges.push(mapped_range_extraction.mapped_range);
-                        new_unmapped_ranges.extend(mapped_range_extraction.unmapped_ranges);
-                    } else {
-                        new_unmapped_ranges.push(range.clone());
-                    }
-                }
-
-                unmapped_ranges = new_unmapped_ranges.clone();
-                new_unmapped_ranges.clear();
-            }
-
-            unmapped_ranges.extend(new_mapped_ranges);
-            // println!("unmapped_ranges({}): {:?}", unmapped_ranges.len(), unmapped_ranges);
-            // println!("-----------------------------------");
-        }
-
-        unmapped_ranges.iter().map(|range| range.start).min().unwrap()
+    #[test]
+    fn lowest_seed() {
+        let (seed_ranges, maps) = parse(EXAMPLE.as_bytes()).unwrap();
+        let lowest = lowest_location(&seed_ranges, &Piecewise::from_maps(&maps)).unwrap();
+        assert_eq!(lowest, Lowest { location: 46, seed: 82, range: Range { start: 79, end: 92 } });
     }
-}
-
-impl CommandImpl for Day5b {
-    fn main(&mut self) -> Result<(), DynError> {
-        let bytes = slurp_bytes(self.input.as_path()).unwrap();
-        let answer = Solver5B::new(&bytes[..]).solve();
-        println!("5B: {answer}");
 
-        Ok(())
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
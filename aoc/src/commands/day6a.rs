@@ -1,113 +1,387 @@
-use std::path::PathBuf;
-
 use clap::Parser;
 
-use crate::utils::slurp_bytes;
+use crate::utils::{Inputs, ParseError};
 
 use super::{CommandImpl, DynError};
 
 #[derive(Parser, Debug)]
 pub struct Day6a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    /// Check the closed form against brute force on races of up to 100 million ms
+    #[clap(long)]
+    verify: bool,
 }
 
 pub struct Solver {
     bytes: Vec<u8>,
     read_idx: usize,
+    verify: bool,
+    /// Read each line as one number whose digits are split up by spaces, as part two does
+    kerned: bool,
 }
 
-#[derive(Debug)]
+/// A race, read as `u128` so times and records past `u64` still work.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Race {
-    pub time: u32,
-    pub distance: u32,
+    pub time: u128,
+    pub distance: u128,
 }
 
+/// Brute force is only used to check races with at most this many hold times.
+pub const VERIFY_LIMIT: u128 = 100_000_000;
+
 impl Race {
-    pub fn ways_to_win(&self) -> u32 {
-        let mut ways_to_win = 0;
-
-        for hold_time in 1..=self.time {
-            let distance_covered = hold_time * (self.time - hold_time);
-            if distance_covered > self.distance {
-                ways_to_win += 1;
-            } else if ways_to_win > 1 {
-                break;
-            }
+    /// Holding the button for `half - k` ms of a race of `2 * half + odd` ms travels
+    /// `half * (half + odd) - k * (k + odd)`, so it beats the record exactly when
+    /// `k * (k + odd)` is below the margin between the best distance and the record. The largest
+    /// such `k` is the integer square root of one less than the margin, stepped back by one when
+    /// `odd` pushes it onto the margin. The products are taken at 256 bits so any `u128` race
+    /// works.
+    pub fn ways_to_win(&self) -> u128 {
+        let (half, odd) = (self.time / 2, self.time % 2);
+        let best = widening_mul(half, half + odd);
+        if best <= (0, self.distance) {
+            return 0;
         }
+        let margin = wide_sub(best, (0, self.distance));
 
-        ways_to_win
+        let mut reach = isqrt(wide_sub(margin, (0, 1)));
+        if odd == 1 && widening_mul(reach, reach + 1) >= margin {
+            reach -= 1;
+        }
+        // Every k in -reach..=reach wins, and odd races win once more on the far side
+        2 * reach + 1 + odd
     }
-}
 
-impl Solver {
-    fn new(bytes: Vec<u8>) -> Self {
-        Self {
-            bytes,
-            read_idx: 0,
+    /// Try every hold time, for checking `ways_to_win` on small races.
+    pub fn ways_to_win_brute(&self) -> u128 {
+        (0..=self.time)
+            .filter(|hold| hold.checked_mul(self.time - hold).map_or(true, |d| d > self.distance))
+            .count() as u128
+    }
+
+    /// Compare the closed form with brute force when the race is small enough to try.
+    pub fn verify(&self) -> Result<bool, ParseError> {
+        if self.time > VERIFY_LIMIT {
+            return Ok(false);
         }
+        let (closed, brute) = (self.ways_to_win(), self.ways_to_win_brute());
+        if closed != brute {
+            let Race { time, distance } = self;
+            let msg = format!(
+                "race {time} ms / {distance} mm: closed form {closed}, brute force {brute}"
+            );
+            return Err(ParseError::new(msg));
+        }
+        Ok(true)
     }
+}
 
-    pub fn read_races(&mut self) -> Vec<Race> {
-        let mut times: Vec<u32> = Vec::new();
-        let mut distances: Vec<u32> = Vec::new();
+/// A 256-bit number as its `(high, low)` halves. Tuples compare the high half first, so they
+/// order the same way as the numbers they hold.
+type Wide = (u128, u128);
 
-        // Skip past "Time:" text
-        self.read_idx = 5;
-        while let Some(time) = self.read_next_number() {
-            times.push(time);
+/// The full product of two `u128`s, built from their 64-bit halves.
+fn widening_mul(a: u128, b: u128) -> Wide {
+    let (a_high, a_low) = (a >> 64, a as u64 as u128);
+    let (b_high, b_low) = (b >> 64, b as u64 as u128);
+    let (middle, middle_carry) = (a_low * b_high).overflowing_add(a_high * b_low);
+    let (low, carry) = (a_low * b_low).overflowing_add(middle << 64);
+    let high = a_high * b_high + (middle >> 64) + ((middle_carry as u128) << 64) + carry as u128;
+    (high, low)
+}
+
+fn wide_add(a: Wide, b: Wide) -> Wide {
+    let (low, carry) = a.1.overflowing_add(b.1);
+    (a.0 + b.0 + carry as u128, low)
+}
+
+fn wide_sub(a: Wide, b: Wide) -> Wide {
+    let (low, borrow) = a.1.overflowing_sub(b.1);
+    (a.0 - b.0 - borrow as u128, low)
+}
+
+fn wide_shr(a: Wide, bits: u32) -> Wide {
+    (a.0 >> bits, a.1 >> bits | a.0 << (128 - bits))
+}
+
+/// The floor of the square root of `n`, worked out one bit of the root at a time.
+fn isqrt(n: Wide) -> u128 {
+    let top = match n {
+        (0, 0) => return 0,
+        (0, low) => 127 - low.leading_zeros(),
+        (high, _) => 255 - high.leading_zeros(),
+    };
+    // Start from the highest power of four at or below n
+    let shift = top & !1;
+    let mut bit = if shift >= 128 { (1 << (shift - 128), 0) } else { (0, 1 << shift) };
+    let (mut rest, mut root) = (n, (0, 0));
+    while bit != (0, 0) {
+        let trial = wide_add(root, bit);
+        if rest >= trial {
+            rest = wide_sub(rest, trial);
+            root = wide_add(wide_shr(root, 1), bit);
+        } else {
+            root = wide_shr(root, 1);
         }
+        bit = wide_shr(bit, 2);
+    }
+    root.1
+}
 
-        // Skip past "Distance:" text
-        self.read_idx += 10;
-        while let Some(distance) = self.read_next_number() {
-            distances.push(distance);
+/// Check the races with `--verify` and print how many were small enough to brute force.
+pub fn verify_races(races: &[Race]) -> Result<(), ParseError> {
+    let mut checked = 0;
+    for race in races {
+        checked += race.verify()? as usize;
+    }
+    println!("verified {checked} of {} races against brute force", races.len());
+    Ok(())
+}
+
+impl Solver {
+    pub fn new(bytes: Vec<u8>, verify: bool) -> Self {
+        Self { bytes, read_idx: 0, verify, kerned: false }
+    }
+
+    /// Read the races the way part two does, as one long race.
+    pub fn kerned(self) -> Self {
+        Self { kerned: true, ..self }
+    }
+
+    pub fn read_races(&mut self) -> Result<Vec<Race>, ParseError> {
+        let times = self.read_line(b"Time:")?;
+        let distances = self.read_line(b"Distance:")?;
+        if times.len() != distances.len() {
+            return Err(self.error("expected as many distances as times"));
         }
 
-        times
+        Ok(times
             .iter()
             .zip(distances.iter())
             .map(|f| Race { time: *f.0, distance: *f.1 })
-            .collect()
+            .collect())
     }
 
-    fn read_next_number(&mut self) -> Option<u32> {
-        if self.read_idx >= self.bytes.len() || self.bytes[self.read_idx] == b'\n' {
-            return None;
+    fn read_line(&mut self, label: &[u8]) -> Result<Vec<u128>, ParseError> {
+        self.expect(label)?;
+        let mut numbers = Vec::new();
+        while let Some(number) = self.read_next_number() {
+            numbers.push(number);
+        }
+        if self.kerned && numbers.is_empty() {
+            return Err(self.error("expected a number"));
+        }
+        self.end_line()?;
+        Ok(numbers)
+    }
+
+    fn error(&self, msg: &str) -> ParseError {
+        ParseError::new(format!("{msg} at byte {}", self.read_idx))
+    }
+
+    fn expect(&mut self, label: &[u8]) -> Result<(), ParseError> {
+        if !self.bytes[self.read_idx..].starts_with(label) {
+            let label = String::from_utf8_lossy(label);
+            return Err(self.error(&format!("expected \"{label}\"")));
+        }
+        self.read_idx += label.len();
+        Ok(())
+    }
+
+    /// Step past the newline at the end of a line, which the last line may leave out.
+    fn end_line(&mut self) -> Result<(), ParseError> {
+        match self.bytes.get(self.read_idx) {
+            Some(b'\n') => {
+                self.read_idx += 1;
+                Ok(())
+            }
+            None => Ok(()),
+            Some(_) => Err(self.error("expected the end of the line")),
         }
+    }
 
-        while self.bytes[self.read_idx] == b' ' {
+    /// Read one number, whose digits may be split up by spaces when the solver is kerned.
+    fn read_next_number(&mut self) -> Option<u128> {
+        let start = self.read_idx;
+        while self.bytes.get(self.read_idx) == Some(&b' ') {
             self.read_idx += 1;
         }
 
-        let mut num = 0;
-        while self.bytes[self.read_idx].is_ascii_digit() {
-            num *= 10;
-            num += (self.bytes[self.read_idx] - b'0') as u32;
+        let mut digits = 0;
+        let mut num: u128 = 0;
+        loop {
+            match self.bytes.get(self.read_idx) {
+                Some(b' ') if self.kerned => {}
+                Some(&digit) if digit.is_ascii_digit() => {
+                    let next =
+                        num.checked_mul(10).and_then(|num| num.checked_add((digit - b'0') as u128));
+                    let Some(next) = next else {
+                        self.read_idx = start;
+                        return None;
+                    };
+                    num = next;
+                    digits += 1;
+                }
+                _ => break,
+            }
             self.read_idx += 1;
         }
 
-        while self.bytes[self.read_idx] == b' ' {
+        if digits == 0 {
+            self.read_idx = start;
+            return None;
+        }
+
+        while self.bytes.get(self.read_idx) == Some(&b' ') {
             self.read_idx += 1;
         }
-        // *read_idx += 1;
         Some(num)
     }
 
-    pub fn solve(&mut self) -> u32 {
-        self.read_races()
-            .iter()
-            .map(|r| r.ways_to_win())
-            .product::<u32>()
+    pub fn solve(&mut self) -> Result<u128, ParseError> {
+        let races = self.read_races()?;
+        if self.verify {
+            verify_races(&races)?;
+        }
+        races.iter().try_fold(1u128, |product, race| {
+            product
+                .checked_mul(race.ways_to_win())
+                .ok_or_else(|| ParseError::new(String::from("ways to win overflow a u128")))
+        })
     }
 }
 
 impl CommandImpl for Day6a {
     fn main(&mut self) -> Result<(), DynError> {
-        let bytes = slurp_bytes(self.input.as_path()).unwrap();
-        let answer = Solver::new(bytes).solve();
-        println!("6A: {answer}");
+        let verify = self.verify;
+        self.input.for_each(|bytes| {
+            let answer = Solver::new(bytes, verify).solve()?;
+            println!("6A: {answer}");
+            Ok(())
+        })
+    }
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+Time:      7  15   30
+Distance:  9  40  200
+";
+
+    fn solve(input: &[u8]) -> u128 {
+        Solver::new(input.to_vec(), false).solve().unwrap()
+    }
+
+    fn parse(input: &[u8]) -> Result<Vec<Race>, ParseError> {
+        Solver::new(input.to_vec(), false).read_races()
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 288;
+            input real: "day6.txt" => 1710720;
+        }
+    }
+
+    #[test]
+    fn whole_roots() {
+        // The record is tied, not beaten, at the roots 10 and 20
+        assert_eq!(Race { time: 30, distance: 200 }.ways_to_win(), 9);
+        assert_eq!(Race { time: 4, distance: 4 }.ways_to_win(), 0);
+        assert_eq!(Race { time: 4, distance: 3 }.ways_to_win(), 1);
+        assert_eq!(Race { time: 0, distance: 0 }.ways_to_win(), 0);
+    }
+
+    #[test]
+    fn past_u64() {
+        let time = u64::MAX as u128;
+        let best = (time * time - 1) / 4;
+        assert_eq!(Race { time, distance: 0 }.ways_to_win(), time - 1);
+        assert_eq!(Race { time, distance: best - 1 }.ways_to_win(), 2);
+        assert_eq!(Race { time, distance: best }.ways_to_win(), 0);
+        assert_eq!(Race { time: time + 1, distance: 0 }.ways_to_win(), time);
+        let input = format!("Time: {}\nDistance: 0\n", time + 1);
+        assert_eq!(solve(input.as_bytes()), time);
+        let input = format!("Time: {time}\nDistance: {}\n", best - 1);
+        assert_eq!(solve(input.as_bytes()), 2);
+    }
+
+    #[test]
+    fn past_u128_distances() {
+        // Holding for half of the longest race travels further than a u128 can hold
+        let time = u128::MAX;
+        assert_eq!(Race { time, distance: u128::MAX }.ways_to_win(), time - 3);
+        let half = u64::MAX as u128;
+        let time = 2 * half;
+        let best = half * half;
+        assert_eq!(Race { time, distance: best - 1 }.ways_to_win(), 1);
+        assert_eq!(Race { time, distance: best }.ways_to_win(), 0);
+    }
+
+    #[test]
+    fn square_roots() {
+        assert_eq!(isqrt((0, 0)), 0);
+        assert_eq!(isqrt((0, 99)), 9);
+        assert_eq!(isqrt((0, 100)), 10);
+        assert_eq!(isqrt((0, u128::MAX)), u64::MAX as u128);
+        assert_eq!(isqrt(widening_mul(u128::MAX, u128::MAX)), u128::MAX);
+        let below = wide_sub(widening_mul(u128::MAX, u128::MAX), (0, 1));
+        assert_eq!(isqrt(below), u128::MAX - 1);
+        assert_eq!(widening_mul(1 << 64, 1 << 64), (1, 0));
+    }
+
+    /// Binary search the first half of the race for the shortest winning hold.
+    fn ways_by_search(race: &Race) -> u128 {
+        let wins = |hold: u128| {
+            hold.checked_mul(race.time - hold).map_or(true, |distance| distance > race.distance)
+        };
+        let (mut low, mut high) = (0, race.time / 2);
+        if !wins(high) {
+            return 0;
+        }
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if wins(mid) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        race.time - 2 * low + 1
+    }
+
+    proptest! {
+        #[test]
+        fn closed_form_matches_search(time in any::<u128>(), distance in any::<u128>()) {
+            let race = Race { time, distance };
+            prop_assert_eq!(race.ways_to_win(), ways_by_search(&race));
+        }
+
+        #[test]
+        fn matches_search_near_the_record(time in 0..1u128 << 65, below in 0..1000u128) {
+            // Below 2^65 ms the best distance still fits in a u128
+            let half = time / 2;
+            let distance = (half * (time - half)).saturating_sub(below);
+            let race = Race { time, distance };
+            prop_assert_eq!(race.ways_to_win(), ways_by_search(&race));
+        }
+
+        #[test]
+        fn closed_form_matches_brute_force(time in 0..2000u128, distance in 0..1_000_000u128) {
+            let race = Race { time, distance };
+            prop_assert_eq!(race.ways_to_win(), race.ways_to_win_brute());
+        }
+    }
+
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
@@ -1,221 +1,352 @@
-use std::path::PathBuf;
+use std::fmt;
 
-use clap::Parser;
+use clap::{Args, Parser};
 
-use crate::utils::slurp_bytes;
+use crate::utils::{Inputs, ParseError};
 
 use super::{CommandImpl, DynError};
 
 #[derive(Parser, Debug)]
 pub struct Day7a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    #[clap(flatten)]
+    rules: RuleOptions,
 }
 
-pub struct Solver {
-    bytes: Vec<u8>,
-    read_idx: usize,
+/// Flags that change the rules of the game. Each day fills in the ones left out.
+#[derive(Args, Debug, Clone)]
+pub struct RuleOptions {
+    /// Every card, from weakest to strongest
+    #[clap(long)]
+    ranking: Option<String>,
+    /// Cards that stand in for whichever card makes the best hand
+    #[clap(long)]
+    wildcards: Option<String>,
+    /// Number of cards in a hand
+    #[clap(long)]
+    hand_size: Option<usize>,
+    /// Print every hand with its rank and what kind of hand it is
+    #[clap(long)]
+    pub report: bool,
 }
 
-#[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum HandKind {
-    FiveOfAKind = 6,
-    FourOfAKind = 5,
-    FullHouse = 4,
-    ThreeOfAKind = 3,
-    TwoPair = 2,
-    OnePair = 1,
-    HighCard = 0
+impl RuleOptions {
+    pub fn rules(&self, ranking: &str, wildcards: &str) -> Result<Rules, ParseError> {
+        Rules::new(
+            self.ranking.as_deref().unwrap_or(ranking).as_bytes(),
+            self.wildcards.as_deref().unwrap_or(wildcards).as_bytes(),
+            self.hand_size.unwrap_or(5),
+        )
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct Hand {
-    kind: HandKind,
-    cards: [u8; 5],
-    bet: u32,
+/// How cards rank against each other, which of them are wild, and how many make a hand.
+#[derive(Debug, Clone)]
+pub struct Rules {
+    strength: [Option<u8>; 256],
+    wild: [bool; 256],
+    hand_size: usize,
 }
 
-impl std::cmp::Ord for Hand {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        if self.kind == other.kind {
-            for (card1, card2) in self.cards.iter().zip(other.cards.iter()) {
-                if card1 != card2 {
-                    if card1 == &b'A' {
-                        return std::cmp::Ordering::Greater;
-                    } else if card2 == &b'A' {
-                        return std::cmp::Ordering::Less;
-                    }
-
-                    if card1 == &b'K' {
-                        return std::cmp::Ordering::Greater;
-                    } else if card2 == &b'K' {
-                        return std::cmp::Ordering::Less;
-                    }
-
-                    if card1 == &b'Q' {
-                        return std::cmp::Ordering::Greater;
-                    } else if card2 == &b'Q' {
-                        return std::cmp::Ordering::Less;
-                    }
-
-                    if card1 == &b'J' {
-                        return std::cmp::Ordering::Greater;
-                    } else if card2 == &b'J' {
-                        return std::cmp::Ordering::Less;
-                    }
-
-                    if card1 == &b'T' {
-                        return std::cmp::Ordering::Greater;
-                    } else if card2 == &b'T' {
-                        return std::cmp::Ordering::Less;
-                    }
-
-                    return card1.cmp(card2);
-                }
+impl Rules {
+    pub fn new(ranking: &[u8], wildcards: &[u8], hand_size: usize) -> Result<Self, ParseError> {
+        if ranking.is_empty() || ranking.len() > u8::MAX as usize {
+            return Err(ParseError::new(String::from("the ranking must list 1 to 255 cards")));
+        }
+        if hand_size == 0 {
+            return Err(ParseError::new(String::from("hands must hold at least one card")));
+        }
+
+        let mut strength = [None; 256];
+        for (rank, &card) in ranking.iter().enumerate() {
+            if !card.is_ascii_graphic() || strength[card as usize].is_some() {
+                let msg = format!("card {:?} is not printable or is ranked twice", card as char);
+                return Err(ParseError::new(msg));
             }
-            unreachable!("Hands are equal")
-        } else {
-            let other_kind = other.kind as u8;
-            (self.kind as u8).cmp(&other_kind)
+            strength[card as usize] = Some(rank as u8);
         }
 
-        // let other_kind = other.kind as u8;
-        // (self.kind as u8).cmp(&other_kind)
+        let mut wild = [false; 256];
+        for &card in wildcards {
+            if strength[card as usize].is_none() {
+                let msg = format!("wildcard {:?} is not in the ranking", card as char);
+                return Err(ParseError::new(msg));
+            }
+            wild[card as usize] = true;
+        }
+
+        Ok(Self { strength, wild, hand_size })
     }
-}
 
-impl std::cmp::PartialOrd for Hand {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+    /// Group the cards that are not wild by face, then have the wildcards join the biggest
+    /// group, which always makes the strongest hand.
+    pub fn classify(&self, cards: &[u8]) -> HandKind {
+        let mut counts = [0; 256];
+        let mut wildcards = 0;
+        for &card in cards {
+            if self.wild[card as usize] {
+                wildcards += 1;
+            } else {
+                counts[card as usize] += 1;
+            }
+        }
+
+        let mut groups: Vec<usize> = counts.into_iter().filter(|&count| count > 0).collect();
+        groups.sort_unstable_by(|a, b| b.cmp(a));
+        match groups.first_mut() {
+            Some(biggest) => *biggest += wildcards,
+            None if wildcards > 0 => groups.push(wildcards),
+            None => (),
+        }
+        HandKind { groups }
     }
 }
 
-impl Solver {
-    fn new(bytes: Vec<u8>) -> Self {
-        Self {
-            bytes,
-            read_idx: 0,
-        }
-    }
-
-    fn get_hand_kind(cards: &[u8; 5]) -> HandKind {
-        let mut card_map = [0; 255];
-
-        for card in cards {
-            card_map[*card as usize] += 1;
-        }
-
-        let mut pair_found: bool = false;
-        let mut three_of_a_kind_found: bool = false;
-
-        for count in card_map.iter() {
-            match *count {
-                0 => (),
-                1 => (),
-                2 => {
-                    if pair_found {
-                        return HandKind::TwoPair;
-                    } else if three_of_a_kind_found {
-                        return HandKind::FullHouse;
-                    }
-                    pair_found = true;
-                },
-                3 => {
-                    if pair_found {
-                        return HandKind::FullHouse;
-                    } else {
-                        three_of_a_kind_found = true;
-                    }
-                },
-                4 => return HandKind::FourOfAKind,
-                5 => return HandKind::FiveOfAKind,
-                _ => unreachable!(),
+/// A hand's kind as the sizes of its groups of matching cards, biggest first. Comparing these
+/// lists orders the kinds the way the game does: full house (3, 2) beats three of a kind
+/// (3, 1, 1), which beats two pair (2, 2, 1).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HandKind {
+    groups: Vec<usize>,
+}
+
+impl fmt::Display for HandKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const COUNTS: [&str; 8] = ["", "", "two", "three", "four", "five", "six", "seven"];
+        let pairs = self.groups.iter().filter(|&&size| size == 2).count();
+        match self.groups[..] {
+            [] => write!(f, "no cards"),
+            [1, ..] => write!(f, "high card"),
+            [2, 2, ..] => write!(f, "{} pair", COUNTS.get(pairs).unwrap_or(&"many")),
+            [2, ..] => write!(f, "one pair"),
+            [3, 2, ..] => write!(f, "full house"),
+            [size, 1, ..] | [size] if size < COUNTS.len() => {
+                write!(f, "{} of a kind", COUNTS[size])
+            }
+            _ => {
+                let groups: Vec<String> = self.groups.iter().map(usize::to_string).collect();
+                write!(f, "groups of {}", groups.join("+"))
             }
         }
+    }
+}
 
-        match (pair_found, three_of_a_kind_found) {
-            (true, true) => return HandKind::FullHouse,
-            (true, false) => return HandKind::OnePair,
-            (false, true) => return HandKind::ThreeOfAKind,
-            (false, false) => (),
-        }
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hand {
+    kind: HandKind,
+    /// Each card's place in the ranking, compared in order to break ties between kinds
+    strengths: Vec<u8>,
+    cards: Vec<u8>,
+    bet: u32,
+}
 
-        HandKind::HighCard
+pub struct Solver {
+    bytes: Vec<u8>,
+    read_idx: usize,
+    rules: Rules,
+}
+
+impl Solver {
+    pub fn new(bytes: Vec<u8>, rules: Rules) -> Self {
+        Self { bytes, read_idx: 0, rules }
     }
 
-    fn read_next_hand(&mut self) -> Option<Hand> {
+    fn read_next_hand(&mut self) -> Result<Option<Hand>, ParseError> {
         if self.read_idx == self.bytes.len() {
-            return None;
+            return Ok(None);
         }
 
         // Read cards
-        let cards: [u8; 5] = self.bytes[self.read_idx..self.read_idx + 5].try_into().unwrap();
-        self.read_idx += 5;
-
-        let kind = Self::get_hand_kind(&cards);
+        let hand_size = self.rules.hand_size;
+        let cards = self
+            .bytes
+            .get(self.read_idx..self.read_idx.saturating_add(hand_size))
+            .ok_or_else(|| self.error(&format!("expected {hand_size} cards")))?
+            .to_vec();
+        let strengths = cards
+            .iter()
+            .map(|&card| self.rules.strength[card as usize])
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| self.error("unknown card"))?;
+        self.read_idx += hand_size;
+
+        let kind = self.rules.classify(&cards);
 
         // Read bet
-        let bet = self.read_next_number().unwrap();
-        self.read_idx += 1;
+        if self.bytes.get(self.read_idx) != Some(&b' ') {
+            return Err(self.error("expected a space before the bet"));
+        }
+        let bet = self.read_next_number().ok_or_else(|| self.error("expected a bet"))?;
+        self.end_line()?;
 
-        Some(Hand {
-            kind,
-            cards,
-            bet,
-        })
+        Ok(Some(Hand { kind, strengths, cards, bet }))
     }
 
     fn read_next_number(&mut self) -> Option<u32> {
-        if self.read_idx >= self.bytes.len() || self.bytes[self.read_idx] == b'\n' {
-            return None;
+        let start = self.read_idx;
+        while self.bytes.get(self.read_idx) == Some(&b' ') {
+            self.read_idx += 1;
         }
 
-        while self.bytes[self.read_idx] == b' ' {
+        let digits_start = self.read_idx;
+        let mut num: u32 = 0;
+        while let Some(digit) = self.bytes.get(self.read_idx).filter(|byte| byte.is_ascii_digit()) {
+            let next = num.checked_mul(10).and_then(|num| num.checked_add((digit - b'0') as u32));
+            let Some(next) = next else {
+                self.read_idx = start;
+                return None;
+            };
+            num = next;
             self.read_idx += 1;
         }
 
-        let mut num = 0;
-        while self.bytes[self.read_idx].is_ascii_digit() {
-            num *= 10;
-            num += (self.bytes[self.read_idx] - b'0') as u32;
-            self.read_idx += 1;
+        if self.read_idx == digits_start {
+            self.read_idx = start;
+            return None;
         }
 
-        while self.bytes[self.read_idx] == b' ' {
+        while self.bytes.get(self.read_idx) == Some(&b' ') {
             self.read_idx += 1;
         }
-        // *read_idx += 1;
         Some(num)
     }
 
-    pub fn solve(&mut self) -> u64 {
-        let mut hands = Vec::new();
+    fn error(&self, msg: &str) -> ParseError {
+        ParseError::new(format!("{msg} at byte {}", self.read_idx))
+    }
 
-        while let Some(hand) = self.read_next_hand() {
+    /// Step past the newline at the end of a line, which the last line may leave out.
+    fn end_line(&mut self) -> Result<(), ParseError> {
+        match self.bytes.get(self.read_idx) {
+            Some(b'\n') => {
+                self.read_idx += 1;
+                Ok(())
+            }
+            None => Ok(()),
+            Some(_) => Err(self.error("expected the end of the line")),
+        }
+    }
+
+    pub fn read_hands(&mut self) -> Result<Vec<Hand>, ParseError> {
+        let mut hands = Vec::new();
+        while let Some(hand) = self.read_next_hand()? {
             hands.push(hand);
         }
+        Ok(hands)
+    }
 
+    /// The hands from weakest to strongest, so a hand's rank is its index plus one.
+    pub fn ranked_hands(&mut self) -> Result<Vec<Hand>, ParseError> {
+        let mut hands = self.read_hands()?;
         hands.sort();
+        Ok(hands)
+    }
 
-        let mut winnings: u64 = 0;
+    pub fn solve(&mut self) -> Result<u64, ParseError> {
+        Ok(winnings(&self.ranked_hands()?))
+    }
+}
 
-        for (idx, hand) in hands.iter().enumerate() {
-            // println!("{:#?}", hand);
-            winnings += hand.bet as u64 * (idx + 1) as u64;
-        }
-        // five_of_a_kind.iter().sort_by(|a, b| b.bet.cmp(&a.bet));
-        // println!("{:#?}", hands);
-        winnings
+pub fn winnings(ranked_hands: &[Hand]) -> u64 {
+    ranked_hands.iter().enumerate().map(|(idx, hand)| hand.bet as u64 * (idx + 1) as u64).sum()
+}
+
+/// Print a line for each hand, strongest first.
+pub fn report(ranked_hands: &[Hand]) {
+    for (idx, hand) in ranked_hands.iter().enumerate().rev() {
+        let cards = String::from_utf8_lossy(&hand.cards);
+        println!("rank {}: {cards} bets {}, {}", idx + 1, hand.bet, hand.kind);
     }
 }
 
 impl CommandImpl for Day7a {
     fn main(&mut self) -> Result<(), DynError> {
-        let bytes = slurp_bytes(self.input.as_path()).unwrap();
-        let answer = Solver::new(bytes).solve();
-        println!("7A: {answer}");
+        let rules = self.rules.rules("23456789TJQKA", "")?;
+        let show_report = self.rules.report;
+        self.input.for_each(|bytes| {
+            let hands = Solver::new(bytes, rules.clone()).ranked_hands()?;
+            if show_report {
+                report(&hands);
+            }
+            println!("7A: {}", winnings(&hands));
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483
+";
+
+    fn rules() -> Rules {
+        Rules::new(b"23456789TJQKA", b"", 5).unwrap()
+    }
+
+    fn solve(input: &[u8]) -> u64 {
+        Solver::new(input.to_vec(), rules()).solve().unwrap()
+    }
+
+    fn parse(input: &[u8]) -> Result<Vec<Hand>, ParseError> {
+        Solver::new(input.to_vec(), rules()).read_hands()
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 6440;
+            input real: "day7.txt" => 250120186;
+        }
+    }
+
+    #[test]
+    fn classification() {
+        let kind = |cards: &[u8]| rules().classify(cards).to_string();
+        assert_eq!(kind(b"AAAAA"), "five of a kind");
+        assert_eq!(kind(b"AA8AA"), "four of a kind");
+        assert_eq!(kind(b"23332"), "full house");
+        assert_eq!(kind(b"TTT98"), "three of a kind");
+        assert_eq!(kind(b"23432"), "two pair");
+        assert_eq!(kind(b"A23A4"), "one pair");
+        assert_eq!(kind(b"23456"), "high card");
+
+        let wild = Rules::new(b"23456789TJQKA", b"J2", 7).unwrap();
+        let kind = |cards: &[u8]| wild.classify(cards).to_string();
+        assert_eq!(kind(b"JJJJJJJ"), "seven of a kind");
+        assert_eq!(kind(b"J2QQK3A"), "four of a kind");
+        assert_eq!(kind(b"J2QQKKA"), "groups of 4+2+1");
+        assert_eq!(kind(b"345TQKA"), "high card");
+        assert_eq!(kind(b"JJ2KKKA"), "six of a kind");
+        assert_eq!(kind(b"33KKAA4"), "three pair");
+        assert_eq!(kind(b"333KKK4"), "groups of 3+3+1");
+    }
+
+    #[test]
+    fn custom_rules() {
+        let rules = Rules::new(b"abc", b"c", 3).unwrap();
+        let hands = Solver::new(b"aab 1\nabc 10\nbbb 100\ncba 1000\n".to_vec(), rules)
+            .ranked_hands()
+            .unwrap();
+        let ranked: Vec<&[u8]> = hands.iter().map(|hand| &hand.cards[..]).collect();
+        // The wild c makes abc a pair, which ties aab and cba on kind and then loses on strength
+        assert_eq!(ranked, [&b"aab"[..], b"abc", b"cba", b"bbb"]);
+        assert_eq!(winnings(&hands), 1 + 20 + 3000 + 400);
+
+        assert!(Rules::new(b"", b"", 5).is_err());
+        assert!(Rules::new(b"aa", b"", 5).is_err());
+        assert!(Rules::new(b"ab", b"c", 5).is_err());
+        assert!(Rules::new(b"ab", b"", 0).is_err());
+    }
 
-        Ok(())
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
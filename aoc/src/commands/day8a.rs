@@ -1,151 +1,308 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
 
 use clap::Parser;
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::utils::{AsciiReader, Inputs, ParseError};
 
 use super::{CommandImpl, DynError};
 
-#[derive(Debug, Clone, Copy)]
-enum Instruction {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
     Left,
-    Right
+    Right,
 }
 
-struct InstructionSet {
+/// The desert map with node labels interned, so a node is an index into `names`, `left` and
+/// `right`. Labels are any run of ASCII letters and digits. A label that is only ever a
+/// destination points back at itself, and walking onto it is an error.
+#[derive(Debug)]
+pub struct Network {
     pub instructions: Vec<Instruction>,
-    pub index: usize
+    pub names: Vec<String>,
+    pub left: Vec<usize>,
+    pub right: Vec<usize>,
+    pub defined: Vec<bool>,
 }
 
-impl InstructionSet {
-    pub fn new(instructions: Vec<Instruction>) -> Self {
-        Self {
-            instructions,
-            index: 0
-        }
+impl Network {
+    pub fn node(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|node| node == name)
     }
 
-    pub fn next(&mut self) -> Instruction {
-        if self.index >= self.instructions.len() {
-            self.index = 0;
+    /// Follow the instruction for step `step` from `node`.
+    pub fn step(&self, node: usize, step: u64) -> Result<usize, ParseError> {
+        if !self.defined[node] {
+            return Err(ParseError::new(format!(
+                "walked onto undefined node {}",
+                self.names[node]
+            )));
         }
+        Ok(match self.instructions[(step % self.instructions.len() as u64) as usize] {
+            Instruction::Left => self.left[node],
+            Instruction::Right => self.right[node],
+        })
+    }
 
-        let instruction = self.instructions[self.index];
-        self.index += 1;
-        instruction
+    /// Walk from `start` until the walk repeats a node at the same point in the instructions,
+    /// noting every step that lands on an end node along the way.
+    pub fn cycle(&self, start: usize, is_end: impl Fn(usize) -> bool) -> Result<Cycle, ParseError> {
+        let width = self.instructions.len();
+        let mut first_seen = vec![u64::MAX; self.names.len() * width];
+        let mut hits = Vec::new();
+        let mut node = start;
+        let mut step: u64 = 0;
+        loop {
+            let state = node * width + (step % width as u64) as usize;
+            if first_seen[state] != u64::MAX {
+                let tail = first_seen[state];
+                let (tail_hits, cycle_hits) = hits.iter().partition(|&&hit| hit < tail);
+                return Ok(Cycle { tail, period: step - tail, tail_hits, cycle_hits });
+            }
+            first_seen[state] = step;
+            if is_end(node) {
+                hits.push(step);
+            }
+            node = self.step(node, step)?;
+            step += 1;
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Location(u8,u8,u8);
+/// Where one walk ends up: after `tail` steps it repeats every `period` steps. It is on an end
+/// node at each of `tail_hits` once, and at each of `cycle_hits` plus any multiple of `period`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cycle {
+    pub tail: u64,
+    pub period: u64,
+    pub tail_hits: Vec<u64>,
+    pub cycle_hits: Vec<u64>,
+}
 
-impl Location {
-    pub fn new(bytes: &[u8]) -> Self {
-        Self(
-            bytes[0] - 65,
-            bytes[1] - 65,
-            bytes[2] - 65
-        )
+impl Cycle {
+    pub fn hits(&self, step: u64) -> bool {
+        if step < self.tail {
+            return self.tail_hits.contains(&step);
+        }
+        self.cycle_hits.iter().any(|&hit| step >= hit && (step - hit) % self.period == 0)
     }
-}
 
-#[derive(Debug, Clone, Copy)]
-struct Node {
-    left: Location,
-    right: Location
+    /// The first step at or after `step` that lands on an end node.
+    pub fn next_hit(&self, step: u64) -> Option<u64> {
+        if let Some(&hit) = self.tail_hits.iter().find(|&&hit| hit >= step) {
+            return Some(hit);
+        }
+        self.cycle_hits
+            .iter()
+            .map(|&hit| match step.checked_sub(hit) {
+                Some(past) => step + (self.period - past % self.period) % self.period,
+                None => hit,
+            })
+            .min()
+    }
 }
 
-struct Solver {
-    reader: AsciiReader
+pub struct Solver {
+    reader: AsciiReader,
 }
 
 impl Solver {
     pub fn new(bytes: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(bytes)
-        }
+        Self { reader: AsciiReader::new(bytes) }
     }
 
-    pub fn read_instructions(&mut self) -> Vec<Instruction> {
-        let instruction_slice = self.reader.read_until(b'\n').unwrap();
+    /// Read the instruction line and the blank line after it
+    pub fn read_instructions(&mut self) -> Result<Vec<Instruction>, ParseError> {
+        let Some(instruction_slice) = self.reader.read_until(b'\n') else {
+            return Err(self.reader.error("expected a line of instructions"));
+        };
         let mut instructions = Vec::with_capacity(instruction_slice.len());
         for byte in instruction_slice {
             instructions.push(match byte {
                 b'L' => Instruction::Left,
                 b'R' => Instruction::Right,
-                _ => unreachable!()
+                _ => {
+                    let msg = format!("unknown instruction {:?}", *byte as char);
+                    return Err(ParseError::new(msg));
+                }
             });
         }
 
-        instructions
+        if instructions.is_empty() {
+            return Err(self.reader.error("expected at least one instruction"));
+        }
+        self.reader.expect(b"\n\n")?;
+
+        Ok(instructions)
     }
 
-    pub fn read_next_node(&mut self) -> Option<(Node, Location)> {
-        if self.reader.eof() {
-            return None;
+    fn read_label(&mut self) -> Result<String, ParseError> {
+        let length = self.reader.buffer[self.reader.index..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_alphanumeric())
+            .count();
+        if length == 0 {
+            return Err(self.reader.error("expected a node label"));
         }
+        Ok(String::from_utf8(self.reader.next(length).to_vec()).unwrap())
+    }
 
-        let location = Location::new(self.reader.read_until(b' ').unwrap());
-        self.reader.skip(4);
-
-        let left = Location::new(self.reader.read_until(b',').unwrap());
-        self.reader.skip(2);
+    /// Read a `NAME = (LEFT, RIGHT)` line.
+    pub fn read_next_node(&mut self) -> Result<Option<[String; 3]>, ParseError> {
+        if self.reader.eof() {
+            return Ok(None);
+        }
 
-        let right = Location::new(self.reader.read_until(b')').unwrap());
-        self.reader.skip(2);
+        let name = self.read_label()?;
+        self.reader.expect(b" = (")?;
+        let left = self.read_label()?;
+        self.reader.expect(b", ")?;
+        let right = self.read_label()?;
+        self.reader.expect(b")")?;
+        if !self.reader.eof() {
+            self.reader.expect(b"\n")?;
+        }
 
-        Some((Node {
-            left,
-            right
-        },location))
+        Ok(Some([name, left, right]))
     }
 
-    pub fn solve(&mut self) -> u64 {
-        // Get Instructions
-        let mut instruction_set = InstructionSet::new(self.read_instructions());
-        self.reader.read_until(b'\n');
-        self.reader.skip(2);
+    pub fn read_network(&mut self) -> Result<Network, ParseError> {
+        let instructions = self.read_instructions()?;
 
-        // Build graph
-        let mut graph: [[[Option<Node>; 26]; 26]; 26] = [[[None; 26]; 26]; 26];
-        while let Some((node, location)) = self.read_next_node() {
-            graph[location.0 as usize][location.1 as usize][location.2 as usize] = Some(node);
+        let mut ids: HashMap<String, usize> = HashMap::new();
+        let mut names = Vec::new();
+        let mut intern = |name: String| {
+            *ids.entry(name).or_insert_with_key(|name| {
+                names.push(name.clone());
+                names.len() - 1
+            })
+        };
+        let mut edges = Vec::new();
+        while let Some([name, left, right]) = self.read_next_node()? {
+            edges.push((intern(name), intern(left), intern(right)));
         }
 
-        // Traverse graph
-        let mut location = Location(0,0,0);
-        let mut num_steps: u64 = 0;
-        loop {
-            // println!("{:?}", location);
-            num_steps += 1;
-            match instruction_set.next() {
-                Instruction::Left => {
-                    location = graph[location.0 as usize][location.1 as usize][location.2 as usize].unwrap().left;
-                },
-                Instruction::Right => {
-                    location = graph[location.0 as usize][location.1 as usize][location.2 as usize].unwrap().right;
-                }
-            }
-
-            if location.0 == 25 && location.1 == 25 && location.2 == 25 {
-                break;
+        let mut left: Vec<usize> = (0..names.len()).collect();
+        let mut right = left.clone();
+        let mut defined = vec![false; names.len()];
+        for (node, to_left, to_right) in edges {
+            if defined[node] {
+                return Err(ParseError::new(format!("node {} is defined twice", names[node])));
             }
+            defined[node] = true;
+            left[node] = to_left;
+            right[node] = to_right;
         }
 
-        num_steps
+        Ok(Network { instructions, names, left, right, defined })
+    }
+
+    pub fn solve(&mut self) -> Result<u64, ParseError> {
+        let network = self.read_network()?;
+        let missing = |name| ParseError::new(format!("there is no node {name}"));
+        let start = network.node("AAA").ok_or_else(|| missing("AAA"))?;
+        let end = network.node("ZZZ").ok_or_else(|| missing("ZZZ"))?;
+
+        // The walk is a loop after its tail, so it reaches ZZZ within its first cycle or never
+        let cycle = network.cycle(start, |node| node == end)?;
+        cycle
+            .next_hit(1)
+            .ok_or_else(|| ParseError::new(String::from("the walk from AAA never reaches ZZZ")))
     }
 }
 
 #[derive(Parser, Debug)]
 pub struct Day8a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
 }
 
 impl CommandImpl for Day8a {
     fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input)?).solve();
-        println!("Day8a: {answer}");
-        Ok(())
+        self.input.for_each(|bytes| {
+            let answer = Solver::new(bytes).solve()?;
+            println!("Day8a: {answer}");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)
+";
+
+    fn solve(input: &[u8]) -> u64 {
+        Solver::new(input.to_vec()).solve().unwrap()
+    }
+
+    fn parse(input: &[u8]) -> Result<Network, ParseError> {
+        Solver::new(input.to_vec()).read_network()
+    }
+
+    const FIRST_EXAMPLE: &str = "\
+RL
+
+AAA = (BBB, CCC)
+BBB = (DDD, EEE)
+CCC = (ZZZ, GGG)
+DDD = (DDD, DDD)
+EEE = (EEE, EEE)
+GGG = (GGG, GGG)
+ZZZ = (ZZZ, ZZZ)
+";
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 6;
+            example first: FIRST_EXAMPLE => 2;
+            input real: "day8.txt" => 11911;
+        }
+    }
+
+    #[test]
+    fn any_labels() {
+        let network = parse(b"RL\n\nstart = (x1, AAA)\nx1 = (start, 22)\n22 = (22, 22)\n").unwrap();
+        assert_eq!(network.names, ["start", "x1", "AAA", "22"]);
+        assert_eq!(network.left, [1, 0, 2, 3]);
+        assert_eq!(network.defined, [true, true, false, true]);
+        assert!(network.step(2, 0).is_err());
+        // AAA walks onto BBB, which is only ever a destination
+        let input = b"L\n\nAAA = (BBB, AAA)\nZZZ = (ZZZ, ZZZ)\n";
+        assert!(Solver::new(input.to_vec()).solve().is_err());
+        assert!(parse(b"L\n\nAAA = (AAA, AAA)\nAAA = (AAA, AAA)\n").is_err());
+        assert!(parse(b"L\n\nA-A = (AAA, AAA)\n").is_err());
+    }
+
+    #[test]
+    fn never_reaches_the_end() {
+        let input = b"L\n\nAAA = (BBB, BBB)\nBBB = (AAA, AAA)\nZZZ = (ZZZ, ZZZ)\n";
+        assert!(Solver::new(input.to_vec()).solve().is_err());
+    }
+
+    #[test]
+    fn cycles() {
+        let network = parse(EXAMPLE.as_bytes()).unwrap();
+        let end = network.node("ZZZ").unwrap();
+        // AAA, BBB, AAA, BBB, AAA, BBB, ZZZ, then ZZZ at every step
+        let cycle = network.cycle(0, |node| node == end).unwrap();
+        assert_eq!(
+            cycle,
+            Cycle { tail: 6, period: 3, tail_hits: vec![], cycle_hits: vec![6, 7, 8] }
+        );
+        assert!(cycle.hits(1000) && !cycle.hits(5));
+        assert_eq!(cycle.next_hit(0), Some(6));
+        assert_eq!(cycle.next_hit(100), Some(100));
+    }
+
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
@@ -1,255 +1,343 @@
-use std::path::PathBuf;
-
 use clap::Parser;
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::utils::{Inputs, ParseError};
 
-use super::{CommandImpl, DynError};
+use super::{
+    day8a::{Cycle, Network, Solver},
+    CommandImpl, DynError,
+};
 
-struct Cycle {
-    pub period: u64,
-    pub z_indices: Vec<u64>
-}
+/// Give up on combining cycles when there are more choices of end nodes than this to try.
+const MAX_COMBINATIONS: usize = 10_000;
 
-#[derive(Debug, Clone, Copy)]
-enum Instruction {
-    Left,
-    Right
-}
+/// Stop a simulation that has found nothing after this many steps.
+const MAX_SIMULATED_STEPS: u64 = 100_000_000;
 
-struct InstructionSet {
-    pub instructions: Vec<Instruction>,
-    pub index: usize
+#[derive(Parser, Debug)]
+pub struct Day8b {
+    #[clap(flatten)]
+    input: Inputs,
+    /// Print each ghost's tail, period and the steps that land on a Z
+    #[clap(long)]
+    cycles: bool,
+    /// Walk every ghost step by step instead of combining their cycles
+    #[clap(long)]
+    simulate: bool,
 }
 
-impl InstructionSet {
-    pub fn new(instructions: Vec<Instruction>) -> Self {
-        Self {
-            instructions,
-            index: 0
-        }
+/// The ghosts start on every node ending in A and are done when all of them are on a Z.
+pub fn ghosts(network: &Network) -> Result<Vec<(usize, Cycle)>, ParseError> {
+    let is_end = |node: usize| network.names[node].ends_with('Z');
+    let ghosts = (0..network.names.len())
+        .filter(|&node| network.names[node].ends_with('A'))
+        .map(|start| Ok((start, network.cycle(start, is_end)?)))
+        .collect::<Result<Vec<_>, ParseError>>()?;
+    if ghosts.is_empty() {
+        return Err(ParseError::new(String::from("no node ends in A")));
     }
+    Ok(ghosts)
+}
 
-    pub fn reset(&mut self) {
-        self.index = 0;
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
     }
+}
 
-    pub fn index(&self) -> usize {
-        self.index
+/// Merge `x = a (mod m)` and `x = b (mod n)` into one congruence, or `None` when no number
+/// satisfies both. The moduli need not be coprime, but `n` must fit in an `i128`.
+/// `Some(Err(()))` means the merged modulus does not fit in a `u128`.
+fn merge(a: u128, m: u128, b: u128, n: u128) -> Option<Result<(u128, u128), ()>> {
+    let g = gcd(m, n);
+    let (a_n, b_n) = (a % n, b % n);
+    let diff = if b_n >= a_n { b_n - a_n } else { n - (a_n - b_n) };
+    if diff % g != 0 {
+        return None;
     }
+    let Some(lcm) = (m / g).checked_mul(n) else {
+        return Some(Err(()));
+    };
+    // Solve a + m * k = b (mod n), which is (m / g) * k = diff / g (mod n / g)
+    let (m_g, n_g) = (m / g, n / g);
+    let k = mul_mod(diff / g, inverse(m_g % n_g, n_g), n_g);
+    Some(Ok((add_mod(a % lcm, mul_mod(m, k, lcm), lcm), lcm)))
+}
 
-    pub fn next(&mut self) -> Instruction {
-        if self.index >= self.instructions.len() {
-            self.index = 0;
-        }
-
-        let instruction = self.instructions[self.index];
-        self.index += 1;
-        instruction
+/// `a + b` modulo `modulus`, for `a` and `b` already below it.
+fn add_mod(a: u128, b: u128, modulus: u128) -> u128 {
+    if a >= modulus - b {
+        a - (modulus - b)
+    } else {
+        a + b
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-struct Location(u8,u8,u8);
-
-impl Location {
-    pub fn new(bytes: &[u8]) -> Self {
-        Self(
-            bytes[0] - 65,
-            bytes[1] - 65,
-            bytes[2] - 65
-        )
+/// `a * b % modulus` without overflowing, by doubling and adding.
+fn mul_mod(a: u128, b: u128, modulus: u128) -> u128 {
+    let (mut a, mut b, mut product) = (a % modulus, b, 0);
+    while b > 0 {
+        if b & 1 == 1 {
+            product = add_mod(product, a, modulus);
+        }
+        a = add_mod(a, a, modulus);
+        b >>= 1;
     }
+    product
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Node {
-    left: Location,
-    right: Location
+/// The inverse of `a` modulo `modulus`, for `a` coprime to it.
+fn inverse(a: u128, modulus: u128) -> u128 {
+    if modulus == 1 {
+        return 0;
+    }
+    // Extended Euclid on signed values, which stay below the modulus
+    let (mut old_r, mut r) = (a as i128, modulus as i128);
+    let (mut old_s, mut s) = (1i128, 0i128);
+    while r != 0 {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_s, s) = (s, old_s - quotient * s);
+    }
+    old_s.rem_euclid(modulus as i128) as u128
 }
 
-struct Solver {
-    reader: AsciiReader
+/// What the ghosts' cycles say about when they are all on a Z.
+#[derive(Debug, PartialEq)]
+pub enum Combined {
+    At(u128),
+    Never,
+    /// There were too many combinations of end nodes to try, or the numbers got too big
+    Unknown,
 }
 
-impl Solver {
-    pub fn new(bytes: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(bytes)
-        }
+/// The first step where every ghost is on a Z, worked out from their cycles.
+pub fn combine(cycles: &[Cycle]) -> Combined {
+    // Before the longest tail, a shared step has to be one of that ghost's tail hits
+    let longest = cycles.iter().max_by_key(|cycle| cycle.tail).unwrap();
+    let mut early = longest.tail_hits.iter();
+    if let Some(&step) = early.find(|&&step| cycles.iter().all(|cycle| cycle.hits(step))) {
+        return Combined::At(step as u128);
     }
 
-    pub fn read_instructions(&mut self) -> Vec<Instruction> {
-        let instruction_slice = self.reader.read_until(b'\n').unwrap();
-        let mut instructions = Vec::with_capacity(instruction_slice.len());
-        for byte in instruction_slice {
-            instructions.push(match byte {
-                b'L' => Instruction::Left,
-                b'R' => Instruction::Right,
-                _ => unreachable!()
-            });
+    // After it, every ghost is in its cycle: try each choice of one cycle hit per ghost
+    let combinations = cycles.iter().try_fold(1usize, |count, cycle| {
+        count.checked_mul(cycle.cycle_hits.len()).filter(|&count| count <= MAX_COMBINATIONS)
+    });
+    if combinations.is_none() {
+        return Combined::Unknown;
+    }
+    let mut merged = vec![(0u128, 1u128)];
+    for cycle in cycles {
+        let period = cycle.period as u128;
+        let mut next = Vec::new();
+        for &(residue, modulus) in &merged {
+            for &hit in &cycle.cycle_hits {
+                match merge(residue, modulus, hit as u128 % period, period) {
+                    Some(Ok(congruence)) => next.push(congruence),
+                    Some(Err(())) => return Combined::Unknown,
+                    None => (),
+                }
+            }
         }
+        merged = next;
+    }
 
-        instructions
+    // Lift each solution past the longest tail, where the cycles hold for every ghost
+    let floor = longest.tail as u128;
+    let lifted = merged.into_iter().map(|(residue, modulus)| match residue.checked_sub(floor) {
+        Some(_) => Some(residue),
+        None => (floor - residue).div_ceil(modulus).checked_mul(modulus)?.checked_add(residue),
+    });
+    match lifted.collect::<Option<Vec<u128>>>() {
+        Some(steps) => steps.into_iter().min().map_or(Combined::Never, Combined::At),
+        None => Combined::Unknown,
     }
+}
 
-    pub fn read_next_node(&mut self) -> Option<(Node, Location)> {
-        if self.reader.eof() {
-            return None;
+/// Walk all ghosts together until they are all on a Z, for at most `limit` steps.
+pub fn simulate(network: &Network, starts: &[usize], limit: u64) -> Result<u64, ParseError> {
+    let is_end = |node: usize| network.names[node].ends_with('Z');
+    let mut nodes = starts.to_vec();
+    for step in 0..limit {
+        if nodes.iter().all(|&node| is_end(node)) {
+            return Ok(step);
+        }
+        for node in nodes.iter_mut() {
+            *node = network.step(*node, step)?;
         }
-
-        let location = Location::new(self.reader.read_until(b' ').unwrap());
-        self.reader.skip(4);
-
-        let left = Location::new(self.reader.read_until(b',').unwrap());
-        self.reader.skip(2);
-
-        let right = Location::new(self.reader.read_until(b')').unwrap());
-        self.reader.skip(2);
-
-        Some((Node {
-            left,
-            right
-        },location))
     }
+    Err(ParseError::new(format!("the ghosts do not line up in {limit} steps")))
+}
 
-    pub fn solve(&mut self) -> u64 {
-        // Get Instructions
-        let mut instruction_set = InstructionSet::new(self.read_instructions());
-        self.reader.read_until(b'\n');
-        self.reader.skip(2);
-
-        let mut test_locations = Vec::new();
-
-        let mut z_cycle_idx: Vec<u64> = vec![];
-
-        // Build graph
-        let mut graph: [[[Option<Node>; 26]; 26]; 26] = [[[None; 26]; 26]; 26];
-        while let Some((node, location)) = self.read_next_node() {
-            if location.2 == 0 {
-                test_locations.push(location);
-            }
-
-            // if location.2 == 25 {
-            //     num_zs += 1;
-            // }
-
-            graph[location.0 as usize][location.1 as usize][location.2 as usize] = Some(node);
+pub fn solve(network: &Network, simulate_only: bool) -> Result<u128, ParseError> {
+    let ghosts = ghosts(network)?;
+    let starts: Vec<usize> = ghosts.iter().map(|(start, _)| *start).collect();
+    if simulate_only {
+        return simulate(network, &starts, MAX_SIMULATED_STEPS).map(u128::from);
+    }
+    let cycles: Vec<Cycle> = ghosts.into_iter().map(|(_, cycle)| cycle).collect();
+    match combine(&cycles) {
+        Combined::At(step) => Ok(step),
+        Combined::Never => {
+            Err(ParseError::new(String::from("the ghosts are never all on a Z at once")))
         }
+        Combined::Unknown => simulate(network, &starts, MAX_SIMULATED_STEPS).map(u128::from),
+    }
+}
 
-        // println!("Num Zs: {}", num_zs);
-
-        let mut z_product: u64 = 1;
-
-        for location in test_locations.iter_mut() {
-            instruction_set.reset();
-            let mut num_steps: u64 = 0;
-            let mut steps = vec![];
-            let mut z_step_indices = vec![];
-            'outer: loop {
-                steps.push((*location, instruction_set.index()));
-                // Keep track of where Z's are found
-                if location.2 == 25 {
-                    z_step_indices.push(num_steps);
-                    z_cycle_idx.push(num_steps);
-                    break;
-                }
-
-                // println!("{:?}", location);
-                num_steps += 1;
-
-                // Move to the next node
-                match instruction_set.next() {
-                    Instruction::Left => {
-                        *location = graph[location.0 as usize][location.1 as usize][location.2 as usize].unwrap().left;
-                    },
-                    Instruction::Right => {
-                        *location = graph[location.0 as usize][location.1 as usize][location.2 as usize].unwrap().right;
-                    }
+impl CommandImpl for Day8b {
+    fn main(&mut self) -> Result<(), DynError> {
+        let (show_cycles, simulate_only) = (self.cycles, self.simulate);
+        self.input.for_each(|bytes| {
+            let network = Solver::new(bytes).read_network()?;
+            if show_cycles {
+                for (start, cycle) in ghosts(&network)? {
+                    let Cycle { tail, period, tail_hits, cycle_hits } = cycle;
+                    let name = &network.names[start];
+                    println!(
+                        "{name}: tail {tail}, period {period}, Z at {tail_hits:?} and at \
+                         {cycle_hits:?} plus multiples of {period}"
+                    );
                 }
-                
-                // Check for cycles
-                // for (idx, step) in steps.iter().enumerate() {
-                //     if step.0 == *location && step.1 == instruction_set.index(){
-                //         // println!("{:?}", location);
-                //         // println!("Cycle {} <-> {} Length: {} - Z's {z_step_indices:?}", idx, num_steps, num_steps - (idx as u64));
-                //         // z_product *= z_step_indices[0] as u64;
-                //         z_cycle_idx.push(z_step_indices[0]);
-                //         break 'outer;
-                //     }
-                // }
             }
-            // println!("{:?}", num_steps - 1);
-        }
+            let answer = solve(&network, simulate_only)?;
+            println!("Day8b: {answer}");
+            // 16579584610977290608777500 is too high
+            // 16579584610977290608789412 is too high
+            // 14386467658893820428288 is too high
+
+            // 10151663816849
+            Ok(())
+        })
+    }
+}
 
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::commands::day8a::Instruction;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)
+";
+
+    fn network(input: &[u8]) -> Result<Network, ParseError> {
+        Solver::new(input.to_vec()).read_network()
+    }
 
-        // Traverse graph
-        // let mut num_steps: u64 = 0;
-        // loop {
-        //     let mut all_locations_end_with_z = true;
-        //     // println!("{:?}", test_locations);
-        //     num_steps += 1;
-        //     let instruction = instruction_set.next();
-
-        //     for location in test_locations.iter_mut() {
-        //         match instruction {
-        //             Instruction::Left => {
-        //                 *location = graph[location.0 as usize][location.1 as usize][location.2 as usize].unwrap().left;
-        //             },
-        //             Instruction::Right => {
-        //                 *location = graph[location.0 as usize][location.1 as usize][location.2 as usize].unwrap().right;
-        //             }
-        //         }
-        //         if location.2 != 25 {
-        //             all_locations_end_with_z = false;
-        //         }
-        //     }
-
-        //     // println!("{:?}", test_locations);
-
-        //     if all_locations_end_with_z {
-        //         break;
-        //     }
-        // }
-
-        lcmx::lcmx(&z_cycle_idx).unwrap()
+    fn solve(input: &[u8]) -> u128 {
+        super::solve(&network(input).unwrap(), false).unwrap()
     }
-}
 
-#[derive(Parser, Debug)]
-pub struct Day8b {
-    #[clap(long, short)]
-    input: PathBuf,
-}
+    fn simulated(input: &[u8]) -> u128 {
+        super::solve(&network(input).unwrap(), true).unwrap()
+    }
 
-#[derive(Debug)]
-struct Foo {
-    pub base: u64,
-    pub value: u64
-}
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 6;
+            input real: "day8.txt" => 10151663816849;
+        }
+        simulated {
+            example statement: EXAMPLE => 6;
+            input small: "day8_small.txt" => 6;
+        }
+    }
 
-impl CommandImpl for Day8b {
-    fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input)?).solve();
-        println!("Day8b: {answer}");
-        // 16579584610977290608777500 is too high
-        // 16579584610977290608789412 is too high
-        // 14386467658893820428288 is too high
+    #[test]
+    fn cycles() {
+        let ghosts = ghosts(&network(EXAMPLE.as_bytes()).unwrap()).unwrap();
+        let cycles: Vec<&Cycle> = ghosts.iter().map(|(_, cycle)| cycle).collect();
+        assert_eq!(
+            cycles,
+            [
+                &Cycle { tail: 1, period: 2, tail_hits: vec![], cycle_hits: vec![2] },
+                &Cycle { tail: 1, period: 6, tail_hits: vec![], cycle_hits: vec![3, 6] },
+            ]
+        );
+    }
 
-        // 10151663816849
+    #[test]
+    fn misaligned_cycles() {
+        // Z every 4 steps from 1, and every 6 steps from 3: first together at 9
+        let cycle =
+            |tail, period, cycle_hits| Cycle { tail, period, tail_hits: vec![], cycle_hits };
+        assert_eq!(combine(&[cycle(0, 4, vec![1]), cycle(0, 6, vec![3])]), Combined::At(9));
+        // Odd and even steps never meet
+        assert_eq!(combine(&[cycle(0, 2, vec![1]), cycle(0, 4, vec![2])]), Combined::Never);
+        // A shared tail hit comes before any cycle
+        let early = Cycle { tail: 5, period: 3, tail_hits: vec![2], cycle_hits: vec![6] };
+        assert_eq!(combine(&[early, cycle(0, 2, vec![0])]), Combined::At(2));
+        // Residues below the longest tail are lifted past it
+        let late = Cycle { tail: 10, period: 5, tail_hits: vec![], cycle_hits: vec![12] };
+        assert_eq!(combine(&[late, cycle(0, 1, vec![0])]), Combined::At(12));
+    }
 
-        // panic!();
+    #[test]
+    fn congruences() {
+        assert_eq!(merge(1, 4, 3, 6), Some(Ok((9, 12))));
+        assert_eq!(merge(1, 2, 0, 2), None);
+        assert_eq!(merge(0, u128::MAX, 0, u128::MAX - 1), Some(Err(())));
+        assert_eq!(mul_mod(u128::MAX - 1, u128::MAX - 1, u128::MAX), 1);
+    }
 
-        // let mut data = vec![
-        //     Foo {base: 21883, value: 21883},
-        //     Foo {base: 19667, value: 19667},
-        //     Foo {base: 14681, value: 14681},
-        //     Foo {base: 16897, value: 16897},
-        //     Foo {base: 13019, value: 13019},
-        //     Foo {base: 11911, value: 11911},
-        // ];
+    fn random_network() -> impl Strategy<Value = Network> {
+        (1..6usize, prop::collection::vec(any::<bool>(), 1..5)).prop_flat_map(|(size, turns)| {
+            let edges = prop::collection::vec((0..size, 0..size), size);
+            let ends = prop::collection::vec(any::<bool>(), size);
+            (edges, ends).prop_map(move |(edges, ends)| Network {
+                instructions: turns
+                    .iter()
+                    .map(|&left| if left { Instruction::Left } else { Instruction::Right })
+                    .collect(),
+                names: (0..size)
+                    .map(|node| format!("{node}{}", if ends[node] { 'Z' } else { 'A' }))
+                    .collect(),
+                left: edges.iter().map(|&(left, _)| left).collect(),
+                right: edges.iter().map(|&(_, right)| right).collect(),
+                defined: vec![true; size],
+            })
+        })
+    }
 
+    proptest! {
+        #[test]
+        fn combined_cycles_match_simulation(network in random_network()) {
+            let Ok(ghosts) = ghosts(&network) else {
+                return Ok(());
+            };
+            let starts: Vec<usize> = ghosts.iter().map(|(start, _)| *start).collect();
+            let cycles: Vec<Cycle> = ghosts.into_iter().map(|(_, cycle)| cycle).collect();
+            // After the longest tail the ghosts repeat together within the lcm of their periods
+            let tail = cycles.iter().map(|cycle| cycle.tail).max().unwrap();
+            let period = cycles.iter().fold(1, |lcm, cycle| {
+                lcm / gcd(lcm as u128, cycle.period as u128) as u64 * cycle.period
+            });
+            let simulated = simulate(&network, &starts, tail + period).ok().map(u128::from);
+            let combined = match combine(&cycles) {
+                Combined::At(step) => Some(step),
+                Combined::Never => None,
+                Combined::Unknown => return Ok(()),
+            };
+            prop_assert_eq!(combined, simulated);
+        }
+    }
 
-        Ok(())
+    robustness_tests! {
+        network: EXAMPLE;
     }
 }
@@ -1,107 +1,323 @@
-use std::{path::PathBuf, borrow::BorrowMut};
+use std::fmt;
 
 use clap::Parser;
 
-use crate::utils::{AsciiReader, slurp_bytes};
+use crate::utils::{AsciiReader, Inputs, ParseError};
 
 use super::{CommandImpl, DynError};
 
-struct History(Vec<i64>);
+fn overflow() -> ParseError {
+    ParseError::new(String::from("extrapolation overflows"))
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// A fraction in lowest terms with a positive denominator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rational {
+    pub numerator: i128,
+    pub denominator: i128,
+}
+
+impl Rational {
+    pub fn new(numerator: i128, denominator: i128) -> Self {
+        let divisor = gcd(numerator, denominator).max(1) * denominator.signum();
+        Self { numerator: numerator / divisor, denominator: denominator / divisor }
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        let numerator = self
+            .numerator
+            .checked_mul(other.denominator)?
+            .checked_add(other.numerator.checked_mul(self.denominator)?)?;
+        Some(Self::new(numerator, self.denominator.checked_mul(other.denominator)?))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.denominator {
+            1 => write!(f, "{}", self.numerator),
+            denominator => write!(f, "{}/{denominator}", self.numerator),
+        }
+    }
+}
+
+/// The coefficients of a polynomial in `n`, constant term first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polynomial(pub Vec<Rational>);
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut terms = self.0.iter().enumerate().rev().filter(|(_, c)| c.numerator != 0);
+        let Some(first) = terms.next() else {
+            return write!(f, "0");
+        };
+        for (idx, (power, coefficient)) in std::iter::once(first).chain(terms).enumerate() {
+            let sign = if coefficient.numerator < 0 { "-" } else { "+" };
+            match idx {
+                0 if sign == "-" => write!(f, "-")?,
+                0 => (),
+                _ => write!(f, " {sign} ")?,
+            }
+            let magnitude = Rational { numerator: coefficient.numerator.abs(), ..*coefficient };
+            match (power, magnitude.numerator == magnitude.denominator) {
+                (0, _) => write!(f, "{magnitude}")?,
+                (_, true) => (),
+                (_, false) if magnitude.denominator == 1 => write!(f, "{magnitude}")?,
+                (_, false) => write!(f, "{magnitude} ")?,
+            }
+            match power {
+                0 => (),
+                1 => write!(f, "n")?,
+                _ => write!(f, "n^{power}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A sequence of readings, taken to be the values of a polynomial at `n = 0, 1, 2, ...`: the
+/// lowest degree one through all of them, which is what repeatedly taking differences finds.
+pub struct History(Vec<i64>);
 
 impl History {
     pub fn new(values: Vec<i64>) -> Self {
         Self(values)
     }
 
-    pub fn next_value(self) -> i64 {
-        let mut rows: Vec<Vec<i64>> = vec![self.0];
-        let mut last_row_idx = 0;
-        
-        let mut next_row_value = loop {
-            let mut next_row: Vec<i64> = vec![];
-            rows[last_row_idx].iter().enumerate().skip(1).for_each(|(i, v)| {
-                next_row.push(v - rows[last_row_idx][i - 1]);
-            });
-            let homogenous = next_row.iter().all(|v| *v == next_row[0]);
-
-            if homogenous {
-                break next_row[0];
+    /// The first value of each row of differences, which are the coefficients of the polynomial
+    /// in Newton's forward form, `sum(differences[k] * binomial(n, k))`. Rows that are all zero
+    /// are left off.
+    pub fn differences(&self) -> Result<Vec<i128>, ParseError> {
+        let mut row: Vec<i128> = self.0.iter().map(|&value| value as i128).collect();
+        let mut differences = Vec::with_capacity(row.len());
+        while !row.is_empty() && row.iter().any(|&value| value != 0) {
+            differences.push(row[0]);
+            for idx in 0..row.len() - 1 {
+                row[idx] = row[idx + 1].checked_sub(row[idx]).ok_or_else(overflow)?;
             }
+            row.pop();
+        }
+        Ok(differences)
+    }
 
-            rows.push(next_row);
+    /// The polynomial's value at `n`, which may come before the first reading.
+    pub fn value_at(&self, n: i64) -> Result<i64, ParseError> {
+        let n = n as i128;
+        let mut value: i128 = 0;
+        // binomial(n, k), which works for negative `n` too
+        let mut binomial: i128 = 1;
+        for (k, difference) in self.differences()?.into_iter().enumerate() {
+            let term = difference.checked_mul(binomial).ok_or_else(overflow)?;
+            value = value.checked_add(term).ok_or_else(overflow)?;
+            let k = k as i128;
+            binomial = binomial.checked_mul(n - k).ok_or_else(overflow)? / (k + 1);
+        }
+        value.try_into().map_err(|_| overflow())
+    }
 
-            last_row_idx += 1;
+    /// The value `steps` readings after the last one, or before the first when negative.
+    pub fn extrapolate(&self, steps: i64) -> Result<i64, ParseError> {
+        let n = match steps {
+            0.. => (self.0.len() as i64 - 1).checked_add(steps).ok_or_else(overflow)?,
+            _ => steps,
         };
+        self.value_at(n)
+    }
 
-        for row in rows.iter_mut().rev() {
-            next_row_value += row.last().unwrap();
+    /// Expand Newton's form into powers of `n`, building each falling factorial
+    /// `n (n - 1) ... (n - k + 1)` one factor at a time and dividing it by `k!`.
+    pub fn polynomial(&self) -> Result<Polynomial, ParseError> {
+        let differences = self.differences()?;
+        let zero = Rational::new(0, 1);
+        let mut coefficients = vec![zero; differences.len().max(1)];
+        let mut falling: Vec<i128> = vec![1];
+        let mut factorial: i128 = 1;
+        for (k, difference) in differences.into_iter().enumerate() {
+            for (power, &coefficient) in falling.iter().enumerate() {
+                let numerator = difference.checked_mul(coefficient).ok_or_else(overflow)?;
+                coefficients[power] = coefficients[power]
+                    .checked_add(Rational::new(numerator, factorial))
+                    .ok_or_else(overflow)?;
+            }
+            // Multiply by (n - k)
+            let k = k as i128;
+            let mut next = vec![0; falling.len() + 1];
+            for (power, &coefficient) in falling.iter().enumerate() {
+                next[power + 1] += coefficient;
+                next[power] -= coefficient.checked_mul(k).ok_or_else(overflow)?;
+            }
+            falling = next;
+            factorial = factorial.checked_mul(k + 1).ok_or_else(overflow)?;
         }
-
-        next_row_value
+        Ok(Polynomial(coefficients))
     }
 }
 
-struct HistoryReader {
-    reader: AsciiReader
+pub struct HistoryReader {
+    reader: AsciiReader,
 }
 
 impl HistoryReader {
     pub fn new(buffer: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(buffer)
-        }
+        Self { reader: AsciiReader::new(buffer) }
     }
 }
 
 impl Iterator for HistoryReader {
-    type Item = History;
+    type Item = Result<History, ParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.reader.eof() {
+            return None;
+        }
+
         let mut history = Vec::new();
 
         while let Some(value) = self.reader.read_next_number() {
             history.push(value);
         }
 
-        // Move past new line
-        self.reader.skip(1);
-
         if history.is_empty() {
-            return None;
+            return Some(Err(self.reader.error("expected a history")));
         }
 
-        Some(History::new(history))
-    }
-}
+        // Move past new line
+        match self.reader.peek() {
+            Some(b'\n') => self.reader.skip(1),
+            None => (),
+            Some(_) => return Some(Err(self.reader.error("expected a number"))),
+        }
 
-struct Solver {
-    reader: HistoryReader,
+        Some(Ok(History::new(history)))
+    }
 }
 
-impl Solver {
-    pub fn new(buffer: Vec<u8>) -> Self {
-        Self {
-            reader: HistoryReader::new(buffer)
+/// Add up every history's value `steps` readings past its end, or before its start when
+/// negative, reading one history at a time.
+pub fn sum_extrapolated(buffer: Vec<u8>, steps: i64, polynomials: bool) -> Result<i64, ParseError> {
+    let mut sum: i64 = 0;
+    for history in HistoryReader::new(buffer) {
+        let history = history?;
+        if polynomials {
+            println!("{}", history.polynomial()?);
         }
+        sum = sum.checked_add(history.extrapolate(steps)?).ok_or_else(overflow)?;
     }
-
-    pub fn solve(self) -> i64 {
-        self.reader
-            .map(|h| h.next_value())
-            .sum()
-    }
+    Ok(sum)
 }
 
 #[derive(Parser, Debug)]
 pub struct Day9a {
-    #[clap(long, short)]
-    input: PathBuf,
+    #[clap(flatten)]
+    input: Inputs,
+    /// How many readings past the end of each history to extrapolate
+    #[clap(long, default_value_t = 1)]
+    steps: u32,
+    /// Print the polynomial in n through each history, where n = 0 at its first reading
+    #[clap(long)]
+    polynomials: bool,
 }
 
 impl CommandImpl for Day9a {
     fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day9a: {answer}");
-        Ok(())
+        let (steps, polynomials) = (self.steps, self.polynomials);
+        self.input.for_each(|bytes| {
+            let answer = sum_extrapolated(bytes, steps as i64, polynomials)?;
+            println!("Day9a: {answer}");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::testing::{puzzle_tests, robustness_tests};
+
+    const EXAMPLE: &str = "\
+0 3 6 9 12 15
+1 3 6 10 15 21
+10 13 16 21 30 45
+";
+
+    fn solve(input: &[u8]) -> i64 {
+        sum_extrapolated(input.to_vec(), 1, false).unwrap()
+    }
+
+    fn parse(input: &[u8]) -> Result<Vec<History>, ParseError> {
+        HistoryReader::new(input.to_vec()).collect()
+    }
+
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 114;
+            input real: "day9.txt" => 1757008019;
+            input big: "day9_big.txt" => 87850400950;
+        }
+    }
+
+    #[test]
+    fn steps() {
+        let history = History::new(vec![10, 13, 16, 21, 30, 45]);
+        assert_eq!(history.extrapolate(1).unwrap(), 68);
+        assert_eq!(history.extrapolate(2).unwrap(), 101);
+        assert_eq!(history.extrapolate(0).unwrap(), 45);
+        assert_eq!(history.extrapolate(-1).unwrap(), 5);
+        assert_eq!(history.extrapolate(-2).unwrap(), -4);
+        assert_eq!(History::new(vec![7]).extrapolate(-5).unwrap(), 7);
+    }
+
+    #[test]
+    fn polynomials() {
+        let polynomial = |values: &[i64]| History::new(values.to_vec()).polynomial().unwrap();
+        assert_eq!(polynomial(&[0, 3, 6, 9, 12, 15]).to_string(), "3n");
+        assert_eq!(polynomial(&[1, 3, 6, 10, 15, 21]).to_string(), "1/2 n^2 + 3/2 n + 1");
+        assert_eq!(
+            polynomial(&[10, 13, 16, 21, 30, 45]).to_string(),
+            "1/3 n^3 - n^2 + 11/3 n + 10"
+        );
+        assert_eq!(polynomial(&[0, 0]).to_string(), "0");
+        assert_eq!(polynomial(&[-4, -4]).to_string(), "-4");
+    }
+
+    #[test]
+    fn overflow() {
+        let history = History::new(vec![0, i64::MAX]);
+        assert!(history.extrapolate(1).is_err());
+        assert_eq!(history.extrapolate(-1).unwrap(), -i64::MAX);
+        assert!(History::new(vec![i64::MIN, i64::MAX, i64::MIN]).extrapolate(1).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn recovers_polynomials(
+            coefficients in prop::collection::vec(-20i64..20, 1..5),
+            length in 5usize..10,
+            steps in -10i64..10,
+        ) {
+            let at = |n: i64| coefficients.iter().rev().fold(0, |value, &c| value * n + c);
+            let history = History::new((0..length as i64).map(at).collect());
+            let n = if steps >= 0 { length as i64 - 1 + steps } else { steps };
+            prop_assert_eq!(history.extrapolate(steps).unwrap(), at(n));
+            let expected: Vec<Rational> =
+                coefficients.iter().map(|&c| Rational::new(c as i128, 1)).collect();
+            let Polynomial(mut recovered) = history.polynomial().unwrap();
+            recovered.resize(expected.len(), Rational::new(0, 1));
+            prop_assert_eq!(recovered, expected);
+        }
+    }
+
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
@@ -1,107 +1,70 @@
-use std::{path::PathBuf, borrow::BorrowMut};
-
 use clap::Parser;
 
-use crate::utils::{AsciiReader, slurp_bytes};
-
-use super::{CommandImpl, DynError};
-
-struct History(Vec<i64>);
-
-impl History {
-    pub fn new(values: Vec<i64>) -> Self {
-        Self(values)
-    }
-
-    pub fn next_value(self) -> i64 {
-        let mut rows: Vec<Vec<i64>> = vec![self.0];
-        let mut last_row_idx = 0;
-        
-        let mut next_row_value = loop {
-            let mut next_row: Vec<i64> = vec![];
-            rows[last_row_idx].iter().enumerate().skip(1).for_each(|(i, v)| {
-                next_row.push(v - rows[last_row_idx][i - 1]);
-            });
-            let homogenous = next_row.iter().all(|v| *v == next_row[0]);
-
-            if homogenous {
-                break next_row[0];
-            }
-
-            rows.push(next_row);
+use crate::utils::Inputs;
 
-            last_row_idx += 1;
-        };
-
-        for row in rows.iter_mut().rev() {
-            next_row_value = row.first().unwrap() - next_row_value;
-        }
+use super::{day9a::sum_extrapolated, CommandImpl, DynError};
 
-        next_row_value
-    }
-}
-
-struct HistoryReader {
-    reader: AsciiReader
+#[derive(Parser, Debug)]
+pub struct Day9b {
+    #[clap(flatten)]
+    input: Inputs,
+    /// How many readings before the start of each history to extrapolate
+    #[clap(long, default_value_t = 1)]
+    steps: u32,
+    /// Print the polynomial in n through each history, where n = 0 at its first reading
+    #[clap(long)]
+    polynomials: bool,
 }
 
-impl HistoryReader {
-    pub fn new(buffer: Vec<u8>) -> Self {
-        Self {
-            reader: AsciiReader::new(buffer)
-        }
+impl CommandImpl for Day9b {
+    fn main(&mut self) -> Result<(), DynError> {
+        let (steps, polynomials) = (self.steps, self.polynomials);
+        self.input.for_each(|bytes| {
+            let answer = sum_extrapolated(bytes, -(steps as i64), polynomials)?;
+            println!("Day9b: {answer}");
+            Ok(())
+        })
     }
 }
 
-impl Iterator for HistoryReader {
-    type Item = History;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let mut history = Vec::new();
-
-        while let Some(value) = self.reader.read_next_number() {
-            history.push(value);
-        }
-
-        // Move past new line
-        self.reader.skip(1);
-
-        if history.is_empty() {
-            return None;
-        }
-
-        Some(History::new(history))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::day9a::{History, HistoryReader};
+    use crate::testing::{puzzle_tests, robustness_tests};
+    use crate::utils::ParseError;
+
+    const EXAMPLE: &str = "\
+0 3 6 9 12 15
+1 3 6 10 15 21
+10 13 16 21 30 45
+";
+
+    fn solve(input: &[u8]) -> i64 {
+        sum_extrapolated(input.to_vec(), -1, false).unwrap()
     }
-}
 
-struct Solver {
-    reader: HistoryReader,
-}
-
-impl Solver {
-    pub fn new(buffer: Vec<u8>) -> Self {
-        Self {
-            reader: HistoryReader::new(buffer)
-        }
+    fn two_back(input: &[u8]) -> i64 {
+        sum_extrapolated(input.to_vec(), -2, false).unwrap()
     }
 
-    pub fn solve(self) -> i64 {
-        self.reader
-            .map(|h| h.next_value())
-            .sum()
+    fn parse(input: &[u8]) -> Result<Vec<History>, ParseError> {
+        HistoryReader::new(input.to_vec()).collect()
     }
-}
 
-#[derive(Parser, Debug)]
-pub struct Day9b {
-    #[clap(long, short)]
-    input: PathBuf,
-}
+    puzzle_tests! {
+        solve {
+            example statement: EXAMPLE => 2;
+            input real: "day9.txt" => 995;
+            input big: "day9_big.txt" => 49750;
+        }
+        two_back {
+            // -6, 0 and -4
+            example statement: EXAMPLE => -10;
+        }
+    }
 
-impl CommandImpl for Day9b {
-    fn main(&mut self) -> Result<(), DynError> {
-        let answer = Solver::new(slurp_bytes(&self.input).unwrap()).solve();
-        println!("Day9b: {answer}");
-        Ok(())
+    robustness_tests! {
+        parse: EXAMPLE;
     }
 }
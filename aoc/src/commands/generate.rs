@@ -0,0 +1,845 @@
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+    ops::RangeInclusive,
+};
+
+use clap::{Parser, Subcommand};
+
+use super::{CommandImpl, DynError};
+
+/// Write a random, syntactically valid puzzle input to stdout.
+#[derive(Parser, Debug)]
+pub struct Generate {
+    /// The same seed always produces the same input
+    #[clap(long, default_value_t = 0)]
+    seed: u64,
+    #[clap(subcommand)]
+    puzzle: Puzzle,
+}
+
+#[derive(Subcommand, Debug)]
+enum Puzzle {
+    /// Calibration lines of letters, digits and spelled-out digits
+    Day1 {
+        #[clap(long, default_value_t = 1000)]
+        lines: usize,
+        #[clap(long, default_value_t = 30)]
+        max_length: usize,
+    },
+    /// Cube games
+    Day2 {
+        #[clap(long, default_value_t = 100)]
+        games: usize,
+        /// Most draws in one game
+        #[clap(long, default_value_t = 6)]
+        draws: usize,
+        /// Most cubes of one color in one draw
+        #[clap(long, default_value_t = 20, value_parser = clap::value_parser!(u8).range(1..))]
+        cubes: u8,
+    },
+    /// Engine schematic
+    Day3 {
+        #[clap(long, default_value_t = 140)]
+        width: usize,
+        #[clap(long, default_value_t = 140)]
+        height: usize,
+        /// Chance that a tile starts a part number
+        #[clap(long, default_value_t = 0.1)]
+        numbers: f64,
+        /// Chance that a tile is a symbol
+        #[clap(long, default_value_t = 0.05)]
+        symbols: f64,
+    },
+    /// Scratchcards
+    Day4 {
+        #[clap(long, default_value_t = 200)]
+        cards: usize,
+        #[clap(long, default_value_t = 10, value_parser = clap::value_parser!(u8).range(1..=99))]
+        winning: u8,
+        #[clap(long, default_value_t = 25, value_parser = clap::value_parser!(u8).range(1..=99))]
+        have: u8,
+        /// Chance that each winning number is also one you have. Copies grow exponentially
+        /// above about 0.1.
+        #[clap(long, default_value_t = 0.1)]
+        matches: f64,
+    },
+    /// Seeds and the seven almanac maps
+    Day5 {
+        /// Number of seed ranges; the seeds line holds twice as many numbers
+        #[clap(long, default_value_t = 10)]
+        seeds: usize,
+        /// Ranges in each map
+        #[clap(long, default_value_t = 30)]
+        ranges: usize,
+        #[clap(long, default_value_t = u32::MAX as u64)]
+        max_value: u64,
+    },
+    /// Race times and record distances
+    Day6 {
+        #[clap(long, default_value_t = 4)]
+        races: usize,
+        #[clap(long, default_value_t = 100)]
+        max_time: u64,
+    },
+    /// Camel Cards hands and bids
+    Day7 {
+        #[clap(long, default_value_t = 1000)]
+        hands: usize,
+        #[clap(long, default_value_t = 1000)]
+        max_bid: u32,
+    },
+    /// Left/right instructions and a desert network
+    Day8 {
+        #[clap(long, default_value_t = 280)]
+        instructions: usize,
+        #[clap(long, default_value_t = 700)]
+        nodes: usize,
+        /// Number of ..A to ..Z paths, the first of which is AAA to ZZZ
+        #[clap(long, default_value_t = 6, value_parser = clap::value_parser!(u8).range(1..=26))]
+        ghosts: u8,
+    },
+    /// Polynomial value histories
+    Day9 {
+        #[clap(long, default_value_t = 200)]
+        lines: usize,
+        #[clap(long, default_value_t = 21)]
+        values: usize,
+        #[clap(long, default_value_t = 10)]
+        max_degree: usize,
+    },
+    /// Pipe maze with a single loop through S
+    Day10 {
+        #[clap(long, default_value_t = 140, value_parser = clap::value_parser!(u16).range(3..))]
+        width: u16,
+        #[clap(long, default_value_t = 140, value_parser = clap::value_parser!(u16).range(3..))]
+        height: u16,
+    },
+    /// Galaxy image
+    Day11 {
+        #[clap(long, default_value_t = 140)]
+        width: usize,
+        #[clap(long, default_value_t = 140)]
+        height: usize,
+        /// Chance that a tile is a galaxy
+        #[clap(long, default_value_t = 0.02)]
+        galaxies: f64,
+    },
+    /// Spring condition records
+    Day12 {
+        #[clap(long, default_value_t = 1000)]
+        rows: usize,
+        #[clap(long, default_value_t = 20)]
+        max_length: usize,
+        /// Chance that a spring's condition is hidden behind a ?
+        #[clap(long, default_value_t = 0.5)]
+        unknown: f64,
+    },
+    /// Mirror patterns, each with one perfect reflection and one off by a single smudge
+    Day13 {
+        #[clap(long, default_value_t = 100)]
+        patterns: usize,
+        /// Widest pattern
+        #[clap(long, default_value_t = 17, value_parser = clap::value_parser!(u16).range(4..))]
+        width: u16,
+        /// Tallest pattern
+        #[clap(long, default_value_t = 17, value_parser = clap::value_parser!(u16).range(4..))]
+        height: u16,
+    },
+    /// Platform of rounded and cube-shaped rocks
+    Day14 {
+        #[clap(long, default_value_t = 100)]
+        width: usize,
+        #[clap(long, default_value_t = 100)]
+        height: usize,
+        #[clap(long, default_value_t = 0.2)]
+        rounded: f64,
+        #[clap(long, default_value_t = 0.1)]
+        cubes: f64,
+    },
+    /// Initialization sequence
+    Day15 {
+        #[clap(long, default_value_t = 4000)]
+        steps: usize,
+        #[clap(long, default_value_t = 6)]
+        max_label: usize,
+    },
+    /// Contraption of mirrors and splitters
+    Day16 {
+        #[clap(long, default_value_t = 110)]
+        width: usize,
+        #[clap(long, default_value_t = 110)]
+        height: usize,
+        /// Chance that a tile is a mirror or splitter
+        #[clap(long, default_value_t = 0.1)]
+        mirrors: f64,
+    },
+    /// Heat loss map
+    Day17 {
+        #[clap(long, default_value_t = 141)]
+        width: usize,
+        #[clap(long, default_value_t = 141)]
+        height: usize,
+    },
+}
+
+/// SplitMix64. Kept in-tree so a seed produces the same input regardless of dependency versions.
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub(crate) fn range(&mut self, range: RangeInclusive<u64>) -> u64 {
+        let span = range.end() - range.start();
+        if span == u64::MAX {
+            return self.next_u64();
+        }
+        range.start() + self.next_u64() % (span + 1)
+    }
+
+    fn index(&mut self, len: usize) -> usize {
+        self.range(0..=len as u64 - 1) as usize
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.index(items.len())]
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for idx in (1..items.len()).rev() {
+            items.swap(idx, self.index(idx + 1));
+        }
+    }
+}
+
+const DIGIT_WORDS: [&str; 9] =
+    ["one", "two", "three", "four", "five", "six", "seven", "eight", "nine"];
+
+impl Puzzle {
+    fn generate(&self, rng: &mut Rng) -> Result<String, DynError> {
+        let mut out = String::new();
+        match *self {
+            Puzzle::Day1 { lines, max_length } => {
+                for _ in 0..lines {
+                    let length = rng.range(1..=max_length.max(1) as u64);
+                    let mut line = String::new();
+                    // Every line needs at least one plain digit for part one
+                    let digit_at = rng.range(0..=length - 1);
+                    for idx in 0..length {
+                        if idx == digit_at || rng.chance(0.1) {
+                            line.push(char::from(b'0' + rng.range(1..=9) as u8));
+                        } else if rng.chance(0.1) {
+                            line.push_str(rng.pick(&DIGIT_WORDS));
+                        } else {
+                            line.push(char::from(b'a' + rng.range(0..=25) as u8));
+                        }
+                    }
+                    out.push_str(&line);
+                    out.push('\n');
+                }
+            }
+            Puzzle::Day2 { games, draws, cubes } => {
+                for game in 1..=games {
+                    let draws: Vec<String> = (0..rng.range(1..=draws.max(1) as u64))
+                        .map(|_| {
+                            let mut colors = ["red", "green", "blue"];
+                            rng.shuffle(&mut colors);
+                            let count = rng.range(1..=3) as usize;
+                            colors[..count]
+                                .iter()
+                                .map(|color| format!("{} {color}", rng.range(1..=cubes as u64)))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        })
+                        .collect();
+                    out.push_str(&format!("Game {game}: {}\n", draws.join("; ")));
+                }
+            }
+            Puzzle::Day3 { width, height, numbers, symbols } => {
+                for _ in 0..height {
+                    let mut row = String::new();
+                    while row.len() < width {
+                        let after_number = row.ends_with(|c: char| c.is_ascii_digit());
+                        let digits = rng.range(1..=3) as usize;
+                        if !after_number && row.len() + digits <= width && rng.chance(numbers) {
+                            let low = 10u64.pow(digits as u32 - 1);
+                            row.push_str(&rng.range(low..=low * 10 - 1).to_string());
+                        } else if rng.chance(symbols) {
+                            row.push(rng.pick(&['*', '#', '+', '$', '/', '@', '=', '%', '&', '-']));
+                        } else {
+                            row.push('.');
+                        }
+                    }
+                    out.push_str(&row);
+                    out.push('\n');
+                }
+            }
+            Puzzle::Day4 { cards, winning, have, matches } => {
+                let (winning, have) = (winning as usize, have as usize);
+                if winning + have > 99 {
+                    return Err("--winning plus --have must be at most 99".into());
+                }
+                let label_width = cards.to_string().len();
+                let list = |numbers: &[u64]| {
+                    numbers
+                        .iter()
+                        .map(|number| format!("{number:>2}"))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                };
+                for card in 1..=cards {
+                    let mut pool: Vec<u64> = (1..=99).collect();
+                    rng.shuffle(&mut pool);
+                    let (winners, others) = pool.split_at(winning);
+                    // Cards never win copies of cards past the end of the table
+                    let matched =
+                        winners.iter().filter(|_| rng.chance(matches)).count().min(cards - card);
+                    let mut yours = [&winners[..matched], &others[..have - matched]].concat();
+                    rng.shuffle(&mut yours);
+                    out.push_str(&format!(
+                        "Card {card:>label_width$}: {} | {}\n",
+                        list(winners),
+                        list(&yours)
+                    ));
+                }
+            }
+            Puzzle::Day5 { seeds, ranges, max_value } => {
+                let max_value = max_value.max(2);
+                let seeds: Vec<String> = (0..seeds)
+                    .map(|_| {
+                        let start = rng.range(0..=max_value - 1);
+                        let length = rng.range(1..=((max_value - start) / 16).max(1));
+                        format!("{start} {length}")
+                    })
+                    .collect();
+                out.push_str(&format!("seeds: {}\n", seeds.join(" ")));
+
+                let names = [
+                    "seed",
+                    "soil",
+                    "fertilizer",
+                    "water",
+                    "light",
+                    "temperature",
+                    "humidity",
+                    "location",
+                ];
+                for pair in names.windows(2) {
+                    out.push_str(&format!("\n{}-to-{} map:\n", pair[0], pair[1]));
+                    // Source ranges lie between sorted cut points, so they never overlap
+                    let mut cuts: Vec<u64> =
+                        (0..2 * ranges).map(|_| rng.range(0..=max_value)).collect();
+                    cuts.sort_unstable();
+                    cuts.dedup();
+                    for cut in cuts.chunks_exact(2) {
+                        let (source, length) = (cut[0], cut[1] - cut[0]);
+                        if length == 0 {
+                            continue;
+                        }
+                        let destination = rng.range(0..=max_value - length);
+                        out.push_str(&format!("{destination} {source} {length}\n"));
+                    }
+                }
+            }
+            Puzzle::Day6 { races, max_time } => {
+                let races: Vec<(u64, u64)> = (0..races)
+                    .map(|_| {
+                        let time = rng.range(2..=max_time.max(2));
+                        // Holding for half the race goes farthest, so every record can be beaten
+                        let best = (time / 2) * (time - time / 2);
+                        (time, rng.range(0..=best - 1))
+                    })
+                    .collect();
+                let width =
+                    races.iter().map(|(_, distance)| distance.to_string().len()).max().unwrap_or(0)
+                        + 3;
+                out.push_str("Time:    ");
+                for (time, _) in &races {
+                    out.push_str(&format!("{time:>width$}"));
+                }
+                out.push_str("\nDistance:");
+                for (_, distance) in &races {
+                    out.push_str(&format!("{distance:>width$}"));
+                }
+                out.push('\n');
+            }
+            Puzzle::Day7 { hands, max_bid } => {
+                let cards = b"23456789TJQKA";
+                let mut seen = HashSet::new();
+                // There are only 13^5 distinct hands
+                while seen.len() < hands.min(13usize.pow(5)) {
+                    let hand: String = (0..5).map(|_| rng.pick(cards) as char).collect();
+                    if seen.insert(hand.clone()) {
+                        out.push_str(&format!("{hand} {}\n", rng.range(1..=max_bid.max(1) as u64)));
+                    }
+                }
+            }
+            Puzzle::Day8 { instructions, nodes, ghosts } => {
+                let instructions: String =
+                    (0..instructions.max(1)).map(|_| rng.pick(&['L', 'R'])).collect();
+                out.push_str(&instructions);
+                out.push_str("\n\n");
+
+                let chain_length = (nodes / ghosts as usize).max(3);
+                // Names between a chain's ..A and ..Z nodes end in B to Y, which leaves 26 * 26 *
+                // 24 of them. Past that the loop picking an unused name would never finish.
+                if ghosts as usize * (chain_length - 2) > 26 * 26 * 24 {
+                    let msg =
+                        format!("--nodes {nodes} needs more three-letter names than there are");
+                    return Err(msg.into());
+                }
+                let mut names = HashSet::from([String::from("AAA"), String::from("ZZZ")]);
+                let mut lines = vec![];
+                for ghost in 0..ghosts {
+                    // Each node steps one to three nodes along its own chain, so every ghost
+                    // reaches its ..Z node, which leads back into the chain.
+                    let mut chain = vec![];
+                    for idx in 0..chain_length {
+                        let name = if ghost == 0 && idx == 0 {
+                            String::from("AAA")
+                        } else if ghost == 0 && idx == chain_length - 1 {
+                            String::from("ZZZ")
+                        } else {
+                            loop {
+                                let last = match idx {
+                                    0 => b'A',
+                                    _ if idx == chain_length - 1 => b'Z',
+                                    _ => b'B' + rng.range(0..=23) as u8,
+                                };
+                                let name: String = [
+                                    b'A' + rng.range(0..=25) as u8,
+                                    b'A' + rng.range(0..=25) as u8,
+                                    last,
+                                ]
+                                .iter()
+                                .map(|&b| b as char)
+                                .collect();
+                                if names.insert(name.clone()) {
+                                    break name;
+                                }
+                            }
+                        };
+                        chain.push(name);
+                    }
+                    for idx in 0..chain_length {
+                        let mut next = || {
+                            if idx == chain_length - 1 {
+                                &chain[rng.range(1..=idx as u64 - 1) as usize]
+                            } else {
+                                &chain[rng
+                                    .range(idx as u64 + 1..=(idx + 3).min(chain_length - 1) as u64)
+                                    as usize]
+                            }
+                        };
+                        let (left, right) = (next().clone(), next().clone());
+                        lines.push(format!("{} = ({left}, {right})\n", chain[idx]));
+                    }
+                }
+                rng.shuffle(&mut lines);
+                out.extend(lines);
+            }
+            Puzzle::Day9 { lines, values, max_degree } => {
+                for _ in 0..lines {
+                    // Values of a polynomial in the Newton basis, so the differences reach zero
+                    let degree = rng.range(0..=max_degree as u64) as usize;
+                    let coefficients: Vec<i64> =
+                        (0..=degree).map(|_| rng.range(0..=20) as i64 - 10).collect();
+                    let history: Option<Vec<String>> = (0..values as i64)
+                        .map(|x| {
+                            let mut binomial: i64 = 1;
+                            let mut value: i64 = 0;
+                            for (k, coefficient) in coefficients.iter().enumerate() {
+                                if k > 0 {
+                                    binomial = binomial.checked_mul(x - k as i64 + 1)? / k as i64;
+                                }
+                                value = value.checked_add(coefficient.checked_mul(binomial)?)?;
+                            }
+                            Some(value.to_string())
+                        })
+                        .collect();
+                    let history = history.ok_or_else(|| {
+                        format!("--values {values} with --max-degree {max_degree} overflows i64")
+                    })?;
+                    out.push_str(&history.join(" "));
+                    out.push('\n');
+                }
+            }
+            Puzzle::Day10 { width, height } => {
+                let (width, height) = (width as usize, height as usize);
+                let cells = ((width - 1) / 2) * ((height - 1) / 2);
+                // Grow the region over a random share of the cells so the loop varies in size
+                let steps = rng.index(cells);
+                let growth: Vec<usize> = (0..steps).map(|_| rng.next_u64() as usize).collect();
+                let filler: Vec<u8> = (0..width * height).map(|_| rng.pick(b"|-LJ7F.")).collect();
+                let (seed, start) = (rng.next_u64() as usize, rng.next_u64() as usize);
+                out = maze((width, height), seed, &growth, &filler, start);
+            }
+            Puzzle::Day11 { width, height, galaxies } => {
+                out = grid(rng, width, height, |rng| if rng.chance(galaxies) { '#' } else { '.' });
+            }
+            Puzzle::Day12 { rows, max_length, unknown } => {
+                for _ in 0..rows {
+                    // Start from a known arrangement so every row has at least one
+                    let length = rng.range(1..=max_length.max(1) as u64) as usize;
+                    let mut damaged: Vec<bool> = (0..length).map(|_| rng.chance(0.5)).collect();
+                    let forced = rng.index(length);
+                    damaged[forced] = true;
+                    let springs: String = damaged
+                        .iter()
+                        .map(|&damaged| match (damaged, rng.chance(unknown)) {
+                            (_, true) => '?',
+                            (true, false) => '#',
+                            (false, false) => '.',
+                        })
+                        .collect();
+                    let groups: Vec<String> = damaged
+                        .split(|damaged| !damaged)
+                        .filter(|group| !group.is_empty())
+                        .map(|group| group.len().to_string())
+                        .collect();
+                    out.push_str(&format!("{springs} {}\n", groups.join(",")));
+                }
+            }
+            Puzzle::Day13 { patterns, width, height } => {
+                let patterns: Vec<String> = (0..patterns)
+                    .map(|_| mirror_pattern(rng, width as usize, height as usize))
+                    .collect();
+                out = patterns.join("\n");
+            }
+            Puzzle::Day14 { width, height, rounded, cubes } => {
+                out = grid(rng, width, height, |rng| {
+                    if rng.chance(rounded) {
+                        'O'
+                    } else if rng.chance(cubes) {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                });
+            }
+            Puzzle::Day15 { steps, max_label } => {
+                // Draw labels from a small pool so lenses get replaced and removed
+                let pool: Vec<String> = (0..(steps / 4).max(1))
+                    .map(|_| {
+                        (0..rng.range(1..=max_label.max(1) as u64))
+                            .map(|_| (b'a' + rng.range(0..=25) as u8) as char)
+                            .collect()
+                    })
+                    .collect();
+                let steps: Vec<String> = (0..steps)
+                    .map(|_| {
+                        let label = &pool[rng.index(pool.len())];
+                        if rng.chance(0.3) {
+                            format!("{label}-")
+                        } else {
+                            format!("{label}={}", rng.range(1..=9))
+                        }
+                    })
+                    .collect();
+                out.push_str(&steps.join(","));
+                out.push('\n');
+            }
+            Puzzle::Day16 { width, height, mirrors } => {
+                out = grid(rng, width, height, |rng| {
+                    if rng.chance(mirrors) {
+                        rng.pick(&['/', '\\', '|', '-'])
+                    } else {
+                        '.'
+                    }
+                });
+            }
+            Puzzle::Day17 { width, height } => {
+                out = grid(rng, width, height, |rng| (b'0' + rng.range(1..=9) as u8) as char);
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn grid(
+    rng: &mut Rng,
+    width: usize,
+    height: usize,
+    mut tile: impl FnMut(&mut Rng) -> char,
+) -> String {
+    let mut out = String::with_capacity((width + 1) * height);
+    for _ in 0..height {
+        for _ in 0..width {
+            out.push(tile(rng));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Mismatched tile pairs when `rows` is folded between `line - 1` and `line`.
+fn mismatches(rows: &[Vec<bool>], line: usize) -> usize {
+    (0..line.min(rows.len() - line))
+        .map(|offset| {
+            let (above, below) = (&rows[line - 1 - offset], &rows[line + offset]);
+            above.iter().zip(below).filter(|(a, b)| a != b).count()
+        })
+        .sum()
+}
+
+fn transpose(rows: &[Vec<bool>]) -> Vec<Vec<bool>> {
+    (0..rows[0].len()).map(|col| rows.iter().map(|row| row[col]).collect()).collect()
+}
+
+/// A pattern with exactly one perfect reflection line and exactly one line that is off by a
+/// single smudge.
+fn mirror_pattern(rng: &mut Rng, max_width: usize, max_height: usize) -> String {
+    loop {
+        let width = rng.range(4..=max_width as u64) as usize;
+        let height = rng.range(4..=max_height as u64) as usize;
+        let mut rows: Vec<Vec<bool>> =
+            (0..height).map(|_| (0..width).map(|_| rng.chance(0.5)).collect()).collect();
+
+        // The perfect vertical line sits right of center, so the leftmost columns are not
+        // reflected and a smudge there leaves it intact.
+        let vertical = rng.range(width as u64 / 2 + 1..=width as u64 - 1) as usize;
+        for row in rows.iter_mut() {
+            for col in vertical..width {
+                row[col] = row[2 * vertical - 1 - col];
+            }
+        }
+        let horizontal = rng.range(1..=height as u64 - 1) as usize;
+        for row in horizontal..height.min(2 * horizontal) {
+            rows[row] = rows[2 * horizontal - 1 - row].clone();
+        }
+        let smudge_row =
+            rng.range((2 * horizontal).saturating_sub(height) as u64..=horizontal as u64 - 1);
+        let smudge_col = rng.index(2 * vertical - width);
+        rows[smudge_row as usize][smudge_col] ^= true;
+
+        if rng.chance(0.5) {
+            rows = transpose(&rows);
+        }
+        let columns = transpose(&rows);
+        let lines: Vec<usize> = (1..rows.len())
+            .map(|line| mismatches(&rows, line))
+            .chain((1..columns.len()).map(|line| mismatches(&columns, line)))
+            .collect();
+        // Random tiles can line up into extra reflections; start over when they do
+        if lines.iter().filter(|&&count| count == 0).count() != 1
+            || lines.iter().filter(|&&count| count == 1).count() != 1
+        {
+            continue;
+        }
+
+        return rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&rock| if rock { '#' } else { '.' })
+                    .chain(['\n'])
+                    .collect::<String>()
+            })
+            .collect();
+    }
+}
+
+fn in_region(region: &[bool], cols: usize, rows: usize, x: isize, y: isize) -> bool {
+    let on_grid = x >= 0 && y >= 0 && x < cols as isize && y < rows as isize;
+    on_grid && region[y as usize * cols + x as usize]
+}
+
+/// Whether adding `cell` to `region` keeps it free of holes and of cells touching only at a
+/// corner, so that its outline stays a single loop.
+fn keeps_outline_simple(region: &[bool], cols: usize, rows: usize, cell: usize) -> bool {
+    let (x, y) = ((cell % cols) as isize, (cell / cols) as isize);
+    let ring = [(-1, -1), (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0)]
+        .map(|(dx, dy)| in_region(region, cols, rows, x + dx, y + dy));
+    let runs = (0..8).filter(|&i| ring[i] && !ring[(i + 7) % 8]).count();
+    let corner_only = (0..8).step_by(2).any(|i| ring[i] && !ring[(i + 7) % 8] && !ring[i + 1]);
+    runs == 1 && !corner_only
+}
+
+/// A `width` x `height` pipe maze whose only loop runs around a region of cells grown from
+/// `seed`, with each entry of `growth` picking the next cell to add from the cells bordering it.
+/// `S` is placed on the loop by `start` and every other tile is taken from `filler`.
+pub(crate) fn maze(
+    (width, height): (usize, usize),
+    seed: usize,
+    growth: &[usize],
+    filler: &[u8],
+    start: usize,
+) -> String {
+    let (cols, rows) = ((width - 1) / 2, (height - 1) / 2);
+    let mut region = vec![false; cols * rows];
+    // The cells bordering the region, kept up to date as it grows instead of searched for
+    let mut frontier = Vec::new();
+    let mut on_frontier = vec![false; cols * rows];
+    let mut picks = growth.iter();
+    let mut next = Some(seed % (cols * rows));
+    while let Some(cell) = next {
+        region[cell] = true;
+        let (x, y) = (cell % cols, cell / cols);
+        let neighbors = [
+            (x > 0, cell.wrapping_sub(1)),
+            (x + 1 < cols, cell + 1),
+            (y > 0, cell.wrapping_sub(cols)),
+            (y + 1 < rows, cell + cols),
+        ];
+        for (_, neighbor) in neighbors.into_iter().filter(|&(on_grid, _)| on_grid) {
+            if !region[neighbor] && !on_frontier[neighbor] {
+                on_frontier[neighbor] = true;
+                frontier.push(neighbor);
+            }
+        }
+
+        // Take the picked cell, or the next one after it that keeps the outline simple
+        next = picks.next().and_then(|pick| {
+            let len = frontier.len();
+            let found = (0..len)
+                .map(|offset| (pick % len + offset) % len)
+                .find(|&idx| keeps_outline_simple(&region, cols, rows, frontier[idx]))?;
+            Some(frontier.swap_remove(found))
+        });
+    }
+    let inside = |x, y| in_region(&region, cols, rows, x, y);
+
+    // Cell corners land on even tiles and the outline edges between them on the odd tiles in
+    // between. Each tile records which of its north, east, south and west sides it connects.
+    let mut links = vec![[false; 4]; width * height];
+    for vy in 0..=rows as isize {
+        for vx in 0..=cols as isize {
+            let (tx, ty) = (2 * vx as usize, 2 * vy as usize);
+            if vx < cols as isize && inside(vx, vy - 1) != inside(vx, vy) {
+                links[ty * width + tx][1] = true;
+                links[ty * width + tx + 1] = [false, true, false, true];
+                links[ty * width + tx + 2][3] = true;
+            }
+            if vy < rows as isize && inside(vx - 1, vy) != inside(vx, vy) {
+                links[ty * width + tx][2] = true;
+                links[(ty + 1) * width + tx] = [true, false, true, false];
+                links[(ty + 2) * width + tx][0] = true;
+            }
+        }
+    }
+
+    let mut tiles: Vec<u8> = links
+        .iter()
+        .enumerate()
+        .map(|(idx, link)| match link {
+            [true, false, true, false] => b'|',
+            [false, true, false, true] => b'-',
+            [true, true, false, false] => b'L',
+            [true, false, false, true] => b'J',
+            [false, false, true, true] => b'7',
+            [false, true, true, false] => b'F',
+            _ => filler.get(idx % filler.len().max(1)).copied().unwrap_or(b'.'),
+        })
+        .collect();
+
+    // Blank the tiles around S that are not on the loop so S has exactly two connections.
+    let on_loop: Vec<usize> = (0..tiles.len()).filter(|&idx| links[idx].contains(&true)).collect();
+    let s = on_loop[start % on_loop.len()];
+    tiles[s] = b'S';
+    let (sx, sy) = (s % width, s / width);
+    let (west, north) = (sx.wrapping_sub(1), sy.wrapping_sub(1));
+    for (x, y) in [(sx, north), (sx + 1, sy), (sx, sy + 1), (west, sy)] {
+        if x < width && y < height && !links[y * width + x].contains(&true) {
+            tiles[y * width + x] = b'.';
+        }
+    }
+
+    let lines: Vec<u8> = tiles.chunks(width).flat_map(|row| [row, b"\n"].concat()).collect();
+    String::from_utf8(lines).unwrap()
+}
+
+impl CommandImpl for Generate {
+    fn main(&mut self) -> Result<(), DynError> {
+        let input = self.puzzle.generate(&mut Rng(self.seed))?;
+        io::stdout().lock().write_all(input.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{day10a, day10b, day13a::PatternReader};
+
+    fn generate(args: &[&str]) -> Result<String, DynError> {
+        let command = Generate::parse_from(["generate"].iter().chain(args));
+        command.puzzle.generate(&mut Rng(command.seed))
+    }
+
+    #[test]
+    fn seed_reproduces_input() {
+        let args = ["--seed", "7", "day12", "--rows", "50"];
+        assert_eq!(generate(&args).unwrap(), generate(&args).unwrap());
+        assert_ne!(
+            generate(&args).unwrap(),
+            generate(&["--seed", "8", "day12", "--rows", "50"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn mazes_have_a_loop_through_s() {
+        for seed in 0..20 {
+            let seed = seed.to_string();
+            let maze =
+                generate(&["--seed", &seed, "day10", "--width", "24", "--height", "17"]).unwrap();
+            assert_eq!(maze.lines().count(), 17);
+            assert!(maze.lines().all(|line| line.len() == 24));
+            assert!(day10a::farthest(maze.as_bytes()).unwrap() > 0);
+            day10b::enclosed(maze.as_bytes(), true).unwrap();
+        }
+    }
+
+    #[test]
+    fn mazes_vary_with_the_seed() {
+        let farthest: Vec<usize> = (0..10)
+            .map(|seed| {
+                let maze = generate(&["--seed", &seed.to_string(), "day10"]).unwrap();
+                day10a::farthest(maze.as_bytes()).unwrap()
+            })
+            .collect();
+        assert!(farthest.iter().any(|&steps| steps != farthest[0]));
+
+        let big = generate(&["day10", "--width", "1000", "--height", "1000"]).unwrap();
+        assert!(day10a::farthest(big.as_bytes()).unwrap() > 0);
+    }
+
+    #[test]
+    fn patterns_have_one_clean_and_one_smudged_line() {
+        let input = generate(&["--seed", "3", "day13", "--patterns", "20"]).unwrap();
+        for pattern in PatternReader::new(input.into_bytes()) {
+            let pattern = pattern.unwrap();
+            let mismatches: Vec<u32> = pattern.candidates().map(|line| line.mismatches).collect();
+            assert_eq!(mismatches.iter().filter(|&&count| count == 0).count(), 1);
+            assert_eq!(mismatches.iter().filter(|&&count| count == 1).count(), 1);
+        }
+    }
+
+    #[test]
+    fn scratchcard_lists_must_fit() {
+        assert!(generate(&["day4", "--winning", "50", "--have", "50"]).is_err());
+    }
+
+    #[test]
+    fn network_names_must_fit() {
+        let input = generate(&["day8", "--nodes", "1000"]).unwrap();
+        assert_eq!(input.lines().count(), 2 + 6 * (1000 / 6));
+        assert!(generate(&["day8", "--nodes", "100000"]).is_err());
+    }
+
+    #[test]
+    fn histories_must_fit_in_i64() {
+        let input = generate(&["day9", "--values", "21", "--max-degree", "10"]).unwrap();
+        assert!(input.lines().all(|line| line.split(' ').count() == 21));
+        assert!(generate(&["day9", "--values", "10000", "--max-degree", "40"]).is_err());
+    }
+}
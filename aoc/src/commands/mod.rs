@@ -9,8 +9,8 @@ pub mod day6a;
 pub mod day6b;
 pub mod day7a;
 pub mod day7b;
-// pub mod day8a;
-// pub mod day8b;
+pub mod day8a;
+pub mod day8b;
 pub mod day9a;
 pub mod day9b;
 pub mod day10a;
@@ -28,6 +28,8 @@ pub mod day15b;
 pub mod day16a;
 pub mod day16b;
 pub mod day17a;
+pub mod day17b;
+pub mod generate;
 
 use std::error::Error;
 
@@ -1,6 +1,10 @@
+pub mod animation;
 #[allow(unused)]
 pub mod commands;
+pub mod render;
 pub mod utils;
+#[cfg(test)]
+mod testing;
 
 use commands::*;
 use enum_dispatch::enum_dispatch;
@@ -27,8 +31,8 @@ enum SubCommand {
     Day6b(day6b::Day6b),
     Day7a(day7a::Day7a),
     Day7b(day7b::Day7b),
-    // Day8a(day8a::Day8a),
-    // Day8b(day8b::Day8b),
+    Day8a(day8a::Day8a),
+    Day8b(day8b::Day8b),
     Day9a(day9a::Day9a),
     Day9b(day9b::Day9b),
     Day10a(day10a::Day10a),
@@ -46,6 +50,8 @@ enum SubCommand {
     Day16a(day16a::Day16a),
     Day16b(day16b::Day16b),
     Day17a(day17a::Day17a),
+    Day17b(day17b::Day17b),
+    Generate(generate::Generate),
 }
 fn main() -> Result<(), DynError> {
     let mut opts = Opts::parse();
@@ -0,0 +1,334 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, ValueEnum};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0, 0, 0);
+    pub const WHITE: Rgb = Rgb(255, 255, 255);
+}
+
+/// A grid that can be drawn as an image, one colour per cell.
+pub trait Render {
+    /// Width and height in cells.
+    fn size(&self) -> (usize, usize);
+
+    fn color(&self, x: usize, y: usize) -> Rgb;
+
+    /// The grid as an image, each cell drawn as a `scale` by `scale` square.
+    fn canvas(&self, scale: usize) -> Canvas {
+        let (width, height) = self.size();
+        Canvas::from_fn(width, height, scale, |x, y| self.color(x, y))
+    }
+}
+
+/// An RGB image, row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Rgb>,
+}
+
+/// The image formats `Canvas::save` can write, picked by file extension.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Ppm,
+    Png,
+}
+
+impl Format {
+    pub fn of(path: &Path) -> io::Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("ppm") => Ok(Format::Ppm),
+            Some(extension) if extension.eq_ignore_ascii_case("png") => Ok(Format::Png),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} should end in .ppm or .png", path.display()),
+            )),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Ppm => "ppm",
+            Format::Png => "png",
+        }
+    }
+}
+
+impl Canvas {
+    /// A `width` by `height` grid of cells coloured by `color`, each drawn `scale` pixels wide.
+    pub fn from_fn<F>(width: usize, height: usize, scale: usize, mut color: F) -> Self
+    where
+        F: FnMut(usize, usize) -> Rgb,
+    {
+        let scale = scale.max(1);
+        let mut pixels = Vec::with_capacity(width * height * scale * scale);
+        for y in 0..height {
+            let row: Vec<Rgb> = (0..width).map(|x| color(x, y)).collect();
+            for _ in 0..scale {
+                for &cell in &row {
+                    pixels.extend(std::iter::repeat(cell).take(scale));
+                }
+            }
+        }
+        Self { width: width * scale, height: height * scale, pixels }
+    }
+
+    fn bytes(&self) -> impl Iterator<Item = [u8; 3]> + '_ {
+        self.pixels.iter().map(|&Rgb(red, green, blue)| [red, green, blue])
+    }
+
+    /// Binary PPM, which almost any image viewer opens.
+    pub fn write_ppm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in self.bytes() {
+            out.write_all(&pixel)?;
+        }
+        Ok(())
+    }
+
+    /// PNG with its image data stored uncompressed, which keeps the encoder to a few lines and
+    /// needs no dependencies.
+    pub fn write_png<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "image is too large");
+        let width = u32::try_from(self.width).map_err(|_| too_large())?;
+        let height = u32::try_from(self.height).map_err(|_| too_large())?;
+
+        // Each row starts with filter type 0, meaning none
+        let mut raw = Vec::with_capacity((self.width * 3 + 1) * self.height);
+        for row in self.pixels.chunks(self.width.max(1)) {
+            raw.push(0);
+            raw.extend(row.iter().flat_map(|&Rgb(red, green, blue)| [red, green, blue]));
+        }
+
+        // A zlib stream of stored deflate blocks, each holding at most 65535 bytes
+        let mut zlib = vec![0x78, 0x01];
+        let mut blocks = raw.chunks(0xffff).peekable();
+        if blocks.peek().is_none() {
+            zlib.extend([1, 0, 0, 0xff, 0xff]);
+        }
+        while let Some(block) = blocks.next() {
+            let len = block.len() as u16;
+            zlib.push(blocks.peek().is_none() as u8);
+            zlib.extend(len.to_le_bytes());
+            zlib.extend((!len).to_le_bytes());
+            zlib.extend(block);
+        }
+        zlib.extend(adler32(&raw).to_be_bytes());
+
+        let mut header = Vec::with_capacity(13);
+        header.extend(width.to_be_bytes());
+        header.extend(height.to_be_bytes());
+        // 8 bits per channel, RGB, default compression and filtering, no interlacing
+        header.extend([8, 2, 0, 0, 0]);
+
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+        write_chunk(out, b"IHDR", &header)?;
+        write_chunk(out, b"IDAT", &zlib)?;
+        write_chunk(out, b"IEND", &[])
+    }
+
+    /// Write the image to `path` as PPM or PNG, going by its extension.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let format = Format::of(path)?;
+        let mut out = BufWriter::new(File::create(path)?);
+        match format {
+            Format::Ppm => self.write_ppm(&mut out)?,
+            Format::Png => self.write_png(&mut out)?,
+        }
+        out.flush()
+    }
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = !crc32(kind.iter().chain(data).copied(), !0);
+    out.write_all(&crc.to_be_bytes())
+}
+
+fn crc32(bytes: impl Iterator<Item = u8>, mut crc: u32) -> u32 {
+    for byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
+
+/// Numbered images in a directory, `frame-00000.png` and on, one per step of a simulation.
+///
+/// Like `Animation`, a write error is kept and returned by `finish` so the simulations only
+/// hand frames over, and no more frames are written after it.
+pub struct Frames {
+    dir: PathBuf,
+    format: Format,
+    scale: usize,
+    count: usize,
+    error: Option<io::Error>,
+}
+
+impl Frames {
+    pub fn new(dir: &Path, format: Format, scale: usize) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self { dir: dir.to_owned(), format, scale, count: 0, error: None })
+    }
+
+    pub fn frame(&mut self, grid: &impl Render) {
+        if self.error.is_some() {
+            return;
+        }
+        let name = format!("frame-{:05}.{}", self.count, self.format.extension());
+        match grid.canvas(self.scale).save(&self.dir.join(name)) {
+            Ok(()) => self.count += 1,
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// How many frames have been written.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        self.error.map_or(Ok(()), Err)
+    }
+}
+
+/// `--image` and `--scale`, for the days whose grids can be drawn.
+#[derive(Args, Debug, Clone)]
+pub struct ImageOptions {
+    /// Write the grid to an image file, PPM or PNG going by its extension
+    #[clap(long, value_name = "FILE")]
+    pub image: Option<PathBuf>,
+    /// Pixels per grid cell across and down
+    #[clap(long, value_name = "N", default_value = "4")]
+    pub scale: NonZeroUsize,
+}
+
+impl ImageOptions {
+    /// Write `grid` to the `--image` file, if there is one.
+    pub fn save(&self, grid: &impl Render) -> io::Result<()> {
+        match &self.image {
+            Some(path) => grid.canvas(self.scale.get()).save(path),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `ImageOptions` plus `--frames`, for the days that simulate a grid step by step.
+#[derive(Args, Debug, Clone)]
+pub struct RenderOptions {
+    #[clap(flatten)]
+    pub image: ImageOptions,
+    /// Write every step of the simulation as a numbered image in this directory
+    #[clap(long, value_name = "DIR")]
+    pub frames: Option<PathBuf>,
+    /// Format of the images written by --frames
+    #[clap(long, value_enum, default_value = "png")]
+    pub frame_format: Format,
+}
+
+impl RenderOptions {
+    /// Whether `--image` asks for the final grid to be drawn.
+    pub fn wants_image(&self) -> bool {
+        self.image.image.is_some()
+    }
+
+    pub fn save(&self, grid: &impl Render) -> io::Result<()> {
+        self.image.save(grid)
+    }
+
+    /// The frame sequence to write to, or `None` without `--frames`.
+    pub fn frames(&self) -> io::Result<Option<Frames>> {
+        let (format, scale) = (self.frame_format, self.image.scale.get());
+        self.frames.as_deref().map(|dir| Frames::new(dir, format, scale)).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Checkers;
+
+    impl Render for Checkers {
+        fn size(&self) -> (usize, usize) {
+            (3, 2)
+        }
+
+        fn color(&self, x: usize, y: usize) -> Rgb {
+            if (x + y) % 2 == 0 {
+                Rgb::WHITE
+            } else {
+                Rgb(200, 0, 0)
+            }
+        }
+    }
+
+    #[test]
+    fn scaled_ppm() {
+        let canvas = Checkers.canvas(2);
+        assert_eq!((canvas.width, canvas.height), (6, 4));
+        assert_eq!(
+            canvas.pixels[..6],
+            [Rgb::WHITE, Rgb::WHITE, Rgb(200, 0, 0), Rgb(200, 0, 0), Rgb::WHITE, Rgb::WHITE]
+        );
+        assert_eq!(canvas.pixels[6..12], canvas.pixels[..6]);
+        let mut ppm = Vec::new();
+        canvas.write_ppm(&mut ppm).unwrap();
+        assert!(ppm.starts_with(b"P6\n6 4\n255\n\xff\xff\xff"));
+        assert_eq!(ppm.len(), b"P6\n6 4\n255\n".len() + 6 * 4 * 3);
+    }
+
+    #[test]
+    fn png_layout() {
+        let mut png = Vec::new();
+        Checkers.canvas(1).write_png(&mut png).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x03\0\0\0\x02\x08\x02"));
+        assert!(png.ends_with(b"\0\0\0\0IEND\xae\x42\x60\x82"));
+        // Two rows of a filter byte and three pixels, stored in a single final block
+        let idat = png.windows(4).position(|window| window == b"IDAT").unwrap();
+        assert_eq!(&png[idat - 4..idat], &(2u32 + 5 + 20 + 4).to_be_bytes());
+        assert_eq!(&png[idat + 4..idat + 11], &[0x78, 0x01, 1, 20, 0, !20, 0xff]);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(!crc32(b"123456789".iter().copied(), !0), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn frames_and_formats() {
+        let dir = std::env::temp_dir().join(format!("aoc-frames-{}", std::process::id()));
+        let mut frames = Frames::new(&dir, Format::Ppm, 1).unwrap();
+        frames.frame(&Checkers);
+        frames.frame(&Checkers);
+        assert_eq!(frames.count(), 2);
+        frames.finish().unwrap();
+        assert!(dir.join("frame-00001.ppm").exists());
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(Format::of(Path::new("out.PNG")).unwrap(), Format::Png);
+        assert_eq!(Format::of(Path::new("out.ppm")).unwrap(), Format::Ppm);
+        assert!(Format::of(Path::new("out.gif")).is_err());
+        assert!(Format::of(Path::new("out")).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+use std::{fs, panic, path::PathBuf, sync::mpsc, thread, time::Duration};
+
+use proptest::{collection::vec, prelude::*, sample::select, test_runner::TestCaseError};
+
+use crate::utils::ParseError;
+
+/// Read a puzzle input from `aoc/inputs/`, or `None` when the file is not checked out.
+///
+/// Real puzzle inputs are personal and may be missing from a clone, so regression tests skip
+/// instead of failing when their input is absent.
+pub fn read_input(name: &str) -> Option<Vec<u8>> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("inputs").join(name);
+    fs::read(path).ok()
+}
+
+/// Generate `#[test]` cases for a day's solvers.
+///
+/// Each block is named after a function in scope taking `&[u8]` and returning the answer. Inside
+/// it, `example` entries hold inline puzzle-statement text and `input` entries name a file in
+/// `aoc/inputs/`, which is skipped when absent:
+///
+/// ```ignore
+/// puzzle_tests! {
+///     part_one {
+///         example statement: "0 3 6 9 12 15\n" => 18;
+///         input real: "day9.txt" => 1789635132;
+///     }
+/// }
+/// ```
+macro_rules! puzzle_tests {
+    ($($solver:ident { $($(#[$meta:meta])* $kind:ident $name:ident: $input:expr => $expected:expr;)* })*) => {
+        $(
+            mod $solver {
+                use super::*;
+
+                $(
+                    $crate::testing::puzzle_test!($(#[$meta])* $kind $name, $solver, $input, $expected);
+                )*
+            }
+        )*
+    };
+}
+
+macro_rules! puzzle_test {
+    ($(#[$meta:meta])* example $name:ident, $solver:ident, $input:expr, $expected:expr) => {
+        #[test]
+        $(#[$meta])*
+        fn $name() {
+            assert_eq!(super::$solver($input.as_bytes()), $expected);
+        }
+    };
+    ($(#[$meta:meta])* input $name:ident, $solver:ident, $file:expr, $expected:expr) => {
+        #[test]
+        $(#[$meta])*
+        fn $name() {
+            match $crate::testing::read_input($file) {
+                Some(bytes) => assert_eq!(super::$solver(&bytes), $expected),
+                None => eprintln!(
+                    "skipping {}::{}: aoc/inputs/{} not found",
+                    module_path!(),
+                    stringify!($name),
+                    $file
+                ),
+            }
+        }
+    };
+}
+
+/// Bytes for robustness tests: arbitrary bytes, prefixes of `example`, strings drawn from the
+/// bytes `example` uses, and copies of `example` with bytes replaced, inserted or removed.
+pub fn fuzz_input(example: &'static str) -> impl Strategy<Value = Vec<u8>> {
+    let example = example.as_bytes();
+    let mut alphabet = example.to_vec();
+    alphabet.sort_unstable();
+    alphabet.dedup();
+
+    let edit = (any::<prop::sample::Index>(), 0..3u8, any::<u8>());
+    prop_oneof![
+        vec(any::<u8>(), 0..64),
+        (0..=example.len()).prop_map(move |length| example[..length].to_vec()),
+        vec(select(alphabet), 0..2 * example.len()),
+        vec(edit, 1..4).prop_map(move |edits| {
+            let mut bytes = example.to_vec();
+            for (position, kind, byte) in edits {
+                let position = position.index(bytes.len() + 1);
+                match kind {
+                    0 if position < bytes.len() => bytes[position] = byte,
+                    1 if position < bytes.len() => {
+                        bytes.remove(position);
+                    }
+                    _ => bytes.insert(position, byte),
+                }
+            }
+            bytes
+        }),
+    ]
+}
+
+/// Run `parse` on `input` in its own thread and fail if it panics or is still running after a
+/// few seconds. The result itself does not matter as long as one is returned.
+pub fn check_parser<T: 'static>(
+    input: Vec<u8>,
+    parse: fn(&[u8]) -> Result<T, ParseError>,
+) -> Result<(), TestCaseError> {
+    let (done, finished) = mpsc::channel();
+    thread::spawn(move || {
+        let outcome = panic::catch_unwind(|| parse(&input).map(|_| ())).map_err(|payload| {
+            let msg = payload.downcast_ref::<&str>().map(|msg| msg.to_string());
+            msg.or_else(|| payload.downcast_ref::<String>().cloned()).unwrap_or_default()
+        });
+        let _ = done.send(outcome);
+    });
+    match finished.recv_timeout(Duration::from_secs(5)) {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(msg)) => Err(TestCaseError::fail(format!("parser panicked: {msg}"))),
+        Err(_) => Err(TestCaseError::fail("parser did not finish")),
+    }
+}
+
+/// Generate property tests feeding `fuzz_input` built from an example to parser functions in
+/// scope, each taking `&[u8]` and returning `Result<_, ParseError>`:
+///
+/// ```ignore
+/// robustness_tests! {
+///     parse: EXAMPLE;
+/// }
+/// ```
+macro_rules! robustness_tests {
+    ($($parse:ident: $example:expr;)*) => {
+        mod robustness {
+            use proptest::prelude::*;
+
+            // Examples are usually constants from the enclosing test module
+            #[allow(unused_imports)]
+            use super::*;
+
+            proptest! {
+                $(
+                    #[test]
+                    fn $parse(input in $crate::testing::fuzz_input($example)) {
+                        $crate::testing::check_parser(input, super::$parse)?;
+                    }
+                )*
+            }
+        }
+    };
+}
+
+pub(crate) use puzzle_test;
+pub(crate) use puzzle_tests;
+pub(crate) use robustness_tests;
@@ -2,11 +2,13 @@ use std::{
     error::Error,
     fmt::{self, Debug},
     fs::File,
-    io::{BufRead, BufReader, Read},
-    path::Path,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
+use clap::Args;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Direction {
     North,
@@ -56,6 +58,102 @@ where
     Ok(result)
 }
 
+/// Puzzle input sources shared by every day's subcommand.
+///
+/// Each value may be a file path, a glob pattern such as `aoc/inputs/day5*.txt`, or `-` to read
+/// from stdin. The solver is run once per resolved source.
+#[derive(Args, Debug, Clone)]
+pub struct Inputs {
+    /// Input files, glob patterns, or `-` for stdin
+    #[clap(long = "input", short = 'i', required = true, num_args = 1..)]
+    paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputSource {
+    Stdin,
+    File(PathBuf),
+}
+
+impl InputSource {
+    pub fn read(&self) -> Result<Vec<u8>, SlurpError> {
+        match self {
+            InputSource::Stdin => {
+                let mut bytes = Vec::new();
+                io::stdin()
+                    .lock()
+                    .read_to_end(&mut bytes)
+                    .map_err(|e| SlurpError { line: 0, msg: e.to_string() })?;
+                Ok(bytes)
+            }
+            InputSource::File(path) => {
+                let file = File::open(path)
+                    .map_err(|e| SlurpError { line: 0, msg: format!("{}: {e}", path.display()) })?;
+                let mut bytes = Vec::new();
+                BufReader::new(file)
+                    .read_to_end(&mut bytes)
+                    .map_err(|e| SlurpError { line: 0, msg: e.to_string() })?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+impl fmt::Display for InputSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputSource::Stdin => write!(f, "<stdin>"),
+            InputSource::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl Inputs {
+    /// Resolve `-` and glob patterns into the list of sources to run, in the order given
+    pub fn sources(&self) -> Result<Vec<InputSource>, Box<dyn Error>> {
+        let mut sources = Vec::new();
+        for path in &self.paths {
+            let pattern = path.to_string_lossy();
+            if pattern == "-" {
+                // Stdin can only be read once, so a second `-` would silently get no input
+                if sources.contains(&InputSource::Stdin) {
+                    return Err("'-' can only be given once, stdin is read to the end".into());
+                }
+                sources.push(InputSource::Stdin);
+            } else if pattern.contains(['*', '?', '[']) {
+                let mut matches = glob::glob(&pattern)?.collect::<Result<Vec<_>, _>>()?;
+                if matches.is_empty() {
+                    return Err(format!("no input files match '{pattern}'").into());
+                }
+                matches.sort();
+                sources.extend(matches.into_iter().map(InputSource::File));
+            } else {
+                sources.push(InputSource::File(path.clone()));
+            }
+        }
+        Ok(sources)
+    }
+
+    /// Run `solve` once per input, labelling each result when there is more than one input
+    pub fn for_each<F>(&self, mut solve: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(Vec<u8>) -> Result<(), Box<dyn Error>>,
+    {
+        let sources = self.sources()?;
+        let label = sources.len() > 1;
+        for (idx, source) in sources.iter().enumerate() {
+            if label {
+                if idx > 0 {
+                    println!();
+                }
+                println!("==> {source} <==");
+            }
+            solve(source.read()?)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ParseError {
     msg: String,
@@ -84,35 +182,51 @@ impl AsciiReader {
         Self { buffer, index: 0 }
     }
 
+    /// Read a possibly negative number after any spaces, then skip the spaces and commas that
+    /// follow it. Returns `None`, without moving, at a newline, at the end of the buffer, when no
+    /// digits follow, or when the number does not fit in an `i64`.
     pub fn read_next_number(&mut self) -> Option<i64> {
+        let start = self.index;
         if self.index >= self.buffer.len() || self.buffer[self.index] == b'\n' {
             return None;
         }
 
-        while self.buffer[self.index] == b' ' {
+        while self.index < self.buffer.len() && self.buffer[self.index] == b' ' {
             self.index += 1;
         }
 
-        let is_negative = if self.buffer[self.index] == b'-' {
+        let is_negative = if self.index < self.buffer.len() && self.buffer[self.index] == b'-' {
             self.index += 1;
             true
         } else {
             false
         };
 
+        let digits_start = self.index;
         let mut num: i64 = 0;
-        while self.buffer[self.index].is_ascii_digit() {
-            num *= 10;
-            num += (self.buffer[self.index] - b'0') as i64;
+        while self.index < self.buffer.len() && self.buffer[self.index].is_ascii_digit() {
+            let digit = (self.buffer[self.index] - b'0') as i64;
+            let Some(next) = num.checked_mul(10).and_then(|num| num.checked_add(digit)) else {
+                self.index = start;
+                return None;
+            };
+            num = next;
             self.index += 1;
         }
 
-        while self.buffer[self.index] == b' ' || self.buffer[self.index] == b',' {
+        if self.index == digits_start {
+            self.index = start;
+            return None;
+        }
+
+        while self.index < self.buffer.len()
+            && (self.buffer[self.index] == b' ' || self.buffer[self.index] == b',')
+        {
             self.index += 1;
         }
 
         if is_negative {
-            num = num.wrapping_neg();
+            num = -num;
         }
         // *read_idx += 1;
         Some(num)
@@ -133,7 +247,7 @@ impl AsciiReader {
         }
         let end = self.index;
 
-        self.index += 1;
+        self.index = (end + 1).min(self.buffer.len());
 
         Some(&self.buffer[start..end])
     }
@@ -143,13 +257,12 @@ impl AsciiReader {
             return None;
         }
 
+        // Leave the reader where it was when `char` never comes
+        let length = self.buffer[self.index..].iter().position(|&byte| byte == char)?;
         let start = self.index;
-        while self.buffer[self.index] != char {
-            self.index += 1;
-        }
-        let end = self.index;
+        self.index += length;
 
-        Some(&self.buffer[start..end])
+        Some(&self.buffer[start..self.index])
     }
 
     pub fn skip(&mut self, amount: usize) {
@@ -161,8 +274,8 @@ impl AsciiReader {
     }
 
     pub fn next(&mut self, amount: usize) -> &[u8] {
-        let start = self.index;
-        let end = start + amount;
+        let start = self.index.min(self.buffer.len());
+        let end = (start + amount).min(self.buffer.len());
         self.index = end;
 
         &self.buffer[start..end]
@@ -179,4 +292,126 @@ impl AsciiReader {
     pub fn len(&self) -> usize {
         self.buffer.len()
     }
-}
\ No newline at end of file
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// The byte at the current position, or `None` past the end of the buffer.
+    pub fn peek(&self) -> Option<u8> {
+        self.buffer.get(self.index).copied()
+    }
+
+    /// Consume `expected` if the buffer continues with it.
+    pub fn expect(&mut self, expected: &[u8]) -> Result<(), ParseError> {
+        if !self.buffer[self.index.min(self.buffer.len())..].starts_with(expected) {
+            return Err(self.error(&format!("expected {:?}", String::from_utf8_lossy(expected))));
+        }
+        self.index += expected.len();
+        Ok(())
+    }
+
+    /// A `ParseError` pointing at the current position.
+    pub fn error(&self, msg: &str) -> ParseError {
+        ParseError::new(format!("{msg} at byte {}", self.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+    use crate::testing::robustness_tests;
+
+    #[derive(Debug, Clone)]
+    enum Read {
+        Number,
+        Line,
+        To(u8),
+        Until(u8),
+        Next(usize),
+        Skip(usize),
+        Expect(Vec<u8>),
+    }
+
+    fn read() -> impl Strategy<Value = Read> {
+        let byte = prop_oneof![Just(b' '), Just(b','), Just(b'\n'), any::<u8>()];
+        prop_oneof![
+            Just(Read::Number),
+            Just(Read::Line),
+            byte.clone().prop_map(Read::To),
+            byte.prop_map(Read::Until),
+            (0..8usize).prop_map(Read::Next),
+            (0..8usize).prop_map(Read::Skip),
+            vec(any::<u8>(), 0..3).prop_map(Read::Expect),
+        ]
+    }
+
+    /// Walk a whole buffer the way the day parsers do, stopping only at the end
+    fn read_everything(input: &[u8]) -> Result<usize, ParseError> {
+        let mut reader = AsciiReader::new(input.to_vec());
+        let mut lines = 0;
+        while !reader.eof() {
+            while reader.read_next_number().is_some() {}
+            let _ = reader.expect(b"=");
+            reader.read_until(b' ');
+            reader.read_line();
+            lines += 1;
+        }
+        Ok(lines)
+    }
+
+    robustness_tests! {
+        read_everything: "Time: 7 15\nab=-5, 12\n\n";
+    }
+
+    #[test]
+    fn stdin_only_once() {
+        let inputs = |paths: &[&str]| Inputs { paths: paths.iter().map(PathBuf::from).collect() };
+        assert_eq!(
+            inputs(&["-", "a.txt"]).sources().unwrap(),
+            [InputSource::Stdin, InputSource::File(PathBuf::from("a.txt"))]
+        );
+        assert!(inputs(&["-", "a.txt", "-"]).sources().is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn reads_stay_in_bounds(buffer in vec(any::<u8>(), 0..32), reads in vec(read(), 0..16)) {
+            let mut reader = AsciiReader::new(buffer);
+            for read in reads {
+                let before = reader.index;
+                match read {
+                    Read::Number => {
+                        if reader.read_next_number().is_none() {
+                            prop_assert_eq!(reader.index, before);
+                        }
+                    }
+                    Read::Line => {
+                        reader.read_line();
+                    }
+                    Read::To(byte) => {
+                        reader.read_to(byte);
+                    }
+                    Read::Until(byte) => {
+                        if reader.read_until(byte).is_none() {
+                            prop_assert_eq!(reader.index, before);
+                        }
+                    }
+                    Read::Next(amount) => {
+                        reader.next(amount);
+                    }
+                    Read::Skip(amount) => reader.skip(amount),
+                    Read::Expect(expected) => {
+                        if reader.expect(&expected).is_err() {
+                            prop_assert_eq!(reader.index, before);
+                        }
+                    }
+                }
+                prop_assert!(reader.index <= reader.len());
+                prop_assert_eq!(reader.peek(), reader.buffer.get(reader.index).copied());
+            }
+        }
+    }
+}